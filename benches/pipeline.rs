@@ -0,0 +1,70 @@
+// Throughput of the import pipeline on synthetic exports.
+//
+// BENCH_EVENTS sets the dataset size (default 1,000,000):
+//     BENCH_EVENTS=100000 cargo bench
+
+use std::path::Path;
+
+use amplitude_things::generate;
+use amplitude_things::sink::sqlite::{RawJson, SqliteSink};
+use amplitude_things::sink::write_parsed_items;
+use amplitude_things::{decompress_files, parse_json_objects_in_dir, ParseOptions};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use tempfile::tempdir;
+
+fn pipeline(c: &mut Criterion) {
+    let events: u64 = std::env::var("BENCH_EVENTS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1_000_000);
+
+    let dir = tempdir().unwrap();
+    let compressed_dir = dir.path().join("compressed");
+    let unzipped_dir = dir.path().join("data");
+    std::fs::create_dir_all(&compressed_dir).unwrap();
+    generate::run(&generate::synthetic(
+        &compressed_dir.join("synthetic.json.gz"),
+        events / 100,
+        events,
+    ))
+    .unwrap();
+    decompress_files(&compressed_dir, &unzipped_dir).unwrap();
+    let items = parse_json_objects_in_dir(&unzipped_dir, &ParseOptions::default()).unwrap();
+
+    let mut group = c.benchmark_group("pipeline");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(events));
+
+    group.bench_function("parse", |b| {
+        b.iter(|| parse_json_objects_in_dir(&unzipped_dir, &ParseOptions::default()).unwrap())
+    });
+
+    group.bench_function("sqlite_insert", |b| {
+        b.iter_batched(
+            || {
+                let db_dir = tempdir().unwrap();
+                let sink = open_sink(db_dir.path());
+                (db_dir, sink)
+            },
+            |(_db_dir, mut sink)| write_parsed_items(&mut sink, &items, &[]).unwrap(),
+            BatchSize::PerIteration,
+        )
+    });
+
+    // Re-importing an already imported window: every row is a duplicate
+    let db_dir = tempdir().unwrap();
+    let mut sink = open_sink(db_dir.path());
+    write_parsed_items(&mut sink, &items, &[]).unwrap();
+    group.bench_function("sqlite_duplicates", |b| {
+        b.iter(|| write_parsed_items(&mut sink, &items, &[]).unwrap())
+    });
+
+    group.finish();
+}
+
+fn open_sink(dir: &Path) -> SqliteSink {
+    SqliteSink::open(dir.join("bench.sqlite"), "bench", RawJson::Keep).unwrap()
+}
+
+criterion_group!(benches, pipeline);
+criterion_main!(benches);
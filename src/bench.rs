@@ -0,0 +1,86 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::Result as AnyhowResult;
+use tempfile::tempdir;
+
+use crate::generate;
+use crate::sink::sqlite::{RawJson, SqliteSink};
+use crate::sink::write_parsed_items;
+use crate::{decompress_files, parse_json_objects_in_dir, ParseOptions};
+
+#[derive(clap::Args, Debug)]
+pub struct BenchArgs {
+    /// Number of synthetic events to push through the pipeline
+    #[arg(long, default_value_t = 1_000_000)]
+    events: u64,
+
+    /// Number of distinct synthetic users
+    #[arg(long, default_value_t = 10_000)]
+    users: u64,
+}
+
+// Times each pipeline stage on a synthetic dataset and prints events per second
+pub fn run(args: &BenchArgs) -> AnyhowResult<()> {
+    let dir = tempdir()?;
+    let compressed_dir = dir.path().join("compressed");
+    let unzipped_dir = dir.path().join("data");
+    std::fs::create_dir_all(&compressed_dir)?;
+
+    let mut timings = Vec::new();
+    let mut time = |stage: &'static str, f: &mut dyn FnMut() -> AnyhowResult<()>| {
+        let started = Instant::now();
+        let result = f();
+        timings.push((stage, started.elapsed()));
+        result
+    };
+
+    let export = compressed_dir.join("synthetic.json.gz");
+    time("generate", &mut || {
+        generate::run(&generate::synthetic(&export, args.users, args.events))
+    })?;
+    time("decompress", &mut || {
+        decompress_files(&compressed_dir, &unzipped_dir)?;
+        Ok(())
+    })?;
+
+    let mut items = Vec::new();
+    time("parse", &mut || {
+        items = parse_json_objects_in_dir(&unzipped_dir, &ParseOptions::default())?;
+        Ok(())
+    })?;
+
+    let mut sink = SqliteSink::open(dir.path().join("bench.sqlite"), "bench", RawJson::Keep)?;
+    time("sqlite insert", &mut || {
+        write_parsed_items(&mut sink, &items, &[])?;
+        Ok(())
+    })?;
+    time("sqlite re-insert (all duplicates)", &mut || {
+        write_parsed_items(&mut sink, &items, &[])?;
+        Ok(())
+    })?;
+
+    println!();
+    for (stage, elapsed) in &timings {
+        println!("{}", format_timing(stage, *elapsed, items.len()));
+    }
+    report_size(&dir.path().join("bench.sqlite"))
+}
+
+fn format_timing(stage: &str, elapsed: Duration, events: usize) -> String {
+    format!(
+        "{stage:<36} {:>8.2}s {:>12.0} events/s",
+        elapsed.as_secs_f64(),
+        events as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+    )
+}
+
+fn report_size(db_path: &Path) -> AnyhowResult<()> {
+    let bytes = std::fs::metadata(db_path)?.len();
+    println!(
+        "{:<36} {:>8.1} MB",
+        "sqlite file size",
+        bytes as f64 / 1_048_576.0
+    );
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use signal_hook::consts::{SIGINT, SIGTERM};
+
+use crate::error::Error;
+
+// Set by the first Ctrl-C or SIGTERM; work checks it between units it can stop after
+static REQUESTED: LazyLock<Arc<AtomicBool>> = LazyLock::new(Arc::default);
+
+// Makes the first Ctrl-C (or SIGTERM) ask running work to stop at the next safe point,
+// where transactions are committed and temporary directories removed; a second one
+// exits at once with status 130.
+pub fn install() -> io::Result<()> {
+    for signal in [SIGINT, SIGTERM] {
+        // Registered first, so it only fires once the flag is already set
+        signal_hook::flag::register_conditional_shutdown(signal, 130, Arc::clone(&REQUESTED))?;
+        signal_hook::flag::register(signal, Arc::clone(&REQUESTED))?;
+    }
+    Ok(())
+}
+
+// Asks running work to stop at its next safe point, as the first Ctrl-C does; for the
+// TUI, whose raw mode delivers Ctrl-C as a key press instead of SIGINT
+pub fn request() {
+    REQUESTED.store(true, Ordering::SeqCst);
+}
+
+// Lets work started after an earlier cancellation run again
+pub fn reset() {
+    REQUESTED.store(false, Ordering::SeqCst);
+}
+
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+// Fails with Error::Cancelled once cancellation was requested
+pub fn check() -> Result<(), Error> {
+    if requested() {
+        return Err(Error::Cancelled);
+    }
+    Ok(())
+}
+
+// Sleeps for `duration` unless cancelled first; returns whether it was cancelled
+pub fn sleep(duration: Duration) -> bool {
+    sleep_unless(&REQUESTED, duration)
+}
+
+fn sleep_unless(flag: &AtomicBool, duration: Duration) -> bool {
+    let until = Instant::now() + duration;
+    while !flag.load(Ordering::SeqCst) {
+        let left = until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return false;
+        }
+        thread::sleep(left.min(Duration::from_millis(200)));
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Uses its own flag: setting the global one would cancel tests running alongside
+    #[test]
+    fn test_sleep_stops_once_cancelled() {
+        let flag = AtomicBool::new(false);
+        assert!(!sleep_unless(&flag, Duration::from_millis(1)));
+
+        let started = Instant::now();
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                thread::sleep(Duration::from_millis(50));
+                flag.store(true, Ordering::SeqCst);
+            });
+            assert!(sleep_unless(&flag, Duration::from_secs(60)));
+        });
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(check().is_ok());
+    }
+}
@@ -0,0 +1,238 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result as AnyhowResult};
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Timelike, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::json;
+
+use crate::sink::EventSink;
+use crate::windows::{self, parse_export_hour, EXPORT_HOUR_FORMAT};
+use crate::{cancel, manifest, notify, open_sink, progress, status_server, SyncArgs};
+
+#[derive(clap::Args, Debug)]
+pub struct DaemonArgs {
+    /// How often to sync, e.g. 15m, 1h, 1d
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "1h")]
+    every: Duration,
+
+    /// Hours to stay behind the current time; exports trail ingestion by a couple of hours
+    #[arg(long, default_value_t = 3)]
+    lag_hours: i64,
+
+    /// First hour (YYYYMMDDTHH) to sync when the project has no watermark yet
+    #[arg(long)]
+    initial_start: Option<String>,
+
+    /// Address to serve GET /health and /metrics on, e.g. 127.0.0.1:9090
+    #[arg(long)]
+    health_addr: Option<SocketAddr>,
+
+    /// Also append status lines to this file, rotating it when it grows too large
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+
+    /// Size at which the log file is rotated
+    #[arg(long, default_value_t = 10 * 1024 * 1024)]
+    log_max_bytes: u64,
+
+    /// Number of rotated log files to keep
+    #[arg(long, default_value_t = 5)]
+    log_keep: usize,
+
+    /// Run a single cycle and exit, for use from cron
+    #[arg(long)]
+    once: bool,
+}
+
+struct Health {
+    last_success: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    watermark: Option<String>,
+}
+
+static HEALTH: Mutex<Health> = Mutex::new(Health {
+    last_success: None,
+    last_error: None,
+    watermark: None,
+});
+
+// Syncs every hour between the stored watermark and now - lag, forever
+pub fn run(args: &SyncArgs, options: &DaemonArgs) -> AnyhowResult<()> {
+    if let Some(path) = &options.log_file {
+        progress::log_to_file(path, options.log_max_bytes, options.log_keep)?;
+    }
+    if let Some(addr) = options.health_addr {
+        status_server::spawn(addr, route)?;
+        progress::info(format!(
+            "Serving http://{addr}/health and http://{addr}/metrics"
+        ));
+    }
+
+    let db_path = args
+        .layout
+        .db_path(args.project_id.as_deref().unwrap_or_default());
+    let conn = Connection::open(&db_path)?;
+    ensure_schema(&conn)?;
+    let mut sink = open_sink(args, &db_path)?;
+
+    loop {
+        let result = run_cycle(args, options, &conn, sink.as_mut());
+        {
+            let mut health = HEALTH.lock().unwrap();
+            match &result {
+                Ok(()) => {
+                    health.last_success = Some(Utc::now());
+                    health.last_error = None;
+                }
+                Err(e) => health.last_error = Some(format!("{e:#}")),
+            }
+        }
+        if let Err(e) = &result {
+            progress::error(format!("Sync cycle failed: {e:#}"));
+        }
+
+        if options.once {
+            return result;
+        }
+        if cancel::requested() || cancel::sleep(options.every) {
+            progress::info("Daemon stopped");
+            return Ok(());
+        }
+    }
+}
+
+fn run_cycle(
+    args: &SyncArgs,
+    options: &DaemonArgs,
+    conn: &Connection,
+    sink: &mut dyn EventSink,
+) -> AnyhowResult<()> {
+    let project_id = args
+        .project_id
+        .as_deref()
+        .ok_or_else(|| anyhow!("--project-id is required"))?;
+
+    let start = match read_watermark(conn, project_id)? {
+        Some(watermark) => parse_export_hour(&watermark)? + TimeDelta::hours(1),
+        None => parse_export_hour(options.initial_start.as_deref().ok_or_else(|| {
+            anyhow!("No watermark for project {project_id} yet; pass --initial-start")
+        })?)?,
+    };
+    let now = Utc::now().naive_utc() - TimeDelta::hours(options.lag_hours);
+    let end = now
+        .with_minute(0)
+        .and_then(|t| t.with_second(0))
+        .and_then(|t| t.with_nanosecond(0))
+        .unwrap_or(now)
+        - TimeDelta::hours(1);
+
+    // Everything before `start` is already in the database
+    record_lag(start);
+
+    if start > end {
+        progress::info(format!(
+            "Up to date through {}",
+            end.format(EXPORT_HOUR_FORMAT)
+        ));
+        return Ok(());
+    }
+
+    let (start, end) = (
+        start.format(EXPORT_HOUR_FORMAT).to_string(),
+        end.format(EXPORT_HOUR_FORMAT).to_string(),
+    );
+    progress::info(format!("Syncing {project_id} {start}..{end}"));
+
+    // Each cycle starts from a clean slate so only this range's files are parsed; with
+    // --window each window also cleans up after itself, as in a one-off sync
+    remove_intermediates(args, &start, &end)?;
+    // The counters keep running across cycles for /metrics, so report this cycle's share
+    let before = progress::COUNTERS.snapshot();
+    let outcome = windows::sync_range(args, sink, &start, &end).and_then(|()| {
+        Ok(manifest::record_synced_range(
+            conn, project_id, &start, &end,
+        )?)
+    });
+    remove_intermediates(args, &start, &end)?;
+    let run = format!("Daemon sync of {project_id} {start}..{end}");
+    let error = outcome.as_ref().err().map(|e| format!("{e:#}"));
+    let counters = progress::COUNTERS.snapshot().since(&before);
+    notify::send_summary(&args.notify, &args.http, &run, &counters, error.as_deref());
+    outcome?;
+
+    write_watermark(conn, project_id, &end)?;
+    record_lag(parse_export_hour(&end)? + TimeDelta::hours(1));
+    HEALTH.lock().unwrap().watermark = Some(end);
+    Ok(())
+}
+
+// Publishes how far behind now the mirror is, given the end of the newest imported hour
+fn record_lag(imported_through: NaiveDateTime) {
+    progress::SYNC_LAG_SECONDS.store(
+        (Utc::now().naive_utc() - imported_through).num_seconds(),
+        Ordering::Relaxed,
+    );
+}
+
+fn route(path: &str) -> Option<status_server::Response> {
+    if path != "/health" {
+        return progress::metrics_route(path);
+    }
+    let health = HEALTH.lock().unwrap();
+    let body = json!({
+        "status": if health.last_error.is_none() { "ok" } else { "failing" },
+        "last_success": health.last_success.map(|t| t.to_rfc3339()),
+        "last_error": health.last_error,
+        "watermark": health.watermark,
+    });
+    let status = if health.last_error.is_none() {
+        200
+    } else {
+        503
+    };
+    Some((status, "application/json", format!("{body}\n")))
+}
+
+fn ensure_schema(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS sync_watermarks (
+            project_id TEXT PRIMARY KEY,
+            synced_through TEXT NOT NULL,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );
+        ",
+    )
+}
+
+// Last export hour (YYYYMMDDTHH, inclusive) fully imported for a project
+fn read_watermark(conn: &Connection, project_id: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT synced_through FROM sync_watermarks WHERE project_id = ?1",
+        params![project_id],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+fn write_watermark(conn: &Connection, project_id: &str, hour: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO sync_watermarks (project_id, synced_through) VALUES (?1, ?2)
+         ON CONFLICT (project_id) DO UPDATE SET synced_through = ?2, updated_at = CURRENT_TIMESTAMP",
+        params![project_id, hour],
+    )?;
+    Ok(())
+}
+
+// Removes the downloaded archive and extracted directories left by sync_window
+fn remove_intermediates(args: &SyncArgs, start: &str, end: &str) -> AnyhowResult<()> {
+    crate::remove_intermediates(args, start, end)?;
+    if args.no_clean {
+        return Ok(());
+    }
+    windows::remove_archive(args, start, end)
+}
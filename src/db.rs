@@ -0,0 +1,308 @@
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result as AnyhowResult};
+use chrono::NaiveDateTime;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, Connection};
+use tempfile::NamedTempFile;
+
+use crate::fs_util::{self, sanitize_filename};
+use crate::sink::sqlite::raw_archive_path;
+use crate::user_merges;
+
+#[derive(clap::Subcommand, Debug)]
+pub enum DbCommand {
+    /// Delete rows orphaned by purged events, then VACUUM and report the space reclaimed
+    Compact {
+        /// SQLite database to compact, relative to --workdir unless absolute
+        /// [default: --db-name]
+        #[arg(long)]
+        db: Option<PathBuf>,
+    },
+    /// Write stored events back out as export files: gzipped JSONL, one file per
+    /// project and hour, named like Amplitude's (`187520_2025-01-31_5#0.json.gz`)
+    ExportJsonl {
+        /// SQLite database to read, relative to --workdir unless absolute
+        /// [default: --db-name]
+        #[arg(long)]
+        db: Option<PathBuf>,
+
+        /// Directory the export files are written to
+        #[arg(long, default_value = "export")]
+        out: PathBuf,
+
+        /// Only export this project's events
+        #[arg(long)]
+        project_id: Option<String>,
+    },
+    /// Rebuild user_merges, the amplitude_ids Amplitude merged into each user's, from
+    /// stored events; the merged_events view applies it
+    UserMerges {
+        /// SQLite database to update, relative to --workdir unless absolute
+        /// [default: --db-name]
+        #[arg(long)]
+        db: Option<PathBuf>,
+    },
+}
+
+impl DbCommand {
+    pub fn db(&self) -> Option<&Path> {
+        match self {
+            DbCommand::Compact { db }
+            | DbCommand::ExportJsonl { db, .. }
+            | DbCommand::UserMerges { db } => db.as_deref(),
+        }
+    }
+}
+
+// `db` is the command's --db resolved against the layout
+pub fn run(command: &DbCommand, db: &Path) -> AnyhowResult<()> {
+    match command {
+        DbCommand::Compact { .. } => compact(db),
+        DbCommand::ExportJsonl {
+            out, project_id, ..
+        } => export_jsonl(db, out, project_id.as_deref()),
+        DbCommand::UserMerges { .. } => {
+            if !db.exists() {
+                bail!("{} does not exist", db.display());
+            }
+            let merges = user_merges::derive(&mut Connection::open(db)?)?;
+            println!("user_merges: {merges} merged amplitude_ids");
+            Ok(())
+        }
+    }
+}
+
+// Side tables keyed by event uuid, with the column holding it
+const EVENT_CHILDREN: [(&str, &str); 4] = [
+    ("event_groups", "event_uuid"),
+    ("user_properties_history", "uuid"),
+    ("duplicate_events", "duplicate_of"),
+    ("amplitude_events_fts", "uuid"),
+];
+
+fn compact(db_path: &Path) -> AnyhowResult<()> {
+    let before = fs::metadata(db_path)?.len();
+    let conn = Connection::open(db_path)?;
+
+    for (table, column) in EVENT_CHILDREN {
+        if !table_exists(&conn, table)? {
+            continue;
+        }
+        let deleted = conn.execute(
+            &format!(
+                "DELETE FROM {table} WHERE {column} NOT IN (SELECT uuid FROM amplitude_events)"
+            ),
+            [],
+        )?;
+        println!("{table}: deleted {deleted} orphaned rows");
+    }
+
+    // Raw JSON kept aside by --raw-json archive
+    let archive = raw_archive_path(db_path);
+    let archive_before = fs::metadata(&archive).map(|m| m.len()).ok();
+    if archive_before.is_some() {
+        conn.execute(
+            "ATTACH DATABASE ?1 AS raw",
+            params![archive.to_string_lossy()],
+        )?;
+        let deleted = conn.execute(
+            "DELETE FROM raw.amplitude_raw_json WHERE uuid NOT IN (SELECT uuid FROM main.amplitude_events)",
+            [],
+        )?;
+        println!("raw.amplitude_raw_json: deleted {deleted} orphaned rows");
+        conn.execute_batch("VACUUM raw")?;
+    }
+
+    println!("Vacuuming {}...", db_path.display());
+    conn.execute_batch("VACUUM")?;
+    drop(conn);
+
+    if let Some(archive_before) = archive_before {
+        report(&archive, archive_before)?;
+    }
+    report(db_path, before)
+}
+
+// Events go to the hour they were exported in, or the hour of their event_time when
+// export_hour is unknown. Raw JSON is read from wherever --raw-json put it.
+fn export_jsonl(db_path: &Path, out: &Path, project_id: Option<&str>) -> AnyhowResult<()> {
+    if !db_path.exists() {
+        bail!("{} does not exist", db_path.display());
+    }
+    let conn = Connection::open(db_path)?;
+    let archive = raw_archive_path(db_path);
+    let archived = if archive.exists() {
+        conn.execute(
+            "ATTACH DATABASE ?1 AS raw",
+            params![archive.to_string_lossy()],
+        )?;
+        "(SELECT raw_json FROM raw.amplitude_raw_json r WHERE r.uuid = e.uuid)"
+    } else {
+        "NULL"
+    };
+    fs::create_dir_all(out)?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT e.project_id,
+                strftime('%Y-%m-%d %H:00:00', COALESCE(e.export_hour, e.event_time)) AS hour,
+                e.raw_json, {archived}
+         FROM amplitude_events e
+         WHERE ?1 IS NULL OR e.project_id = ?1
+         ORDER BY e.project_id, hour, e.event_time, e.uuid"
+    ))?;
+    let mut rows = stmt.query(params![project_id])?;
+
+    // Each file is written under a temporary name and renamed once complete
+    let mut current: Option<(String, String, PathBuf, GzEncoder<BufWriter<NamedTempFile>>)> = None;
+    let (mut files, mut events, mut missing) = (0, 0, 0);
+    while let Some(row) = rows.next()? {
+        let project: String = row.get(0)?;
+        let hour: String = row.get(1)?;
+        let Some(raw_json) = raw_json(row.get(2)?, row.get(3)?)? else {
+            missing += 1;
+            continue;
+        };
+
+        if current
+            .as_ref()
+            .is_none_or(|(p, h, _, _)| *p != project || *h != hour)
+        {
+            if let Some((_, _, path, writer)) = current.take() {
+                finish_export_file(writer, &path)?;
+            }
+            let path = out.join(export_file_name(&project, &hour)?);
+            let file = BufWriter::new(fs_util::temp_file_for(&path)?);
+            let writer = GzEncoder::new(file, Compression::default());
+            current = Some((project, hour, path, writer));
+            files += 1;
+        }
+        if let Some((_, _, _, writer)) = &mut current {
+            writer.write_all(&raw_json)?;
+            writer.write_all(b"\n")?;
+        }
+        events += 1;
+    }
+    if let Some((_, _, path, writer)) = current {
+        finish_export_file(writer, &path)?;
+    }
+
+    println!(
+        "Wrote {events} events to {files} files in {}",
+        out.display()
+    );
+    if missing > 0 {
+        println!("Skipped {missing} events whose raw JSON was not stored (--raw-json drop)");
+    }
+    Ok(())
+}
+
+fn finish_export_file(
+    writer: GzEncoder<BufWriter<NamedTempFile>>,
+    path: &Path,
+) -> AnyhowResult<()> {
+    let file = writer.finish()?.into_inner().map_err(|e| e.into_error())?;
+    fs_util::persist(file, path)?;
+    Ok(())
+}
+
+// An event's original JSON from amplitude_events.raw_json (plain or zstd) or the archive
+pub fn raw_json(stored: SqlValue, archived: Option<String>) -> AnyhowResult<Option<Vec<u8>>> {
+    Ok(match (stored, archived) {
+        (SqlValue::Blob(compressed), _) => Some(zstd::decode_all(compressed.as_slice())?),
+        (SqlValue::Text(text), _) if !text.is_empty() => Some(text.into_bytes()),
+        (_, Some(text)) if !text.is_empty() => Some(text.into_bytes()),
+        _ => None,
+    })
+}
+
+// Export layout name for a project and hour; databases from before projects use project 0
+fn export_file_name(project_id: &str, hour: &str) -> AnyhowResult<String> {
+    let hour = NaiveDateTime::parse_from_str(hour, "%Y-%m-%d %H:%M:%S")?;
+    let project_id = if project_id.is_empty() {
+        "0".to_string()
+    } else {
+        sanitize_filename(project_id)
+    };
+    Ok(format!(
+        "{project_id}_{}#0.json.gz",
+        hour.format("%Y-%m-%d_%-H")
+    ))
+}
+
+fn report(path: &Path, before: u64) -> AnyhowResult<()> {
+    let after = fs::metadata(path)?.len();
+    println!(
+        "{}: {} -> {} ({} reclaimed)",
+        path.display(),
+        format_size(before),
+        format_size(after),
+        format_size(before.saturating_sub(after))
+    );
+    Ok(())
+}
+
+pub fn table_exists(conn: &Connection, table: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        params![table],
+        |row| row.get(0),
+    )
+}
+
+fn format_size(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / 1_048_576.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::sqlite::{RawJson, SqliteSink};
+    use crate::sink::write_parsed_items;
+    use crate::test_support::parsed_item;
+    use crate::ParsedItem;
+    use chrono::{TimeZone, Utc};
+    use flate2::read::GzDecoder;
+    use std::fs::File;
+    use std::io::Read;
+    use tempfile::tempdir;
+
+    fn event(uuid: &str, hour: u32) -> ParsedItem {
+        ParsedItem {
+            event_time: Utc.with_ymd_and_hms(2025, 1, 31, hour, 30, 0).unwrap(),
+            raw_json: format!("{{\"uuid\":\"{uuid}\"}}"),
+            source_file: "synthetic.json".to_string(),
+            ..parsed_item(uuid)
+        }
+    }
+
+    #[test]
+    fn test_exports_one_gzip_file_per_hour() {
+        let dir = tempdir().unwrap();
+        let db = dir.path().join("test.sqlite");
+        let mut sink = SqliteSink::open(&db, "187520", RawJson::Compress).unwrap();
+        let items = [event("a", 5), event("b", 5), event("c", 23)];
+        write_parsed_items(&mut sink, &items, &[]).unwrap();
+        drop(sink);
+
+        let out = dir.path().join("export");
+        export_jsonl(&db, &out, None).unwrap();
+
+        let read = |name: &str| {
+            let mut contents = String::new();
+            GzDecoder::new(File::open(out.join(name)).unwrap())
+                .read_to_string(&mut contents)
+                .unwrap();
+            contents
+        };
+        assert_eq!(
+            read("187520_2025-01-31_5#0.json.gz"),
+            "{\"uuid\":\"a\"}\n{\"uuid\":\"b\"}\n"
+        );
+        assert_eq!(read("187520_2025-01-31_23#0.json.gz"), "{\"uuid\":\"c\"}\n");
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, IsTerminal};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result as AnyhowResult};
+use clap::ColorChoice;
+use serde_json::Value;
+use tempfile::tempdir;
+
+use crate::decompress_files;
+use crate::fs_util::sorted_entries;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(clap::Args, Debug)]
+pub struct DiffEventsArgs {
+    /// Two export files (plain, .gz, .zst or .zip) whose first events are compared
+    #[arg(num_args = 2, value_names = ["FILE_A", "FILE_B"], required_unless_present = "dir")]
+    files: Vec<PathBuf>,
+
+    /// Look the events up by --id in this directory of export files instead
+    #[arg(long, conflicts_with = "files", requires = "ids")]
+    dir: Option<PathBuf>,
+
+    /// With --dir, the $insert_id or uuid of each event; given once, the first two
+    /// events carrying it are compared
+    #[arg(long = "id", num_args = 1..=2, value_name = "ID")]
+    ids: Vec<String>,
+
+    /// Color the diff: auto (when printing to a terminal), always or never
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+}
+
+#[derive(Debug, PartialEq)]
+enum Difference {
+    Removed(String, Value),
+    Added(String, Value),
+    Changed(String, Value, Value),
+}
+
+// An event and where it was found, e.g. 187520_2025-01-31_5#0.json:12
+struct Located {
+    event: Value,
+    location: String,
+}
+
+// Prints a field-by-field diff of two events; nested fields are shown as dotted paths
+pub fn run(options: &DiffEventsArgs) -> AnyhowResult<()> {
+    let (a, b) = match &options.dir {
+        Some(dir) => find_by_id(dir, &options.ids)?,
+        None => (
+            first_event(&options.files[0])?,
+            first_event(&options.files[1])?,
+        ),
+    };
+    let color = match options.color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+    };
+    let paint = |code: &str, text: String| {
+        if color {
+            format!("{code}{text}{RESET}")
+        } else {
+            text
+        }
+    };
+
+    println!("{}", paint(RED, format!("--- {}", a.location)));
+    println!("{}", paint(GREEN, format!("+++ {}", b.location)));
+    let differences = differences(&a.event, &b.event);
+    for difference in &differences {
+        let line = match difference {
+            Difference::Removed(path, value) => paint(RED, format!("- {path}: {value}")),
+            Difference::Added(path, value) => paint(GREEN, format!("+ {path}: {value}")),
+            Difference::Changed(path, before, after) => {
+                paint(YELLOW, format!("~ {path}: {before} -> {after}"))
+            }
+        };
+        println!("{line}");
+    }
+    if differences.is_empty() {
+        println!("The events are identical");
+    }
+    Ok(())
+}
+
+fn differences(a: &Value, b: &Value) -> Vec<Difference> {
+    let (mut a_fields, mut b_fields) = (BTreeMap::new(), BTreeMap::new());
+    flatten("", a, &mut a_fields);
+    flatten("", b, &mut b_fields);
+
+    let mut differences = Vec::new();
+    for (path, before) in &a_fields {
+        match b_fields.remove(path) {
+            None => differences.push(Difference::Removed(path.clone(), before.clone())),
+            Some(after) if after != *before => {
+                differences.push(Difference::Changed(path.clone(), before.clone(), after))
+            }
+            Some(_) => {}
+        }
+    }
+    differences.extend(
+        b_fields
+            .into_iter()
+            .map(|(path, value)| Difference::Added(path, value)),
+    );
+    // Keep each field's line in one place regardless of which side it is on
+    differences.sort_by(|x, y| path_of(x).cmp(path_of(y)));
+    differences
+}
+
+fn path_of(difference: &Difference) -> &str {
+    match difference {
+        Difference::Removed(path, _)
+        | Difference::Added(path, _)
+        | Difference::Changed(path, _, _) => path,
+    }
+}
+
+// Leaf values by dotted path; arrays and empty objects are compared whole
+fn flatten(prefix: &str, value: &Value, fields: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                flatten(&path, value, fields);
+            }
+        }
+        _ => {
+            fields.insert(prefix.to_string(), value.clone());
+        }
+    }
+}
+
+fn first_event(path: &Path) -> AnyhowResult<Located> {
+    let name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let source = tempdir()?;
+    fs::copy(path, source.path().join(&name))?;
+    let mut found = None;
+    scan_events(source.path(), |event| {
+        found = Some(event);
+        false
+    })?;
+    match found {
+        Some(found) => Ok(found),
+        None => bail!("{} holds no JSON events", path.display()),
+    }
+}
+
+fn find_by_id(dir: &Path, ids: &[String]) -> AnyhowResult<(Located, Located)> {
+    let wanted: Vec<&str> = match ids {
+        [id] => vec![id, id],
+        [a, b] => vec![a, b],
+        _ => bail!("--dir needs one or two --id values"),
+    };
+    let matches = |event: &Value, id: &str| {
+        ["$insert_id", "uuid"]
+            .iter()
+            .any(|field| event.get(field).and_then(Value::as_str) == Some(id))
+    };
+
+    let mut found: Vec<Option<Located>> = vec![None, None];
+    scan_events(dir, |located| {
+        if let Some(slot) =
+            (0..2).find(|&i| found[i].is_none() && matches(&located.event, wanted[i]))
+        {
+            found[slot] = Some(located);
+        }
+        found.iter().any(Option::is_none)
+    })?;
+    match (found.remove(0), found.remove(0)) {
+        (Some(a), Some(b)) => Ok((a, b)),
+        _ if wanted[0] == wanted[1] => {
+            bail!(
+                "Fewer than two events with id {} in {}",
+                wanted[0],
+                dir.display()
+            )
+        }
+        _ => bail!(
+            "Events {} and {} were not both found in {}",
+            wanted[0],
+            wanted[1],
+            dir.display()
+        ),
+    }
+}
+
+// Calls `visit` with each event in the export files of `dir` until it returns false
+fn scan_events(dir: &Path, mut visit: impl FnMut(Located) -> bool) -> AnyhowResult<()> {
+    let staging = tempdir()?;
+    decompress_files(dir, staging.path())?;
+    for path in sorted_entries(staging.path())? {
+        let file_name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        for (index, line) in BufReader::new(File::open(&path)?).lines().enumerate() {
+            let Ok(event) = serde_json::from_str::<Value>(&line?) else {
+                continue;
+            };
+            let location = format!("{file_name}:{}", index + 1);
+            if !visit(Located { event, location }) {
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_differences_by_dotted_path() {
+        let a = json!({
+            "uuid": "u-1",
+            "event_type": "Viewed",
+            "event_properties": { "plan": "free", "tags": ["a"] },
+            "city": "Oslo",
+        });
+        let b = json!({
+            "uuid": "u-1",
+            "event_type": "Viewed",
+            "event_properties": { "plan": "pro", "tags": ["a"], "seats": 3 },
+        });
+        assert_eq!(
+            differences(&a, &b),
+            [
+                Difference::Removed("city".into(), json!("Oslo")),
+                Difference::Changed("event_properties.plan".into(), json!("free"), json!("pro")),
+                Difference::Added("event_properties.seats".into(), json!(3)),
+            ]
+        );
+        assert!(differences(&a, &a).is_empty());
+    }
+}
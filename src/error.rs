@@ -0,0 +1,125 @@
+use std::io;
+
+type Source = Box<dyn std::error::Error + Send + Sync>;
+
+/// Failure categories callers may want to tell apart.
+///
+/// Most functions still return `anyhow::Result`; these errors travel inside it
+/// (or inside an `io::Error`) and are found again with [`Error::find`].
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Fetching an export from Amplitude failed
+    #[error("Download of {range} failed: {source}")]
+    Download {
+        range: String,
+        status: Option<u16>,
+        #[source]
+        source: Source,
+    },
+
+    /// A line could not be parsed under --parse-mode strict
+    #[error("{file}:{line}: {message}")]
+    Parse {
+        file: String,
+        line: usize,
+        message: String,
+    },
+
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+
+    /// A destination refused data: a sink server or --upload-to storage
+    #[error("Upload failed{}: {response}", code.map(|code| format!(" with HTTP {code}")).unwrap_or_default())]
+    Upload { code: Option<u16>, response: String },
+
+    /// Flags or settings that do not work together
+    #[error("{0}")]
+    Config(String),
+
+    /// Stopped by Ctrl-C or SIGTERM
+    #[error("Cancelled")]
+    Cancelled,
+}
+
+impl Error {
+    pub fn download(range: &str, source: anyhow::Error) -> Self {
+        let status = source
+            .downcast_ref::<reqwest::Error>()
+            .and_then(|e| e.status())
+            .map(|status| status.as_u16());
+        Self::Download {
+            range: range.to_string(),
+            status,
+            source: source.into(),
+        }
+    }
+
+    // The first Error in a chain, including ones wrapped in an io::Error
+    pub fn find(error: &anyhow::Error) -> Option<&Self> {
+        error.chain().find_map(|cause| {
+            cause.downcast_ref::<Self>().or_else(|| {
+                cause
+                    .downcast_ref::<io::Error>()
+                    .and_then(|e| e.get_ref())
+                    .and_then(|inner| inner.downcast_ref::<Self>())
+            })
+        })
+    }
+
+    // What the user can do about it, printed by the CLI after the error
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::Download {
+                status: Some(401 | 403),
+                ..
+            } => Some("Check --api-key and the secret key; they must belong to the same project"),
+            Self::Download {
+                status: Some(429), ..
+            } => Some("Amplitude is rate limiting; retry later or use a smaller --window"),
+            Self::Download { status: None, .. } => {
+                Some("Check the network connection, --proxy and --ca-bundle")
+            }
+            Self::Download { .. } => None,
+            Self::Parse { .. } => Some(
+                "Use --parse-mode lenient to skip malformed lines or collect-errors to keep them",
+            ),
+            Self::Sqlite(_) => Some(
+                "Check that the database is not locked by another program and the disk is not full",
+            ),
+            Self::Upload { .. } => Some("Check the destination's credentials and permissions"),
+            Self::Config(_) => None,
+            Self::Cancelled => Some(
+                "Files imported before the interruption are kept; run the same command again to resume",
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finds_errors_through_io_and_anyhow() {
+        let parse = Error::Parse {
+            file: "a.json".into(),
+            line: 3,
+            message: "Missing uuid".into(),
+        };
+        let wrapped = anyhow::Error::new(io::Error::new(io::ErrorKind::InvalidData, parse))
+            .context("Failed to import");
+        let found = Error::find(&wrapped).unwrap();
+        assert_eq!(found.to_string(), "a.json:3: Missing uuid");
+        assert!(found.hint().unwrap().contains("--parse-mode"));
+
+        let upload = Error::Upload {
+            code: Some(500),
+            response: "Code: 60. Table does not exist".into(),
+        };
+        assert_eq!(
+            upload.to_string(),
+            "Upload failed with HTTP 500: Code: 60. Table does not exist"
+        );
+        assert!(Error::find(&anyhow::anyhow!("other")).is_none());
+    }
+}
@@ -0,0 +1,95 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result as AnyhowResult;
+
+use crate::progress;
+
+/// Keeps only the event types of interest while parsing, counting what it skips.
+#[derive(Debug, Default)]
+pub struct EventFilter {
+    include: Option<HashSet<String>>,
+    exclude: HashSet<String>,
+    skipped: RefCell<BTreeMap<String, u64>>,
+}
+
+impl EventFilter {
+    // Reads event type lists, one name per line; blank lines and `#` comments are ignored
+    pub fn from_files(include: Option<&Path>, exclude: Option<&Path>) -> AnyhowResult<Self> {
+        Ok(Self {
+            include: include.map(read_event_types).transpose()?,
+            exclude: exclude
+                .map(read_event_types)
+                .transpose()?
+                .unwrap_or_default(),
+            skipped: RefCell::default(),
+        })
+    }
+
+    pub fn allows(&self, event_type: &str) -> bool {
+        let allowed = self
+            .include
+            .as_ref()
+            .is_none_or(|include| include.contains(event_type))
+            && !self.exclude.contains(event_type);
+        if !allowed {
+            *self
+                .skipped
+                .borrow_mut()
+                .entry(event_type.to_string())
+                .or_default() += 1;
+        }
+        allowed
+    }
+
+    // Logs how many events of each type were left out
+    pub fn report(&self) {
+        let skipped = self.skipped.borrow();
+        if skipped.is_empty() {
+            return;
+        }
+        progress::info(format!(
+            "Skipped {} events of {} filtered event types:",
+            skipped.values().sum::<u64>(),
+            skipped.len()
+        ));
+        for (event_type, count) in skipped.iter() {
+            progress::info(format!("  {count:>10}  {event_type}"));
+        }
+    }
+}
+
+fn read_event_types(path: &Path) -> AnyhowResult<HashSet<String>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_include_then_exclude() {
+        let include = NamedTempFile::new().unwrap();
+        fs::write(include.path(), "# wanted\nSigned Up\nPurchased\n\n").unwrap();
+        let exclude = NamedTempFile::new().unwrap();
+        fs::write(exclude.path(), "Purchased\n").unwrap();
+        let filter = EventFilter::from_files(Some(include.path()), Some(exclude.path())).unwrap();
+
+        assert!(filter.allows("Signed Up"));
+        assert!(!filter.allows("Purchased"));
+        assert!(!filter.allows("Page Viewed"));
+        assert!(!filter.allows("Page Viewed"));
+        assert_eq!(
+            *filter.skipped.borrow(),
+            BTreeMap::from([("Page Viewed".to_string(), 2), ("Purchased".to_string(), 1)])
+        );
+    }
+}
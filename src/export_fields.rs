@@ -0,0 +1,121 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::progress;
+
+// Top-level fields of the documented Amplitude export format
+const KNOWN_FIELDS: [&str; 55] = [
+    "$insert_id",
+    "$insert_key",
+    "$schema",
+    "adid",
+    "amplitude_attribution_ids",
+    "amplitude_event_type",
+    "amplitude_id",
+    "app",
+    "city",
+    "client_event_time",
+    "client_upload_time",
+    "country",
+    "data",
+    "data_type",
+    "device_brand",
+    "device_carrier",
+    "device_family",
+    "device_id",
+    "device_manufacturer",
+    "device_model",
+    "device_type",
+    "dma",
+    "event_id",
+    "event_properties",
+    "event_time",
+    "event_type",
+    "global_user_properties",
+    "group_properties",
+    "groups",
+    "idfa",
+    "ip_address",
+    "is_attribution_event",
+    "language",
+    "library",
+    "location_lat",
+    "location_lng",
+    "os_name",
+    "os_version",
+    "partner_id",
+    "paying",
+    "plan",
+    "platform",
+    "processed_time",
+    "region",
+    "sample_rate",
+    "server_received_time",
+    "server_upload_time",
+    "session_id",
+    "source_id",
+    "start_version",
+    "user_creation_time",
+    "user_id",
+    "user_properties",
+    "uuid",
+    "version_name",
+];
+
+/// Counts top-level export fields this tool doesn't know about, so format
+/// changes on Amplitude's side get noticed.
+///
+/// Nothing is dropped: unknown fields stay in `raw_json` like every other field.
+#[derive(Debug, Default)]
+pub struct NewFields {
+    seen: RefCell<BTreeMap<String, u64>>,
+}
+
+impl NewFields {
+    pub fn observe(&self, event: &Value) {
+        let Some(fields) = event.as_object() else {
+            return;
+        };
+        for name in fields.keys() {
+            if !KNOWN_FIELDS.contains(&name.as_str()) {
+                *self.seen.borrow_mut().entry(name.clone()).or_default() += 1;
+            }
+        }
+    }
+
+    // Logs each unknown field with the number of events carrying it
+    pub fn report(&self) {
+        let seen = self.seen.borrow();
+        if seen.is_empty() {
+            return;
+        }
+        let fields: Vec<String> = seen
+            .iter()
+            .map(|(name, count)| format!("{name} ({count})"))
+            .collect();
+        progress::info(format!(
+            "New export fields seen, kept in raw_json: {}",
+            fields.join(", ")
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_counts_only_unknown_fields() {
+        let new_fields = NewFields::default();
+        new_fields.observe(&json!({ "uuid": "u1", "event_type": "a", "ai_context": {} }));
+        new_fields.observe(&json!({ "uuid": "u2", "ai_context": {}, "consent": true }));
+
+        assert_eq!(
+            *new_fields.seen.borrow(),
+            BTreeMap::from([("ai_context".to_string(), 2), ("consent".to_string(), 1)])
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use chrono::{NaiveDate, NaiveDateTime};
+
+/// What an export file's name says about its contents, e.g.
+/// `187520_2025-01-31_5#0.json.gz` holds project 187520's events for
+/// 2025-01-31 05:00-06:00 UTC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportFileName {
+    pub project_id: String,
+    pub hour: NaiveDateTime,
+}
+
+impl ExportFileName {
+    // Parses a file or zip entry name; None for names not following the export layout
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.rsplit('/').next()?;
+        let (project_id, rest) = name.split_once('_')?;
+        let (date, rest) = rest.split_once('_')?;
+        let hour = rest.split(['#', '.']).next()?;
+
+        if project_id.is_empty() || !project_id.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let hour = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .ok()?
+            .and_hms_opt(hour.parse().ok()?, 0, 0)?;
+        Some(Self {
+            project_id: project_id.to_string(),
+            hour,
+        })
+    }
+
+    // Start of the hour the file covers, as stored in `export_hour` columns
+    pub fn export_hour(&self) -> String {
+        self.hour.format("%Y-%m-%d %H:%M:%S").to_string()
+    }
+}
+
+// `export_hour` column value for a file name, if it follows the export layout
+pub fn export_hour(name: &str) -> Option<String> {
+    ExportFileName::parse(name).map(|parsed| parsed.export_hour())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_export_names() {
+        let parsed = ExportFileName::parse("187520/187520_2025-01-31_5#0.json.gz").unwrap();
+        assert_eq!(parsed.project_id, "187520");
+        assert_eq!(parsed.export_hour(), "2025-01-31 05:00:00");
+        assert_eq!(
+            export_hour("187520_2025-01-31_23#12.json").as_deref(),
+            Some("2025-01-31 23:00:00")
+        );
+
+        assert_eq!(ExportFileName::parse("synthetic.json.gz"), None);
+        assert_eq!(ExportFileName::parse("187520_2025-01-31_24#0.json"), None);
+    }
+}
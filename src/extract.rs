@@ -0,0 +1,169 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Component, Path};
+
+use zip::ZipArchive;
+
+/// How the export files are arranged inside an Export API archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveLayout {
+    /// Files at the top level: `187520_2025-01-01_0#0.json.gz`
+    Flat,
+    /// Files in one folder per project: `187520/187520_2025-01-01_0#0.json.gz`
+    ProjectFolder,
+    /// Files in per-day (or deeper) folders: `187520/2025-01-01/187520_2025-01-01_0#0.json.gz`
+    NestedFolders,
+    /// Files at different depths
+    Mixed,
+}
+
+impl fmt::Display for ArchiveLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ArchiveLayout::Flat => "flat",
+            ArchiveLayout::ProjectFolder => "project folder",
+            ArchiveLayout::NestedFolders => "nested folders",
+            ArchiveLayout::Mixed => "mixed folders",
+        })
+    }
+}
+
+// Extracts every export file in `archive` directly into `dst`, whatever folders the
+// archive keeps them in; returns the layout found and the number of files. Files with
+// the same name in different folders keep their folder names as a prefix.
+pub fn extract_archive(archive: &Path, dst: &Path) -> io::Result<(ArchiveLayout, usize)> {
+    let mut zip = ZipArchive::new(File::open(archive)?).map_err(invalid_data)?;
+    fs::create_dir_all(dst)?;
+
+    let mut depths = HashSet::new();
+    let mut names = HashSet::new();
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i).map_err(invalid_data)?;
+        let Some(path) = entry.enclosed_name() else {
+            continue;
+        };
+        let folders: Vec<String> = path
+            .parent()
+            .into_iter()
+            .flat_map(Path::components)
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+                _ => None,
+            })
+            .collect();
+        let Some(file_name) = path.file_name() else {
+            continue;
+        };
+        let file_name = file_name.to_string_lossy().to_string();
+        // Folders, and files macOS adds when an archive is re-zipped by hand
+        let macos_extra = file_name.starts_with('.') || folders.iter().any(|f| f == "__MACOSX");
+        if entry.is_dir() || macos_extra {
+            continue;
+        }
+
+        let name = if names.contains(&file_name) {
+            format!("{}_{file_name}", folders.join("_"))
+        } else {
+            file_name
+        };
+        io::copy(&mut entry, &mut File::create(dst.join(&name))?)?;
+        names.insert(name);
+        depths.insert(folders.len());
+    }
+
+    let layout = match depths.iter().collect::<Vec<_>>().as_slice() {
+        [] => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} holds no export files", archive.display()),
+            ))
+        }
+        [0] => ArchiveLayout::Flat,
+        [1] => ArchiveLayout::ProjectFolder,
+        [_] => ArchiveLayout::NestedFolders,
+        _ => ArchiveLayout::Mixed,
+    };
+    Ok((layout, names.len()))
+}
+
+fn invalid_data(error: zip::result::ZipError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_util::sorted_entries;
+    use std::io::Write;
+    use tempfile::tempdir;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    fn extracted(entries: &[&str]) -> (ArchiveLayout, Vec<String>) {
+        let dir = tempdir().unwrap();
+        let archive = dir.path().join("export.zip");
+        let mut zip = ZipWriter::new(File::create(&archive).unwrap());
+        for entry in entries {
+            if let Some(folder) = entry.strip_suffix('/') {
+                zip.add_directory(folder, SimpleFileOptions::default())
+                    .unwrap();
+            } else {
+                zip.start_file(*entry, SimpleFileOptions::default())
+                    .unwrap();
+                zip.write_all(entry.as_bytes()).unwrap();
+            }
+        }
+        zip.finish().unwrap();
+
+        let dst = dir.path().join("files");
+        let (layout, count) = extract_archive(&archive, &dst).unwrap();
+        let names: Vec<String> = sorted_entries(&dst)
+            .unwrap()
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names.len(), count);
+        (layout, names)
+    }
+
+    #[test]
+    fn test_flat_layout() {
+        let (layout, names) = extracted(&["1_2025-01-01_0#0.json.gz", "1_2025-01-01_1#0.json.gz"]);
+        assert_eq!(layout, ArchiveLayout::Flat);
+        assert_eq!(
+            names,
+            ["1_2025-01-01_0#0.json.gz", "1_2025-01-01_1#0.json.gz"]
+        );
+    }
+
+    #[test]
+    fn test_project_folder_layout() {
+        let (layout, names) = extracted(&["187520/", "187520/1_2025-01-01_0#0.json.gz"]);
+        assert_eq!(layout, ArchiveLayout::ProjectFolder);
+        assert_eq!(names, ["1_2025-01-01_0#0.json.gz"]);
+    }
+
+    #[test]
+    fn test_per_day_folders_layout() {
+        let (layout, names) = extracted(&[
+            "187520/2025-01-01/1_2025-01-01_0#0.json.gz",
+            "187520/2025-01-02/1_2025-01-02_0#0.json.gz",
+            "187520/2025-01-02/.DS_Store",
+            "__MACOSX/187520/._1_2025-01-02_0#0.json.gz",
+        ]);
+        assert_eq!(layout, ArchiveLayout::NestedFolders);
+        assert_eq!(
+            names,
+            ["1_2025-01-01_0#0.json.gz", "1_2025-01-02_0#0.json.gz"]
+        );
+    }
+
+    #[test]
+    fn test_mixed_layout_keeps_same_named_files_apart() {
+        let (layout, names) = extracted(&["events.json.gz", "2025-01-02/events.json.gz"]);
+        assert_eq!(layout, ArchiveLayout::Mixed);
+        assert_eq!(names, ["2025-01-02_events.json.gz", "events.json.gz"]);
+    }
+}
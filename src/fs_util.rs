@@ -0,0 +1,171 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+use tempfile::NamedTempFile;
+
+// Longest name produced; well under the 255 bytes most filesystems allow, leaving room
+// for a suffix such as _2025-01-31_23#0.json.gz
+const MAX_NAME_BYTES: usize = 120;
+
+// Device names Windows reserves with or without an extension
+const RESERVED: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+// Turns a value from the data (a project id, an insert_id, ...) into a file name that is
+// valid on Windows and Unix alike. Separators and characters Windows rejects become `_`,
+// reserved device names get a `_` prefix, and names too long are cut and given a hash
+// of the original so different long values stay different.
+pub fn sanitize_filename(value: &str) -> String {
+    let mut name: String = value
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    // Windows drops trailing dots and spaces, which would merge distinct names
+    let kept = name.trim_end_matches(['.', ' ']).len();
+    name.replace_range(kept.., &"_".repeat(name.len() - kept));
+    if name.is_empty() {
+        return "_".to_string();
+    }
+
+    let stem = name.split('.').next().unwrap_or_default();
+    if RESERVED
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+    {
+        name.insert(0, '_');
+    }
+
+    if name.len() > MAX_NAME_BYTES {
+        let hash = format!("{:x}", Sha256::digest(value.as_bytes()));
+        let mut cut = MAX_NAME_BYTES - 17;
+        while !name.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        name = format!("{}-{}", &name[..cut], &hash[..16]);
+    }
+    name
+}
+
+// Paths in a directory sorted by name, so whatever is produced from them (parse order,
+// JSONL output, error files, reports) does not depend on the filesystem's listing order
+pub fn sorted_entries(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    paths.sort();
+    Ok(paths)
+}
+
+// A temporary file beside `path`, so persisting it is a rename within one filesystem
+pub fn temp_file_for(path: &Path) -> io::Result<NamedTempFile> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    NamedTempFile::new_in(dir)
+}
+
+// Flushes a finished temporary file to disk and renames it to `path`, so readers find
+// either the previous file or the complete new one, never a partial write
+pub fn persist(file: NamedTempFile, path: &Path) -> io::Result<()> {
+    file.as_file().sync_all()?;
+    file.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+// Atomically writes `contents` and then `<path>.sha256` in sha256sum's format; a reader
+// (or `sha256sum -c`) detects a truncated file, or one the checksum no longer belongs to
+// after a crash between the two writes
+pub fn write_checksummed(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = temp_file_for(path)?;
+    file.write_all(contents)?;
+    persist(file, path)?;
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let sidecar = checksum_path(path);
+    let mut file = temp_file_for(&sidecar)?;
+    writeln!(file, "{:x}  {name}", Sha256::digest(contents))?;
+    persist(file, &sidecar)
+}
+
+fn checksum_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".sha256");
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename("187520"), "187520");
+        assert_eq!(sanitize_filename("a/b\\c:d"), "a_b_c_d");
+        assert_eq!(sanitize_filename("con"), "_con");
+        assert_eq!(sanitize_filename("NUL.json"), "_NUL.json");
+        assert_eq!(sanitize_filename("console"), "console");
+        assert_eq!(sanitize_filename("trailing. "), "trailing__");
+        assert_eq!(sanitize_filename(""), "_");
+
+        // Properties over a spread of awkward inputs: always a single, valid, bounded
+        // component, and distinct long inputs stay distinct
+        let pieces = [
+            "é", "../", "COM1", ".", " ", "\u{0}", "*", "id-", "日本", "x",
+        ];
+        let mut seen = std::collections::HashMap::new();
+        for seed in 0..2000u64 {
+            let mut value = String::new();
+            let mut state = seed;
+            for _ in 0..(seed % 97) {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                value.push_str(pieces[(state >> 33) as usize % pieces.len()]);
+            }
+            let name = sanitize_filename(&value);
+            assert!(
+                !name.is_empty() && name.len() <= MAX_NAME_BYTES,
+                "{value:?}"
+            );
+            assert!(!name.contains(['/', '\\', ':', '*', '\u{0}']), "{value:?}");
+            assert!(!name.ends_with(['.', ' ']), "{value:?}");
+            let stem = name.split('.').next().unwrap();
+            assert!(
+                !RESERVED.iter().any(|r| stem.eq_ignore_ascii_case(r)),
+                "{value:?}"
+            );
+            if value.len() > MAX_NAME_BYTES {
+                if let Some(other) = seen.insert(name.clone(), value.clone()) {
+                    assert_eq!(other, value);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_checksummed_write_replaces_whole_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("summary.json");
+        fs::write(&path, "stale and much longer than the new contents").unwrap();
+
+        write_checksummed(&path, b"{}\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{}\n");
+        let sidecar = fs::read_to_string(dir.path().join("summary.json.sha256")).unwrap();
+        assert_eq!(
+            sidecar,
+            format!("{:x}  summary.json\n", Sha256::digest(b"{}\n"))
+        );
+        // Only the two files remain; the temporary files were renamed into place
+        assert_eq!(sorted_entries(dir.path()).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,253 @@
+use std::fs::File;
+use std::io::{self, BufWriter, IntoInnerError, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result as AnyhowResult};
+use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+use clap::Parser;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde_json::{json, Value};
+
+const EXPORT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.6f";
+
+const PLANS: [(&str, u32); 3] = [("free", 70), ("pro", 25), ("enterprise", 5)];
+const PLATFORMS: [(&str, u32); 3] = [("Web", 60), ("iOS", 25), ("Android", 15)];
+const COUNTRIES: [(&str, u32); 5] = [
+    ("United States", 40),
+    ("Germany", 15),
+    ("United Kingdom", 15),
+    ("India", 20),
+    ("Brazil", 10),
+];
+
+#[derive(clap::Args, Debug)]
+pub struct GenerateArgs {
+    /// File to write; a .gz extension gzips it like a real export file
+    #[arg(long, default_value = "synthetic.json.gz")]
+    output: PathBuf,
+
+    /// Number of distinct users
+    #[arg(long, default_value_t = 1_000)]
+    users: u64,
+
+    /// Total number of events
+    #[arg(long, default_value_t = 100_000)]
+    events: u64,
+
+    /// First day events fall on (YYYY-MM-DD)
+    #[arg(long, default_value = "2025-01-01")]
+    start: NaiveDate,
+
+    /// Number of days events are spread over
+    #[arg(long, default_value_t = 7)]
+    days: u32,
+
+    /// Average events per session
+    #[arg(long, default_value_t = 8)]
+    session_length: u64,
+
+    /// Event types with relative weights, e.g. "Page Viewed:10,Signed Up:1"
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "Page Viewed:20,Button Clicked:10,Search:5,Added To Cart:3,Purchased:1"
+    )]
+    event_mix: Vec<String>,
+
+    /// Random seed; the same seed and options always produce the same file
+    #[arg(long, default_value_t = 42)]
+    seed: u64,
+}
+
+// Writes synthetic events in the Amplitude export format, one JSON object per line
+pub fn run(args: &GenerateArgs) -> AnyhowResult<()> {
+    let events = synthetic_events(args)?;
+
+    let file = File::create(&args.output)?;
+    if args.output.extension().is_some_and(|e| e == "gz") {
+        // Buffered in front of the encoder, which is slow with many small writes
+        let mut writer = BufWriter::new(GzEncoder::new(file, Compression::default()));
+        write_lines(&mut writer, &events)?;
+        // finish() writes the gzip trailer; dropping the encoder would ignore its errors
+        writer
+            .into_inner()
+            .map_err(IntoInnerError::into_error)?
+            .finish()?
+            .flush()?;
+    } else {
+        let mut writer = BufWriter::new(file);
+        write_lines(&mut writer, &events)?;
+        writer.flush()?;
+    }
+
+    println!(
+        "Wrote {} events for up to {} users to {}",
+        events.len(),
+        args.users,
+        args.output.display()
+    );
+    Ok(())
+}
+
+fn write_lines(writer: &mut impl Write, events: &[Value]) -> io::Result<()> {
+    for event in events {
+        writeln!(writer, "{event}")?;
+    }
+    Ok(())
+}
+
+// Options for `users` users and `events` events written to `output`, everything else default
+pub fn synthetic(output: &Path, users: u64, events: u64) -> GenerateArgs {
+    #[derive(clap::Parser)]
+    struct Defaults {
+        #[command(flatten)]
+        args: GenerateArgs,
+    }
+    let mut args = Defaults::parse_from(["generate"]).args;
+    args.output = output.to_path_buf();
+    args.users = users;
+    args.events = events;
+    args
+}
+
+// Events sorted by time, as they would appear in an export
+fn synthetic_events(args: &GenerateArgs) -> AnyhowResult<Vec<Value>> {
+    let event_mix = parse_event_mix(&args.event_mix)?;
+    if args.users == 0 || args.days == 0 {
+        bail!("--users and --days must be at least 1");
+    }
+
+    let mut rng = SplitMix64(args.seed);
+    let window_start = args.start.and_hms_opt(0, 0, 0).unwrap_or_default();
+    let window_seconds = i64::from(args.days) * 86_400;
+
+    let mut events = Vec::with_capacity(args.events as usize);
+    while (events.len() as u64) < args.events {
+        // Squaring skews activity so a few users produce most events, as in real projects
+        let user = (rng.next_f64().powi(2) * args.users as f64) as u64;
+        let session_start =
+            window_start + TimeDelta::seconds(rng.below(window_seconds as u64) as i64);
+        let session_id = session_start.and_utc().timestamp_millis();
+        let length = 1 + rng.below(args.session_length * 2);
+
+        let mut time = session_start;
+        for _ in 0..length.min(args.events - events.len() as u64) {
+            let event_type = rng.pick(&event_mix);
+            events.push(event(&mut rng, user, event_type, time, session_id));
+            time += TimeDelta::milliseconds(5_000 + rng.below(115_000) as i64);
+        }
+    }
+    events.sort_by(|a, b| a["event_time"].as_str().cmp(&b["event_time"].as_str()));
+    Ok(events)
+}
+
+fn event(
+    rng: &mut SplitMix64,
+    user: u64,
+    event_type: &str,
+    time: NaiveDateTime,
+    session_id: i64,
+) -> Value {
+    let event_time = time.format(EXPORT_TIME_FORMAT).to_string();
+    let upload_time = (time + TimeDelta::milliseconds(rng.below(2_000) as i64))
+        .format(EXPORT_TIME_FORMAT)
+        .to_string();
+
+    // Per-user attributes derive from the user number alone, so they stay consistent
+    let mut user_rng = SplitMix64(user);
+    let plan = user_rng.pick(&PLANS);
+    let platform = user_rng.pick(&PLATFORMS);
+    let country = user_rng.pick(&COUNTRIES);
+
+    let mut properties = json!({ "page": format!("/page/{}", rng.below(50)) });
+    if event_type == "Purchased" || event_type == "Added To Cart" {
+        // Prices cluster low with a long tail
+        let price = ((5.0 + rng.next_f64().powi(3) * 495.0) * 100.0).round() / 100.0;
+        properties = json!({ "price": price, "quantity": 1 + rng.below(3) });
+    }
+
+    json!({
+        "uuid": rng.uuid(),
+        "$insert_id": rng.uuid(),
+        "user_id": format!("user-{user}"),
+        "device_id": format!("device-{user}"),
+        "event_type": event_type,
+        "event_time": event_time,
+        "client_event_time": event_time,
+        "server_upload_time": upload_time,
+        "session_id": session_id,
+        "platform": platform,
+        "country": country,
+        "event_properties": properties,
+        "user_properties": { "plan": plan },
+        "data": { "path": if platform == "Web" { "/" } else { "/batch" } },
+    })
+}
+
+fn parse_event_mix(entries: &[String]) -> AnyhowResult<Vec<(&str, u32)>> {
+    let mut mix = Vec::new();
+    for entry in entries {
+        let (name, weight) = entry.rsplit_once(':').unwrap_or((entry, "1"));
+        let Ok(weight) = weight.trim().parse::<u32>() else {
+            bail!("Invalid weight in --event-mix entry {entry:?}");
+        };
+        mix.push((name.trim(), weight));
+    }
+    if mix.iter().all(|(_, weight)| *weight == 0) {
+        bail!("--event-mix needs at least one event type with a positive weight");
+    }
+    Ok(mix)
+}
+
+// Small deterministic generator; reproducible output matters more here than statistical quality
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        if n == 0 {
+            0
+        } else {
+            self.next_u64() % n
+        }
+    }
+
+    fn pick<'a>(&mut self, weighted: &[(&'a str, u32)]) -> &'a str {
+        let total: u64 = weighted.iter().map(|(_, w)| u64::from(*w)).sum();
+        let mut roll = self.below(total);
+        for (value, weight) in weighted {
+            if roll < u64::from(*weight) {
+                return value;
+            }
+            roll -= u64::from(*weight);
+        }
+        weighted[0].0
+    }
+
+    // Random (version 4) UUID
+    fn uuid(&mut self) -> String {
+        let bytes = (u128::from(self.next_u64()) << 64) | u128::from(self.next_u64());
+        let bytes = (bytes & !(0xf << 76) | (0x4 << 76)) & !(0x3 << 62) | (0x2 << 62);
+        let hex = format!("{bytes:032x}");
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result as AnyhowResult;
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::{Certificate, Method, Proxy, StatusCode};
+
+use crate::progress::{self, bump, COUNTERS};
+
+// Network settings shared by every HTTP client the tool builds.
+//
+// `HTTPS_PROXY`/`HTTP_PROXY`/`NO_PROXY` are honored unless `--proxy` or
+// `--no-proxy` is given.
+#[derive(clap::Args, Debug, Clone)]
+pub struct HttpOptions {
+    /// Total time allowed for a single HTTP request, in seconds
+    #[arg(long, default_value_t = 300)]
+    pub http_timeout_secs: u64,
+
+    /// Time allowed to establish a connection, in seconds
+    #[arg(long, default_value_t = 30)]
+    pub connect_timeout_secs: u64,
+
+    /// Proxy URL for all requests (overrides HTTPS_PROXY/HTTP_PROXY)
+    #[arg(long, conflicts_with = "no_proxy")]
+    pub proxy: Option<String>,
+
+    /// Ignore proxy environment variables and connect directly
+    #[arg(long)]
+    pub no_proxy: bool,
+
+    /// PEM file with extra root certificates to trust (e.g. a corporate CA)
+    #[arg(long)]
+    pub ca_bundle: Option<PathBuf>,
+
+    /// How many times a failed request is retried before giving up
+    #[arg(long, default_value_t = 2)]
+    pub http_retries: u32,
+
+    /// Longest time to keep retrying one request, including the waits between tries
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "10m")]
+    pub http_retry_budget: Duration,
+}
+
+// First wait between tries, doubled after each one up to MAX_BACKOFF
+const BASE_BACKOFF: Duration = Duration::from_secs(2);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+impl HttpOptions {
+    // Builds a blocking client with the configured timeouts, proxy and trust roots
+    pub fn build_client(&self) -> AnyhowResult<Client> {
+        let mut builder = Client::builder()
+            .timeout(Duration::from_secs(self.http_timeout_secs))
+            .connect_timeout(Duration::from_secs(self.connect_timeout_secs));
+
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        } else if self.no_proxy {
+            builder = builder.no_proxy();
+        }
+
+        if let Some(path) = &self.ca_bundle {
+            for cert in Certificate::from_pem_bundle(&fs::read(path)?)? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        Ok(builder.build()?)
+    }
+
+    // Sends a request, retrying connection failures, 429s and 5xx responses with a jittered
+    // exponential backoff. Requests that are not idempotent (POST, PATCH) are only retried
+    // when they never reached the server; use send_retrying for ones that are safe to repeat.
+    pub fn send(&self, request: RequestBuilder) -> AnyhowResult<Response> {
+        let idempotent = request
+            .try_clone()
+            .and_then(|r| r.build().ok())
+            .is_some_and(|r| !matches!(*r.method(), Method::POST | Method::PATCH));
+        self.send_with_retries(request, idempotent)
+    }
+
+    // Like send, also retrying POSTs the caller knows are safe to repeat, e.g. deduplicated inserts
+    pub fn send_retrying(&self, request: RequestBuilder) -> AnyhowResult<Response> {
+        self.send_with_retries(request, true)
+    }
+
+    fn send_with_retries(
+        &self,
+        request: RequestBuilder,
+        idempotent: bool,
+    ) -> AnyhowResult<Response> {
+        let started = Instant::now();
+        let mut attempt = 0;
+        loop {
+            let this_try = request
+                .try_clone()
+                .expect("requests with streaming bodies cannot be retried");
+            let result = this_try.send().and_then(|r| {
+                if r.status() == StatusCode::TOO_MANY_REQUESTS {
+                    bump(&COUNTERS.http_throttled, 1);
+                }
+                r.error_for_status()
+            });
+
+            match result {
+                Ok(response) => return Ok(response),
+                Err(e)
+                    if attempt < self.http_retries
+                        && (e.is_connect() || idempotent && is_retryable(&e))
+                        && started.elapsed() + backoff(attempt) <= self.http_retry_budget =>
+                {
+                    let wait = backoff(attempt);
+                    attempt += 1;
+                    progress::error(format!(
+                        "Request failed ({}), retrying in {} ({}/{})...",
+                        e,
+                        humantime::format_duration(Duration::from_secs(wait.as_secs())),
+                        attempt,
+                        self.http_retries
+                    ));
+                    thread::sleep(wait);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+// Wait before retry number `attempt + 1`: a random point in the upper half of the
+// exponential step, so clients that failed together do not retry in lockstep
+fn backoff(attempt: u32) -> Duration {
+    let step = BASE_BACKOFF
+        .saturating_mul(1 << attempt.min(16))
+        .min(MAX_BACKOFF);
+    let jitter = RandomState::new().build_hasher().finish() % 1000;
+    step / 2 + (step / 2).mul_f64(jitter as f64 / 1000.0)
+}
+
+fn is_retryable(error: &reqwest::Error) -> bool {
+    error.is_timeout()
+        || error.is_connect()
+        || error.status().is_some_and(|status| {
+            status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_grows_with_jitter_up_to_cap() {
+        for attempt in 0..10 {
+            let step = BASE_BACKOFF.saturating_mul(1 << attempt).min(MAX_BACKOFF);
+            let wait = backoff(attempt);
+            assert!(wait >= step / 2 && wait <= step, "{attempt}: {wait:?}");
+        }
+        assert!(backoff(100) <= MAX_BACKOFF);
+    }
+}
@@ -0,0 +1,136 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Result as AnyhowResult};
+use serde_json::Value;
+
+use crate::progress;
+
+// Unmapped ids kept, and listed in the end-of-run report
+const REPORT_EXAMPLES: usize = 10;
+
+// Ids seen without an entry in the mapping: how often, and the first few distinct ones.
+// A large migration can have millions, so they are not all kept.
+#[derive(Debug, Default, PartialEq)]
+struct Unmapped {
+    count: u64,
+    examples: Vec<String>,
+}
+
+/// Rewrites `user_id` and `device_id` while events are parsed, e.g. when
+/// moving events between projects whose identities differ.
+///
+/// Ids found in the mapping file are replaced; others get `prefix` (if any)
+/// and are counted in [`IdMapping::report`], with a few kept as examples.
+#[derive(Debug, Default)]
+pub struct IdMapping {
+    mapping: HashMap<String, String>,
+    prefix: Option<String>,
+    unmapped: RefCell<Unmapped>,
+}
+
+impl IdMapping {
+    // Reads a two-column CSV without header: old id, new id
+    pub fn from_file(path: Option<&Path>, prefix: Option<String>) -> AnyhowResult<Self> {
+        let mut mapping = HashMap::new();
+        if let Some(path) = path {
+            for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let Some((old, new)) = line.split_once(',') else {
+                    bail!(
+                        "{}:{}: expected `old_id,new_id`",
+                        path.display(),
+                        number + 1
+                    );
+                };
+                mapping.insert(old.trim().to_string(), new.trim().to_string());
+            }
+        }
+        Ok(Self {
+            mapping,
+            prefix,
+            unmapped: RefCell::default(),
+        })
+    }
+
+    pub fn apply(&self, event: &mut Value) {
+        for field in ["user_id", "device_id"] {
+            let Some(Value::String(id)) = event.get_mut(field) else {
+                continue;
+            };
+            match self.mapping.get(id.as_str()) {
+                Some(new) => *id = new.clone(),
+                None => {
+                    if !self.mapping.is_empty() {
+                        let mut unmapped = self.unmapped.borrow_mut();
+                        unmapped.count += 1;
+                        let example = format!("{field}={id}");
+                        if unmapped.examples.len() < REPORT_EXAMPLES
+                            && !unmapped.examples.contains(&example)
+                        {
+                            unmapped.examples.push(example);
+                        }
+                    }
+                    if let Some(prefix) = &self.prefix {
+                        id.insert_str(0, prefix);
+                    }
+                }
+            }
+        }
+    }
+
+    // Logs how many ids had no entry in the mapping file
+    pub fn report(&self) {
+        let unmapped = self.unmapped.borrow();
+        if unmapped.count == 0 {
+            return;
+        }
+        progress::error(format!(
+            "{} ids in events had no entry in the id mapping, e.g. {}",
+            unmapped.count,
+            unmapped.examples.join(", ")
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_maps_known_ids_and_prefixes_the_rest() {
+        let file = NamedTempFile::new().unwrap();
+        fs::write(file.path(), "# old,new\nu1, member-1\n").unwrap();
+        let mapping = IdMapping::from_file(Some(file.path()), Some("legacy-".into())).unwrap();
+
+        let mut event = json!({ "user_id": "u1", "device_id": "d1" });
+        mapping.apply(&mut event);
+
+        assert_eq!(
+            event,
+            json!({ "user_id": "member-1", "device_id": "legacy-d1" })
+        );
+        assert_eq!(
+            *mapping.unmapped.borrow(),
+            Unmapped {
+                count: 1,
+                examples: vec!["device_id=d1".to_string()]
+            }
+        );
+
+        // Only a few examples are kept, however many ids are missing
+        for n in 0..100 {
+            mapping.apply(&mut json!({ "device_id": format!("d{n}") }));
+        }
+        let unmapped = mapping.unmapped.borrow();
+        assert_eq!(unmapped.count, 101);
+        assert_eq!(unmapped.examples.len(), REPORT_EXAMPLES);
+    }
+}
@@ -0,0 +1,109 @@
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::ParsedItem;
+
+// `time` values above this are milliseconds, as in newer exports; below are seconds
+const MILLIS_THRESHOLD: f64 = 1e11;
+
+// Maps a raw export event onto the Amplitude columns:
+// distinct_id (or $user_id) -> user_id, $device_id -> device_id, time -> event_time.
+//
+// Mixpanel events have no uuid, so one is derived from what Mixpanel itself
+// deduplicates on (event, distinct_id, time and $insert_id); re-importing an
+// overlapping export skips the events already stored.
+pub fn parse(json: &Value) -> Result<ParsedItem, String> {
+    let event_name = json
+        .get("event")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing event")?
+        .to_string();
+    let properties = json
+        .get("properties")
+        .and_then(|v| v.as_object())
+        .ok_or("Missing properties")?;
+    let property = |name: &str| {
+        properties.get(name).and_then(|v| match v {
+            Value::String(s) if !s.is_empty() => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        })
+    };
+
+    let time = properties
+        .get("time")
+        .and_then(|v| v.as_f64())
+        .ok_or("Missing time")?;
+    let millis = if time >= MILLIS_THRESHOLD {
+        time
+    } else {
+        time * 1000.0
+    };
+    let event_time = DateTime::<Utc>::from_timestamp_millis(millis as i64)
+        .ok_or_else(|| format!("Invalid time {time}"))?;
+
+    let distinct_id = property("distinct_id");
+    let insert_id = property("$insert_id");
+    let uuid = {
+        let key = format!(
+            "{event_name}\n{}\n{}\n{}",
+            distinct_id.as_deref().unwrap_or_default(),
+            millis as i64,
+            insert_id.as_deref().unwrap_or_default()
+        );
+        let digest = format!("{:x}", Sha256::digest(key.as_bytes()));
+        format!("mixpanel-{}", &digest[..32])
+    };
+
+    Ok(ParsedItem {
+        user_id: property("$user_id").or(distinct_id),
+        device_id: property("$device_id"),
+        screen_name: None,
+        event_name,
+        server_event: false,
+        event_time,
+        uuid,
+        raw_json: String::new(),
+        source_file: String::new(),
+        source_line: 0,
+        session_id: None,
+        insert_id,
+        amplitude_id: None,
+        event_id: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_maps_mixpanel_events() {
+        let event = json!({
+            "event": "Signed Up",
+            "properties": {
+                "time": 1738299967,
+                "distinct_id": "u1",
+                "$device_id": "d1",
+                "$insert_id": "abc",
+                "plan": "pro"
+            }
+        });
+        let item = parse(&event).unwrap();
+        assert_eq!(item.event_name, "Signed Up");
+        assert_eq!(item.user_id.as_deref(), Some("u1"));
+        assert_eq!(item.device_id.as_deref(), Some("d1"));
+        assert_eq!(item.insert_id.as_deref(), Some("abc"));
+        assert_eq!(item.event_time.to_rfc3339(), "2025-01-31T05:06:07+00:00");
+        assert!(item.uuid.starts_with("mixpanel-"));
+
+        // Millisecond times and identical events give the same result
+        let mut in_millis = event.clone();
+        in_millis["properties"]["time"] = json!(1738299967000i64);
+        assert_eq!(parse(&in_millis).unwrap().uuid, item.uuid);
+
+        assert!(parse(&json!({ "event": "x", "properties": {} })).is_err());
+    }
+}
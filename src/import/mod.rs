@@ -0,0 +1,101 @@
+use std::path::PathBuf;
+
+use anyhow::Result as AnyhowResult;
+use clap::ValueEnum;
+use tempfile::tempdir;
+
+use crate::error::Error;
+use crate::progress::COUNTERS;
+use crate::{import_export, notify, open_sink, ParsedItem, SyncArgs};
+
+pub mod mixpanel;
+pub mod segment;
+
+/// Layout of the event files being imported.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SourceFormat {
+    /// Amplitude export JSON
+    #[default]
+    Amplitude,
+    /// Mixpanel raw export JSONL (`{"event": ..., "properties": {...}}`)
+    Mixpanel,
+    /// Segment archive ndjson of track, identify, page and screen calls
+    Segment,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ImportArgs {
+    /// Format of the files
+    #[arg(value_enum)]
+    format: SourceFormat,
+
+    /// Directory of .json, .jsonl or .ndjson files, optionally gzip or zstd compressed
+    dir: PathBuf,
+}
+
+// Imports another vendor's event files into the same sink as Amplitude exports
+pub fn run(args: &SyncArgs, options: &ImportArgs) -> AnyhowResult<()> {
+    if options.format != SourceFormat::Amplitude {
+        // These rewrite Amplitude's fields, which other vendors' events do not have
+        let amplitude_only = [
+            ("--transform", args.transform.is_some()),
+            ("--id-map", args.id_map.is_some()),
+            ("--id-prefix", args.id_prefix.is_some()),
+            ("--shift-time", args.shift_time.is_some()),
+        ];
+        if let Some((flag, _)) = amplitude_only.iter().find(|(_, set)| *set) {
+            return Err(Error::Config(format!(
+                "{flag} only applies to Amplitude exports, not to `import {}`",
+                options.format.to_possible_value().unwrap().get_name()
+            ))
+            .into());
+        }
+    }
+    let staging = tempdir()?;
+    let db_path = args
+        .layout
+        .db_path(args.project_id.as_deref().unwrap_or_default());
+    let result = open_sink(args, &db_path).and_then(|mut sink| {
+        import_export(
+            args,
+            sink.as_mut(),
+            &options.dir,
+            &staging.path().join("data"),
+            options.format,
+        )
+    });
+
+    let run = format!(
+        "Import of {} files from {}",
+        options.format.to_possible_value().unwrap().get_name(),
+        options.dir.display()
+    );
+    let error = result.as_ref().err().map(|e| format!("{e:#}"));
+    notify::send_summary(
+        &args.notify,
+        &args.http,
+        &run,
+        &COUNTERS.snapshot(),
+        error.as_deref(),
+    );
+    result
+}
+
+// Turns one line of a non-Amplitude file into an event; the line is kept as raw_json
+pub fn parse_line(
+    format: SourceFormat,
+    line: &str,
+    file_name: &str,
+    line_number: usize,
+) -> Result<ParsedItem, String> {
+    let json = serde_json::from_str(line).map_err(|e| format!("Invalid JSON: {e}"))?;
+    let mut item = match format {
+        SourceFormat::Amplitude => unreachable!("Amplitude lines are parsed by crate::parse_line"),
+        SourceFormat::Mixpanel => mixpanel::parse(&json)?,
+        SourceFormat::Segment => segment::parse(&json)?,
+    };
+    item.raw_json = line.to_string();
+    item.source_file = file_name.to_string();
+    item.source_line = line_number;
+    Ok(item)
+}
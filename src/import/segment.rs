@@ -0,0 +1,100 @@
+use serde_json::Value;
+
+use crate::timestamp::parse_amplitude_time;
+use crate::ParsedItem;
+
+// Maps a Segment archive call onto the Amplitude columns: userId -> user_id,
+// anonymousId -> device_id, messageId -> uuid and timestamp -> event_time.
+//
+// track calls keep their event name, screen and page calls become "Viewed <name>"
+// and identify calls become `$identify` events, whose traits --user-properties
+// replays like Amplitude user_properties.
+pub fn parse(json: &Value) -> Result<ParsedItem, String> {
+    let field = |name: &str| {
+        json.get(name)
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+    };
+
+    let call = field("type").ok_or("Missing type")?;
+    let (event_name, screen_name) = match call.as_str() {
+        "track" => (field("event").ok_or("Missing event")?, None),
+        "identify" => ("$identify".to_string(), None),
+        "screen" | "page" => {
+            let name = field("name");
+            let event_name = match &name {
+                Some(name) => format!("Viewed {name}"),
+                None => format!("Viewed {call}"),
+            };
+            (event_name, name.filter(|_| call == "screen"))
+        }
+        other => (other.to_string(), None),
+    };
+
+    let message_id = field("messageId").ok_or("Missing messageId")?;
+    let timestamp = json
+        .get("timestamp")
+        .or_else(|| json.get("receivedAt"))
+        .ok_or("Missing timestamp")?;
+    let event_time = parse_amplitude_time(timestamp).map_err(|e| e.to_string())?;
+
+    Ok(ParsedItem {
+        user_id: field("userId"),
+        device_id: field("anonymousId"),
+        screen_name,
+        event_name,
+        server_event: false,
+        event_time,
+        uuid: format!("segment-{message_id}"),
+        raw_json: String::new(),
+        source_file: String::new(),
+        source_line: 0,
+        session_id: None,
+        insert_id: Some(message_id),
+        amplitude_id: None,
+        event_id: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_maps_segment_calls() {
+        let track = parse(&json!({
+            "type": "track",
+            "event": "Order Completed",
+            "userId": "u1",
+            "anonymousId": "a1",
+            "messageId": "ajs-1",
+            "timestamp": "2025-01-31T05:06:07.250Z",
+            "properties": { "total": 10 }
+        }))
+        .unwrap();
+        assert_eq!(track.event_name, "Order Completed");
+        assert_eq!(track.user_id.as_deref(), Some("u1"));
+        assert_eq!(track.device_id.as_deref(), Some("a1"));
+        assert_eq!(track.uuid, "segment-ajs-1");
+        assert_eq!(
+            track.event_time.to_rfc3339(),
+            "2025-01-31T05:06:07.250+00:00"
+        );
+
+        let screen = parse(&json!({
+            "type": "screen",
+            "name": "Home",
+            "anonymousId": "a1",
+            "messageId": "m2",
+            "timestamp": "2025-01-31T05:06:08Z"
+        }))
+        .unwrap();
+        assert_eq!(screen.event_name, "Viewed Home");
+        assert_eq!(screen.screen_name.as_deref(), Some("Home"));
+
+        let identify = json!({ "type": "identify", "userId": "u1", "messageId": "m3" });
+        assert_eq!(parse(&identify).err().as_deref(), Some("Missing timestamp"));
+    }
+}
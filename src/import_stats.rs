@@ -0,0 +1,203 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::Result as AnyhowResult;
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::{params, Connection};
+
+use crate::progress;
+use crate::report_tz::ReportTimezone;
+use crate::sink::sqlite::has_column;
+use crate::ParsedItem;
+
+// Event types listed in the printed summary; the table has all of them
+const SUMMARY_EVENT_TYPES: usize = 10;
+
+/// What one run read from the export, for checking completeness against the Amplitude
+/// UI, and how much of it was new to the database.
+#[derive(Debug, Default)]
+pub struct ImportStats {
+    // Events parsed, whether or not they were stored already
+    events: usize,
+    inserted: usize,
+    users: usize,
+    earliest: Option<DateTime<Utc>>,
+    latest: Option<DateTime<Utc>>,
+    // (event type, day) -> (parsed events, distinct users)
+    by_type_and_day: BTreeMap<(String, NaiveDate), (usize, usize)>,
+}
+
+impl ImportStats {
+    // Counts per event type and day of the parsed `items`, of which `inserted` were new,
+    // with days starting at midnight in `timezone`
+    pub fn from_items(items: &[ParsedItem], inserted: usize, timezone: &ReportTimezone) -> Self {
+        let mut users = HashSet::new();
+        let mut by_type_and_day: BTreeMap<(&str, NaiveDate), (usize, HashSet<&str>)> =
+            BTreeMap::new();
+        for item in items {
+            let entry = by_type_and_day
+                .entry((&item.event_name, timezone.date(&item.event_time)))
+                .or_default();
+            entry.0 += 1;
+            if let Some(user_id) = &item.user_id {
+                entry.1.insert(user_id);
+                users.insert(user_id.as_str());
+            }
+        }
+
+        Self {
+            events: items.len(),
+            inserted,
+            users: users.len(),
+            earliest: items.iter().map(|item| item.event_time).min(),
+            latest: items.iter().map(|item| item.event_time).max(),
+            by_type_and_day: by_type_and_day
+                .into_iter()
+                .map(|((event_type, day), (events, users))| {
+                    ((event_type.to_string(), day), (events, users.len()))
+                })
+                .collect(),
+        }
+    }
+
+    pub fn print(&self) {
+        let range = match (self.earliest, self.latest) {
+            (Some(earliest), Some(latest)) => format!(", {earliest} to {latest}"),
+            _ => String::new(),
+        };
+        progress::info(format!(
+            "Parsed {} events from {} distinct users{range}; {} inserted, {} duplicates skipped",
+            self.events,
+            self.users,
+            self.inserted,
+            self.events - self.inserted
+        ));
+
+        let mut by_type: BTreeMap<&str, usize> = BTreeMap::new();
+        for ((event_type, _), (events, _)) in &self.by_type_and_day {
+            *by_type.entry(event_type).or_default() += events;
+        }
+        let mut by_type: Vec<_> = by_type.into_iter().collect();
+        by_type.sort_by_key(|&(_, events)| Reverse(events));
+        for (event_type, events) in by_type.iter().take(SUMMARY_EVENT_TYPES) {
+            progress::info(format!("  {events:>10}  {event_type}"));
+        }
+        if by_type.len() > SUMMARY_EVENT_TYPES {
+            progress::info(format!(
+                "  ... {} more event types, per day in import_stats",
+                by_type.len() - SUMMARY_EVENT_TYPES
+            ));
+        }
+    }
+
+    // Adds this run to import_runs, with its per-type per-day counts in import_stats
+    pub fn write(&self, conn: &mut Connection, project_id: &str) -> AnyhowResult<()> {
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS import_runs (
+                run_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                project_id TEXT NOT NULL,
+                finished_at DATETIME NOT NULL,
+                events INTEGER NOT NULL,
+                distinct_users INTEGER NOT NULL,
+                earliest_event_time DATETIME,
+                latest_event_time DATETIME,
+                duplicates_skipped INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS import_stats (
+                run_id INTEGER NOT NULL REFERENCES import_runs (run_id),
+                event_type TEXT NOT NULL,
+                day DATE NOT NULL,
+                events INTEGER NOT NULL,
+                distinct_users INTEGER NOT NULL,
+                PRIMARY KEY (run_id, event_type, day)
+            );
+            ",
+        )?;
+        // Added once `events` was documented as parsed rather than stored events
+        if !has_column(conn, "import_runs", "events_inserted")? {
+            conn.execute_batch("ALTER TABLE import_runs ADD COLUMN events_inserted INTEGER")?;
+        }
+
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO import_runs (project_id, finished_at, events, distinct_users, earliest_event_time, latest_event_time, duplicates_skipped, events_inserted)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                project_id,
+                Utc::now().to_rfc3339(),
+                self.events,
+                self.users,
+                self.earliest.map(|time| time.to_rfc3339()),
+                self.latest.map(|time| time.to_rfc3339()),
+                self.events - self.inserted,
+                self.inserted,
+            ],
+        )?;
+        let run_id = tx.last_insert_rowid();
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO import_stats (run_id, event_type, day, events, distinct_users)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for ((event_type, day), (events, users)) in &self.by_type_and_day {
+                stmt.execute(params![run_id, event_type, day.to_string(), events, users])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::parsed_item;
+    use chrono::TimeZone;
+
+    fn event(user: &str, event_name: &str, day: u32) -> ParsedItem {
+        ParsedItem {
+            user_id: Some(user.to_string()),
+            event_name: event_name.to_string(),
+            event_time: Utc.with_ymd_and_hms(2024, 1, day, 12, 0, 0).unwrap(),
+            ..parsed_item(&format!("{user}-{event_name}-{day}"))
+        }
+    }
+
+    #[test]
+    fn test_counts_per_type_and_day() {
+        let items = [
+            event("a", "Viewed", 1),
+            event("b", "Viewed", 1),
+            event("a", "Viewed", 2),
+            event("a", "Bought", 2),
+        ];
+        let stats = ImportStats::from_items(&items, 3, &ReportTimezone::default());
+        assert_eq!(stats.users, 2);
+        assert_eq!(stats.events - stats.inserted, 1);
+        assert_eq!(stats.earliest, Some(items[0].event_time));
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        stats.write(&mut conn, "123").unwrap();
+        let rows: Vec<(String, String, usize, usize)> = conn
+            .prepare(
+                "SELECT event_type, day, events, distinct_users FROM import_stats ORDER BY 1, 2",
+            )
+            .unwrap()
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("Bought".into(), "2024-01-02".into(), 1, 1),
+                ("Viewed".into(), "2024-01-01".into(), 2, 2),
+                ("Viewed".into(), "2024-01-02".into(), 1, 1),
+            ]
+        );
+    }
+}
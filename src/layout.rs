@@ -0,0 +1,144 @@
+use std::path::{Path, PathBuf};
+
+use crate::fs_util::sanitize_filename;
+
+/// Where a sync keeps its archive, extracted files, database and lock.
+///
+/// Names may use `{project}`; the archive and extraction directory, which only live
+/// for one export window, may also use `{start}` and `{end}`. Giving each project its
+/// own `--workdir`, e.g. `jobs/{project}`, lets syncs of different projects run side
+/// by side.
+#[derive(clap::Args, Debug, Clone)]
+pub struct LayoutOptions {
+    /// Directory holding everything below, and the lock file
+    #[arg(long, env = "AMPLITUDE_WORKDIR", default_value = ".")]
+    workdir: String,
+
+    /// Name of the downloaded export archive inside --workdir
+    #[arg(
+        long,
+        env = "AMPLITUDE_ARCHIVE_NAME",
+        default_value = "amplitude_export.zip"
+    )]
+    archive_name: String,
+
+    /// Directory inside --workdir that export files are decompressed into
+    #[arg(long, env = "AMPLITUDE_EXTRACT_DIR", default_value = "data")]
+    extract_dir: String,
+
+    /// Name of the SQLite database inside --workdir
+    #[arg(
+        long,
+        env = "AMPLITUDE_DB_NAME",
+        default_value = "amplitude_data.sqlite"
+    )]
+    db_name: String,
+}
+
+impl LayoutOptions {
+    pub fn workdir(&self, project: &str) -> PathBuf {
+        PathBuf::from(expand(&self.workdir, project, None))
+    }
+
+    pub fn db_path(&self, project: &str) -> PathBuf {
+        self.workdir(project)
+            .join(expand(&self.db_name, project, None))
+    }
+
+    // Points db_path at `path`, which is relative to --workdir unless absolute
+    pub fn set_db(&mut self, path: &Path) {
+        self.db_name = path.to_string_lossy().into_owned();
+    }
+
+    pub fn archive(&self, project: &str, start: &str, end: &str) -> PathBuf {
+        self.workdir(project)
+            .join(expand(&self.archive_name, project, Some((start, end))))
+    }
+
+    pub fn extract_dir(&self, project: &str, start: &str, end: &str) -> PathBuf {
+        self.workdir(project)
+            .join(expand(&self.extract_dir, project, Some((start, end))))
+    }
+
+    // Where the export files from a window's archive are extracted, whatever its layout.
+    // It is removed after each window, so the project id cannot lead outside the workdir.
+    pub fn project_dir(&self, project: &str) -> PathBuf {
+        self.workdir(project).join(sanitize_filename(project))
+    }
+}
+
+// Parses --project-id, which names directories the sync creates and removes
+pub fn parse_project_id(value: &str) -> Result<String, String> {
+    if matches!(value.trim(), "" | "." | "..") {
+        return Err(format!("{value:?} is not a project id"));
+    }
+    Ok(value.to_string())
+}
+
+// Fills in a name template; without a window, {start} and {end} are left as written
+fn expand(template: &str, project: &str, window: Option<(&str, &str)>) -> String {
+    let name = template.replace("{project}", &sanitize_filename(project));
+    match window {
+        Some((start, end)) => name.replace("{start}", start).replace("{end}", end),
+        None => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        layout: LayoutOptions,
+    }
+
+    #[test]
+    fn test_templated_layout() {
+        let defaults = Cli::parse_from(["test"]).layout;
+        assert_eq!(
+            defaults.db_path("187520"),
+            PathBuf::from("./amplitude_data.sqlite")
+        );
+        assert_eq!(
+            defaults.archive("187520", "20250101T00", "20250101T23"),
+            PathBuf::from("./amplitude_export.zip")
+        );
+
+        let layout = Cli::parse_from([
+            "test",
+            "--workdir",
+            "jobs/{project}",
+            "--archive-name",
+            "{project}_{start}_{end}.zip",
+            "--db-name",
+            "events.sqlite",
+        ])
+        .layout;
+        assert_eq!(
+            layout.archive("187520", "20250101T00", "20250101T23"),
+            PathBuf::from("jobs/187520/187520_20250101T00_20250101T23.zip")
+        );
+        assert_eq!(
+            layout.db_path("187520"),
+            PathBuf::from("jobs/187520/events.sqlite")
+        );
+        assert_eq!(
+            layout.project_dir("187520"),
+            PathBuf::from("jobs/187520/187520")
+        );
+    }
+
+    #[test]
+    fn test_project_dir_stays_inside_the_workdir() {
+        let layout = Cli::parse_from(["test", "--workdir", "jobs"]).layout;
+        assert_eq!(layout.project_dir("../etc"), PathBuf::from("jobs/.._etc"));
+        assert_eq!(layout.project_dir("a/b"), PathBuf::from("jobs/a_b"));
+        for id in ["", ".", ".."] {
+            assert!(parse_project_id(id).is_err());
+        }
+        assert_eq!(parse_project_id("187520").unwrap(), "187520");
+    }
+}
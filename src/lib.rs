@@ -0,0 +1,1455 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Cursor, Read, Write};
+use std::path::Path;
+
+use chrono::{NaiveDateTime, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use flate2::read::GzDecoder;
+use rusqlite::Connection;
+use serde_json::Value;
+
+use anyhow::{bail, Context, Result as AnyhowResult};
+use std::io::copy;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+mod bench;
+mod cancel;
+mod daemon;
+mod db;
+mod diff_events;
+pub mod error;
+mod event_filter;
+mod export_fields;
+mod export_name;
+mod extract;
+mod fs_util;
+pub mod generate;
+mod http;
+mod id_map;
+mod import;
+mod import_stats;
+mod layout;
+mod lock;
+mod manifest;
+mod mirror;
+mod notify;
+mod outcome;
+mod plan;
+mod preflight;
+mod progress;
+mod quality;
+mod reconcile;
+mod remote;
+mod report_tz;
+mod sample;
+mod schema_diff;
+mod secrets;
+mod serve;
+mod sessions;
+pub mod sink;
+mod state;
+mod status_server;
+#[cfg(any(test, feature = "test_support"))]
+pub mod test_support;
+mod time_shift;
+mod timeline;
+mod timestamp;
+mod transform;
+mod transform_cmd;
+mod tui;
+mod user_merges;
+mod user_properties;
+mod watch;
+mod windows;
+
+use crate::error::Error;
+use crate::event_filter::EventFilter;
+use crate::export_fields::NewFields;
+use crate::http::HttpOptions;
+use crate::id_map::IdMapping;
+use crate::import::SourceFormat;
+use crate::import_stats::ImportStats;
+use crate::layout::LayoutOptions;
+use crate::lock::StateLock;
+use crate::notify::NotifyOptions;
+use crate::outcome::OutcomeOptions;
+use crate::preflight::PreflightOptions;
+use crate::progress::{bump, COUNTERS};
+use crate::remote::StorageOptions;
+use crate::report_tz::ReportTimezone;
+use crate::sample::SampleOptions;
+use crate::secrets::SecretOptions;
+use crate::sessions::SessionOptions;
+use crate::sink::clickhouse::ClickhouseSink;
+use crate::sink::jsonl::{self, ChunkOptions, JsonlSink};
+use crate::sink::postgres::PostgresSink;
+use crate::sink::sqlite::{self, Dedup, RawJson, SqliteSink};
+use crate::sink::{write_parsed_items, EventSink};
+use crate::time_shift::TimeShift;
+use crate::transform::EventTransform;
+use crate::windows::Granularity;
+
+// Downloads one export range to `output`; false when Amplitude has no data for it
+fn start_amplitude_download(
+    http: &HttpOptions,
+    api_key: &str,
+    secret_key: &str,
+    start: &str,
+    end: &str,
+    output: &str,
+) -> Result<bool, Error> {
+    fetch_export(http, api_key, secret_key, start, end, output)
+        .map_err(|e| Error::download(&format!("{start}..{end}"), e))
+}
+
+fn fetch_export(
+    http: &HttpOptions,
+    api_key: &str,
+    secret_key: &str,
+    start: &str,
+    end: &str,
+    output: &str,
+) -> AnyhowResult<bool> {
+    // Build URL
+    let url = format!(
+        "https://amplitude.com/api/2/export?start={}&end={}",
+        start, end
+    );
+
+    // Create HTTP client
+    let client = http.build_client()?;
+
+    // Send GET request with Basic Auth; non-2xx responses are errors, except that
+    // the Export API answers 404 when the range holds no data
+    let response = match http.send(client.get(&url).basic_auth(api_key, Some(secret_key))) {
+        Err(e) if is_not_found(&e) => return Ok(false),
+        result => result?,
+    };
+
+    // Write response body to file
+    let mut file = File::create(output)?;
+    let bytes = response.bytes()?;
+    let mut content = bytes.as_ref();
+    copy(&mut content, &mut file)?;
+    bump(&COUNTERS.bytes_downloaded, bytes.len() as u64);
+
+    progress::info(format!("Export saved to {output}"));
+    Ok(true)
+}
+
+fn is_not_found(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<reqwest::Error>()
+        .and_then(|e| e.status())
+        == Some(reqwest::StatusCode::NOT_FOUND)
+}
+
+#[derive(Debug)]
+pub struct ParsedItem {
+    pub user_id: Option<String>,
+    pub device_id: Option<String>,
+    pub screen_name: Option<String>,
+    pub event_name: String,
+    pub server_event: bool,
+    pub event_time: chrono::DateTime<Utc>,
+    pub uuid: String,
+    pub raw_json: String,
+    pub source_file: String,
+    pub source_line: usize,
+    pub session_id: Option<i64>,
+    pub insert_id: Option<String>,
+    pub amplitude_id: Option<i64>,
+    pub event_id: Option<i64>,
+}
+
+// Decompresses every export file in a source directory into a destination directory.
+// Formats are detected from the file contents: gzip, zstd, zip archives (whose entries
+// may themselves be compressed) and plain .json/.jsonl files, which are copied as-is.
+// Returns the names recorded as imported: the file name, or each entry's name for zips.
+pub fn decompress_files(src_dir: &Path, dst_dir: &Path) -> io::Result<Vec<String>> {
+    fs::create_dir_all(dst_dir)?;
+    let mut processed_files = Vec::new();
+
+    for path in fs_util::sorted_entries(src_dir)? {
+        if !path.is_file() {
+            continue;
+        }
+        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+        let mut reader = BufReader::new(File::open(&path)?);
+
+        match sniff(&mut reader)? {
+            Compression::Zip => {
+                let mut archive = zip::ZipArchive::new(File::open(&path)?)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                for i in 0..archive.len() {
+                    let zipped = archive
+                        .by_index(i)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    let Some(entry_name) = zipped
+                        .enclosed_name()
+                        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+                    else {
+                        continue;
+                    };
+                    if zipped.is_dir() {
+                        continue;
+                    }
+                    let mut inner = BufReader::new(zipped);
+                    let compression = sniff(&mut inner)?;
+                    if compression == Compression::Zip {
+                        continue;
+                    }
+                    write_decoded(compression, inner, &dst_dir.join(output_name(&entry_name)))?;
+                    processed_files.push(entry_name);
+                    bump(&COUNTERS.files_unzipped, 1);
+                }
+            }
+            Compression::None if !is_json_name(&file_name) => continue,
+            compression => {
+                write_decoded(compression, reader, &dst_dir.join(output_name(&file_name)))?;
+                processed_files.push(file_name);
+                bump(&COUNTERS.files_unzipped, 1);
+            }
+        }
+    }
+
+    Ok(processed_files)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Zip,
+}
+
+// Identifies the compression of a stream from its magic bytes without consuming them
+fn sniff(reader: &mut impl BufRead) -> io::Result<Compression> {
+    Ok(match reader.fill_buf()? {
+        [0x1f, 0x8b, ..] => Compression::Gzip,
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => Compression::Zstd,
+        [b'P', b'K', 0x03, 0x04, ..] => Compression::Zip,
+        _ => Compression::None,
+    })
+}
+
+fn write_decoded(compression: Compression, reader: impl BufRead, dst: &Path) -> io::Result<()> {
+    let mut decoded: Box<dyn Read + '_> = match compression {
+        Compression::Gzip => Box::new(GzDecoder::new(reader)),
+        Compression::Zstd => Box::new(zstd::Decoder::with_buffer(reader)?),
+        Compression::None | Compression::Zip => Box::new(reader),
+    };
+    let mut writer = BufWriter::new(File::create(dst)?);
+    io::copy(&mut decoded, &mut writer)?;
+    Ok(())
+}
+
+// Drops a trailing compression extension: events.json.gz -> events.json
+fn output_name(file_name: &str) -> &str {
+    [".gz", ".zst"]
+        .iter()
+        .find_map(|ext| file_name.strip_suffix(ext))
+        .unwrap_or(file_name)
+}
+
+fn is_json_name(file_name: &str) -> bool {
+    [".json", ".jsonl", ".ndjson"]
+        .iter()
+        .any(|ext| file_name.ends_with(ext))
+}
+
+/// What to do with export lines that cannot be turned into events.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Stop at the first malformed line
+    Strict,
+    /// Skip malformed lines, counting them as parse errors
+    #[default]
+    Lenient,
+    /// Skip malformed lines and append each one with its error to --parse-errors-file
+    CollectErrors,
+}
+
+// Knobs that change how export lines become ParsedItems
+#[derive(Debug, Default)]
+pub struct ParseOptions {
+    pub transform: Option<EventTransform>,
+    pub id_mapping: Option<IdMapping>,
+    pub time_shift: Option<TimeShift>,
+    pub mode: ParseMode,
+    pub errors_file: Option<PathBuf>,
+    pub new_fields: NewFields,
+    pub event_filter: Option<EventFilter>,
+    pub transform_cmd: Option<String>,
+    pub format: SourceFormat,
+}
+
+// Parses all JSON lines from files in a directory
+pub fn parse_json_objects_in_dir(
+    dir: &Path,
+    options: &ParseOptions,
+) -> io::Result<Vec<ParsedItem>> {
+    parse_json_objects_in_files(&fs_util::sorted_entries(dir)?, options)
+}
+
+// Parses all JSON lines from the given files, in order
+pub fn parse_json_objects_in_files(
+    paths: &[PathBuf],
+    options: &ParseOptions,
+) -> io::Result<Vec<ParsedItem>> {
+    let mut results = Vec::new();
+    let mut errors_out = match (&options.errors_file, options.mode) {
+        (Some(path), ParseMode::CollectErrors) => Some(BufWriter::new(
+            fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?,
+        )),
+        _ => None,
+    };
+
+    for path in paths {
+        cancel::check().map_err(io::Error::other)?;
+        if path.is_file() {
+            let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+            let reader: Box<dyn BufRead> = match &options.transform_cmd {
+                Some(cmd) => Box::new(Cursor::new(transform_cmd::run(cmd, path)?)),
+                None => Box::new(BufReader::new(File::open(path)?)),
+            };
+
+            for (index, line_result) in reader.lines().enumerate() {
+                let line = line_result?;
+                let trimmed = line.trim();
+                if trimmed.is_empty() {
+                    continue;
+                }
+
+                let line_number = index + 1;
+                let error = match parse_line(trimmed, &file_name, line_number, options) {
+                    Ok(item) => {
+                        bump(&COUNTERS.events_parsed, 1);
+                        let allowed = options
+                            .event_filter
+                            .as_ref()
+                            .is_none_or(|filter| filter.allows(&item.event_name));
+                        if allowed {
+                            results.push(item);
+                        }
+                        continue;
+                    }
+                    Err(error) => error,
+                };
+
+                bump(&COUNTERS.parse_errors, 1);
+                match options.mode {
+                    ParseMode::Strict => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            Error::Parse {
+                                file: file_name,
+                                line: line_number,
+                                message: error,
+                            },
+                        ))
+                    }
+                    ParseMode::Lenient => {
+                        progress::error(format!("Skipping {file_name}:{line_number}: {error}"))
+                    }
+                    ParseMode::CollectErrors => {
+                        if let Some(out) = &mut errors_out {
+                            let record = serde_json::json!({
+                                "file": file_name,
+                                "line": line_number,
+                                "error": error,
+                                "raw": trimmed,
+                            });
+                            writeln!(out, "{record}")?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(out) = &mut errors_out {
+        out.flush()?;
+    }
+    Ok(results)
+}
+
+// Turns one export line into an event, or explains why it cannot
+fn parse_line(
+    line: &str,
+    file_name: &str,
+    line_number: usize,
+    options: &ParseOptions,
+) -> Result<ParsedItem, String> {
+    // Mixpanel and Segment lines lack the fields the rewrites below work on, so
+    // import::run refuses those flags for them
+    if options.format != SourceFormat::Amplitude {
+        return import::parse_line(options.format, line, file_name, line_number);
+    }
+    let mut json: Value = serde_json::from_str(line).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    options.new_fields.observe(&json);
+
+    // Ids are remapped first so transform templates see the new ones
+    if let Some(id_mapping) = &options.id_mapping {
+        id_mapping.apply(&mut json);
+    }
+    if let Some(transform) = &options.transform {
+        transform.apply(&mut json);
+    }
+    if let Some(time_shift) = &options.time_shift {
+        time_shift.apply(&mut json);
+    }
+    let raw_json = if options.id_mapping.is_some()
+        || options.transform.is_some()
+        || options.time_shift.is_some()
+    {
+        json.to_string()
+    } else {
+        line.to_string()
+    };
+
+    let field = |name: &str| json.get(name).and_then(|v| v.as_str());
+
+    let user_id = field("user_id").map(|s| s.to_string());
+    let device_id = field("device_id").map(|s| s.to_string());
+    let uuid = field("uuid").ok_or("Missing uuid")?.to_string();
+    let server_event = json
+        .get("data")
+        .and_then(|data| data.get("path"))
+        .and_then(|v| v.as_str())
+        .ok_or("Missing data/path for server_event")?
+        != "/";
+    let event_time = json.get("event_time").ok_or("Missing event time")?;
+    let event_time = timestamp::parse_amplitude_time(event_time).map_err(|e| e.to_string())?;
+    let event_name = field("event_type").ok_or("Missing event name")?.to_string();
+    let session_id = json.get("session_id").and_then(|v| v.as_i64());
+    let insert_id = field("$insert_id").map(|s| s.to_string());
+    let amplitude_id = json.get("amplitude_id").and_then(|v| v.as_i64());
+    let event_id = json.get("event_id").and_then(|v| v.as_i64());
+
+    Ok(ParsedItem {
+        user_id,
+        device_id,
+        uuid,
+        event_name,
+        server_event,
+        event_time,
+        screen_name: None,
+        session_id,
+        insert_id,
+        amplitude_id,
+        event_id,
+        raw_json,
+        source_file: file_name.to_string(),
+        source_line: line_number,
+    })
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum DbEngine {
+    Sqlite,
+    Postgres,
+    Clickhouse,
+    Jsonl,
+    Stdout,
+}
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Without a subcommand, downloads, unzips and imports the given range
+    #[command(flatten)]
+    sync: SyncArgs,
+
+    #[command(flatten)]
+    outcome: OutcomeOptions,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Re-hash downloaded export files against the download manifest
+    VerifyDownloads {
+        /// SQLite database holding the download manifest, relative to --workdir unless
+        /// absolute [default: --db-name]
+        #[arg(long)]
+        db: Option<PathBuf>,
+    },
+    /// Maintenance of the local SQLite database
+    Db {
+        #[command(subcommand)]
+        command: db::DbCommand,
+    },
+    /// Keep the database mirrored by syncing new hours on a schedule
+    Daemon(daemon::DaemonArgs),
+    /// Import export .zip/.gz files as they are dropped into a directory
+    Watch(watch::WatchArgs),
+    /// Time parsing and SQLite inserts on a synthetic dataset
+    Bench(bench::BenchArgs),
+    /// Write synthetic events in the export format, for testing and benchmarking
+    Generate(generate::GenerateArgs),
+    /// Estimate a backfill's download size and duration from one probe hour
+    Plan(plan::PlanArgs),
+    /// Download, extract and import a range into a database in one go, deleting
+    /// archives and extracted files as it goes
+    Mirror(mirror::MirrorArgs),
+    /// Import another vendor's raw event export into the same database
+    Import(import::ImportArgs),
+    /// Compare daily event totals with Amplitude's Dashboard REST API
+    Reconcile(reconcile::ReconcileArgs),
+    /// List one user's events in order, for support and debugging
+    Timeline(timeline::TimelineArgs),
+    /// Check stored events or export files against data quality rules
+    QualityCheck(quality::QualityArgs),
+    /// Compare event types and property keys/types between two date ranges or databases
+    SchemaDiff(schema_diff::SchemaDiffArgs),
+    /// Show how two events differ, field by field, from export files
+    DiffEvents(diff_events::DiffEventsArgs),
+    /// Back up or restore the database and raw JSON archive
+    State {
+        #[command(subcommand)]
+        command: state::StateCommand,
+    },
+    /// Serve a read-only JSON API (events by user, counts, funnels) over the database
+    Serve(serve::ServeArgs),
+    /// Interactive terminal UI that walks through a sync and shows live progress
+    Tui {
+        /// Extra sync flags passed through to every run (e.g. `-- --db-engine postgres`)
+        #[arg(last = true)]
+        sync_args: Vec<String>,
+    },
+}
+
+impl Command {
+    // Name reported in --summary-json
+    fn name(&self) -> &'static str {
+        match self {
+            Command::VerifyDownloads { .. } => "verify-downloads",
+            Command::Db { .. } => "db",
+            Command::Daemon(_) => "daemon",
+            Command::Watch(_) => "watch",
+            Command::Bench(_) => "bench",
+            Command::Generate(_) => "generate",
+            Command::Plan(_) => "plan",
+            Command::Mirror(_) => "mirror",
+            Command::Import(_) => "import",
+            Command::Reconcile(_) => "reconcile",
+            Command::Timeline(_) => "timeline",
+            Command::QualityCheck(_) => "quality-check",
+            Command::SchemaDiff(_) => "schema-diff",
+            Command::DiffEvents(_) => "diff-events",
+            Command::State { .. } => "state",
+            Command::Serve(_) => "serve",
+            Command::Tui { .. } => "tui",
+        }
+    }
+
+    // The database given with the command's own --db, in place of --db-name
+    fn own_db(&self) -> Option<&Path> {
+        match self {
+            Command::VerifyDownloads { db } => db.as_deref(),
+            Command::Db { command } => command.db(),
+            Command::State { command } => command.db(),
+            Command::Reconcile(reconcile_args) => reconcile_args.db(),
+            Command::Timeline(timeline_args) => timeline_args.db(),
+            Command::QualityCheck(quality_args) => quality_args.db(),
+            Command::SchemaDiff(diff_args) => diff_args.db(),
+            Command::Serve(serve_args) => serve_args.db(),
+            Command::Mirror(mirror_args) => mirror_args.db(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct SyncArgs {
+    /// Amplitude project API key (or set AMPLITUDE_PROJECT_API_KEY env var)
+    #[arg(long, env = "AMPLITUDE_PROJECT_API_KEY", required = true)]
+    api_key: Option<String>,
+
+    /// Amplitude project secret key (or set AMPLITUDE_PROJECT_SECRET_KEY env var,
+    /// or use --secret-cmd/--keyring-service)
+    #[arg(long, env = "AMPLITUDE_PROJECT_SECRET_KEY")]
+    secret_key: Option<String>,
+
+    #[command(flatten)]
+    secrets: SecretOptions,
+
+    /// Start date in format YYYYMMDDTHH (e.g., 20250101T00)
+    #[arg(long, required_unless_present_any = ["ranges", "range_file"], requires = "end_date")]
+    start_date: Option<String>,
+
+    /// End date in format YYYYMMDDTHH (e.g., 20251022T23)
+    #[arg(long, required_unless_present_any = ["ranges", "range_file"], requires = "start_date")]
+    end_date: Option<String>,
+
+    /// Another hour range to sync, START..END in YYYYMMDDTHH; repeatable, and
+    /// overlapping ranges are merged
+    #[arg(long = "range", value_parser = windows::parse_range)]
+    ranges: Vec<(NaiveDateTime, NaiveDateTime)>,
+
+    /// File of START..END ranges to sync, one per line
+    #[arg(long)]
+    range_file: Option<PathBuf>,
+
+    /// Project ID (or set AMPLITUDE_PROJECT env var)
+    #[arg(long, env = "AMPLITUDE_PROJECT", required = true, value_parser = layout::parse_project_id)]
+    project_id: Option<String>,
+
+    /// Database engine to write events into
+    #[arg(long, value_enum, default_value_t = DbEngine::Sqlite)]
+    db_engine: DbEngine,
+
+    /// Postgres connection string, ClickHouse HTTP URL, or JSONL output path
+    #[arg(
+        long,
+        env = "AMPLITUDE_DB_DSN",
+        required_if_eq_any([
+            ("db_engine", "postgres"),
+            ("db_engine", "clickhouse"),
+            ("db_engine", "jsonl"),
+        ])
+    )]
+    dsn: Option<String>,
+
+    /// How the SQLite sink stores each event's original JSON
+    #[arg(long, value_enum, default_value_t = RawJson::Keep)]
+    raw_json: RawJson,
+
+    /// TOML or JSON file of event/property renames applied before writing
+    #[arg(long)]
+    transform: Option<PathBuf>,
+
+    /// Shell command each extracted export file is piped through (JSONL on stdin, JSONL
+    /// on stdout) before parsing, for custom enrichment or filtering
+    #[arg(long)]
+    transform_cmd: Option<String>,
+
+    /// Import only the event types listed in this file, one per line
+    #[arg(long)]
+    include_events: Option<PathBuf>,
+
+    /// Skip the event types listed in this file, one per line
+    #[arg(long)]
+    exclude_events: Option<PathBuf>,
+
+    /// CSV of `old_id,new_id` rows applied to user_id and device_id
+    #[arg(long)]
+    id_map: Option<PathBuf>,
+
+    /// Prefix added to user_ids and device_ids that are not in --id-map
+    #[arg(long)]
+    id_prefix: Option<String>,
+
+    /// What to do with export lines that cannot be parsed
+    #[arg(long, value_enum, default_value_t = ParseMode::Lenient)]
+    parse_mode: ParseMode,
+
+    /// Where --parse-mode collect-errors appends malformed lines, one JSON object each
+    #[arg(long, default_value = "parse_errors.jsonl")]
+    parse_errors_file: PathBuf,
+
+    /// Move every event timestamp and session_id by this much, e.g. 30d or -2h
+    #[arg(long, value_parser = TimeShift::parse, allow_hyphen_values = true)]
+    shift_time: Option<TimeShift>,
+
+    /// Split the range into one export request per day or hour; auto starts with days
+    /// and drops to hours for big projects
+    #[arg(long, value_enum, default_value_t = Granularity::Whole)]
+    window: Granularity,
+
+    /// With --window auto, archives at least this large (in bytes) switch to hourly requests
+    #[arg(long, default_value_t = 3 << 30)]
+    window_max_bytes: u64,
+
+    /// How already-stored events are recognised: by uuid, or also by $insert_id and
+    /// device_id within 7 days as Amplitude does
+    #[arg(long, value_enum, default_value_t = Dedup::Uuid)]
+    dedup: Dedup,
+
+    /// Keep a full-text index of raw JSON in amplitude_events_fts (SQLite), for finding
+    /// events by any value, e.g. an order ID or email
+    #[arg(long)]
+    enable_fts: bool,
+
+    /// Top-level export fields to store in amplitude_events columns of their own
+    /// (SQLite), e.g. country,version_name; the rest stays in raw_json. Columns of fields
+    /// no longer listed are dropped and new ones are filled from stored events; `""`
+    /// drops them all. Without it the database keeps its current selection
+    #[arg(
+        long,
+        env = "AMPLITUDE_COLUMNS",
+        value_delimiter = ',',
+        value_parser = sqlite::parse_column_field
+    )]
+    columns: Option<Vec<String>>,
+
+    /// Time zone whose midnight starts a day in import stats and the SQLite analysis
+    /// views: a name such as Asia/Kuala_Lumpur or America/New_York, or an offset like +08:00
+    #[arg(long, value_parser = ReportTimezone::parse, default_value = "UTC", allow_hyphen_values = true)]
+    report_timezone: ReportTimezone,
+
+    /// Add a virtual local_date column to amplitude_events with each event's day in
+    /// --report-timezone (SQLite)
+    #[arg(long)]
+    local_date_column: bool,
+
+    #[command(flatten)]
+    sample: SampleOptions,
+
+    #[command(flatten)]
+    sessions: SessionOptions,
+
+    /// Replay user_properties operations into the user_properties_current and
+    /// user_properties_history tables of the local SQLite file
+    #[arg(long)]
+    user_properties: bool,
+
+    #[command(flatten)]
+    chunks: ChunkOptions,
+
+    #[command(flatten)]
+    storage: StorageOptions,
+
+    #[command(flatten)]
+    http: HttpOptions,
+
+    #[command(flatten)]
+    layout: LayoutOptions,
+
+    #[command(flatten)]
+    preflight: PreflightOptions,
+
+    /// Import the previously downloaded archive instead of downloading again
+    #[arg(long)]
+    skip_download: bool,
+
+    /// Answer yes to confirmations: removing extraction directories this tool did not
+    /// create, and syncing after --preflight found the range too big
+    #[arg(long, conflicts_with = "no_clean")]
+    yes: bool,
+
+    /// Keep extraction directories and the archive between windows, merging new files
+    /// into them; files already imported are skipped either way
+    #[arg(long)]
+    no_clean: bool,
+
+    /// Keep a copy of every downloaded export file in this directory, named by its
+    /// SHA-256 so identical files are stored once and recorded in archived_files; the
+    /// directory can be imported again after parser or schema changes
+    #[arg(long)]
+    archive_dir: Option<PathBuf>,
+
+    /// Delete each window's archive and extracted files once it is imported, so a long
+    /// backfill does not keep about twice its size in intermediates
+    #[arg(long, conflicts_with_all = ["no_clean", "skip_download"])]
+    remove_archives: bool,
+
+    /// Run even if another process holds the lock on this directory
+    #[arg(long)]
+    force: bool,
+
+    /// Address to serve Prometheus metrics on while running, e.g. 127.0.0.1:9091
+    #[arg(long)]
+    metrics_addr: Option<SocketAddr>,
+
+    #[command(flatten)]
+    notify: NotifyOptions,
+}
+
+// Opens the configured event sink; a run opens it once and imports every window into it
+fn open_sink(args: &SyncArgs, db_path: &Path) -> AnyhowResult<Box<dyn EventSink>> {
+    let dsn = args.dsn.as_deref().unwrap_or_default();
+    let project_id = args.project_id.as_deref().unwrap_or_default();
+    Ok(match args.db_engine {
+        DbEngine::Sqlite => Box::new(
+            SqliteSink::open(db_path, project_id, args.raw_json)
+                .and_then(|sink| sink.with_dedup(args.dedup).with_fts(args.enable_fts))
+                .and_then(|sink| {
+                    sink.with_report_timezone(args.report_timezone, args.local_date_column)
+                })
+                .map_err(Error::Sqlite)?
+                .with_columns(args.columns.as_deref())?,
+        ),
+        _ if args.columns.is_some() => {
+            return Err(Error::Config(
+                "--columns is only supported by the sqlite engine".to_string(),
+            )
+            .into())
+        }
+        _ if args.dedup != Dedup::Uuid => {
+            return Err(Error::Config(
+                "--dedup amplitude is only supported by the sqlite engine".to_string(),
+            )
+            .into())
+        }
+        DbEngine::Postgres => Box::new(PostgresSink::connect(dsn, project_id)?),
+        DbEngine::Clickhouse => Box::new(ClickhouseSink::connect(dsn, project_id, &args.http)?),
+        DbEngine::Jsonl if args.chunks.is_enabled() => {
+            Box::new(JsonlSink::create_chunked(dsn, &args.chunks)?)
+        }
+        DbEngine::Jsonl => Box::new(JsonlSink::create(dsn)?),
+        DbEngine::Stdout => Box::new(JsonlSink::stdout()),
+    })
+}
+
+// Application entry point, called from main.rs
+pub fn run() -> AnyhowResult<()> {
+    secrets::load_env_file_from_args()?;
+    let mut cli = Cli::parse();
+
+    if let Some(addr) = cli.sync.metrics_addr {
+        status_server::spawn(addr, progress::metrics_route)?;
+    }
+
+    let command = cli.command.as_ref().map_or("sync", Command::name);
+
+    // Commands that download, extract or write share the working directory's state
+    // Long-running work stops at the next safe point on Ctrl-C instead of mid-write
+    if matches!(
+        cli.command,
+        None | Some(
+            Command::Daemon(_) | Command::Watch(_) | Command::Import(_) | Command::Mirror(_)
+        )
+    ) {
+        cancel::install()?;
+    }
+
+    // A command's own --db stands in for --db-name, so the database locked is the one opened
+    if let Some(db) = cli.command.as_ref().and_then(Command::own_db) {
+        cli.sync.layout.set_db(db);
+    }
+    let db_path = cli
+        .sync
+        .layout
+        .db_path(cli.sync.project_id.as_deref().unwrap_or_default());
+
+    let _lock = match &cli.command {
+        Some(
+            Command::VerifyDownloads { .. }
+            | Command::Bench(_)
+            | Command::Generate(_)
+            | Command::Plan(_)
+            | Command::Reconcile(_)
+            | Command::Timeline(_)
+            | Command::SchemaDiff(_)
+            | Command::DiffEvents(_)
+            | Command::Serve(_),
+        ) => None,
+        // The project is only known once a sync is started from the form
+        Some(Command::Tui { .. }) => None,
+        _ => lock_state(&cli.sync)?,
+    };
+
+    let result = match cli.command {
+        Some(Command::VerifyDownloads { .. }) => verify_downloads(&cli.sync),
+        Some(Command::Db { command }) => db::run(&command, &db_path),
+        Some(Command::Daemon(daemon_args)) => daemon::run(&cli.sync, &daemon_args),
+        Some(Command::Watch(watch_args)) => watch::run(&cli.sync, &watch_args),
+        Some(Command::Bench(bench_args)) => bench::run(&bench_args),
+        Some(Command::Generate(generate_args)) => generate::run(&generate_args),
+        Some(Command::Plan(plan_args)) => plan::run(&cli.sync, &plan_args),
+        Some(Command::Mirror(mirror_args)) => mirror::run(&mut cli.sync, &mirror_args),
+        Some(Command::Import(import_args)) => import::run(&cli.sync, &import_args),
+        Some(Command::Reconcile(reconcile_args)) => reconcile::run(&cli.sync, &reconcile_args),
+        Some(Command::Timeline(timeline_args)) => timeline::run(&cli.sync, &timeline_args),
+        Some(Command::QualityCheck(quality_args)) => quality::run(&cli.sync, &quality_args),
+        Some(Command::SchemaDiff(diff_args)) => schema_diff::run(&cli.sync, &diff_args),
+        Some(Command::DiffEvents(diff_args)) => diff_events::run(&diff_args),
+        Some(Command::State { command }) => state::run(&command, &db_path),
+        Some(Command::Serve(serve_args)) => serve::run(&cli.sync, &serve_args),
+        Some(Command::Tui { sync_args }) => tui::run(sync_args),
+        None => {
+            let args = &cli.sync;
+            let result = sync(args);
+            let run = format!(
+                "Sync of {}",
+                windows::requested_ranges(args)
+                    .map(|ranges| windows::describe(&ranges))
+                    .unwrap_or_default()
+            );
+            let error = result.as_ref().err().map(|e| format!("{e:#}"));
+            notify::send_summary(
+                &args.notify,
+                &args.http,
+                &run,
+                &progress::COUNTERS.snapshot(),
+                error.as_deref(),
+            );
+            result
+        }
+    };
+    if let Some(hint) = result
+        .as_ref()
+        .err()
+        .and_then(Error::find)
+        .and_then(Error::hint)
+    {
+        progress::error(format!("Hint: {hint}"));
+    }
+    cli.outcome.finish(command, result)
+}
+
+// Locks the workdir and database a sync with these flags uses, until dropped
+fn lock_state(args: &SyncArgs) -> AnyhowResult<Option<StateLock>> {
+    let project_id = args.project_id.as_deref().unwrap_or_default();
+    let workdir = args.layout.workdir(project_id);
+    fs::create_dir_all(&workdir)?;
+    StateLock::acquire(&workdir, &args.layout.db_path(project_id), args.force)
+}
+
+// Checks the downloads recorded in the database a sync with the same flags writes to
+fn verify_downloads(args: &SyncArgs) -> AnyhowResult<()> {
+    let db_path = args
+        .layout
+        .db_path(args.project_id.as_deref().unwrap_or_default());
+    if !db_path.exists() {
+        bail!("{} does not exist", db_path.display());
+    }
+    manifest::verify_downloads(&Connection::open(db_path)?, args.project_id.as_deref())
+}
+
+// Downloads, unzips and imports the ranges given on the command line, each split per
+// --window, and records every finished range in the manifest
+fn sync(args: &SyncArgs) -> AnyhowResult<()> {
+    let project_id = args.project_id.as_deref().unwrap_or_default();
+    let db_path = args.layout.db_path(project_id);
+    let ranges = windows::requested_ranges(args)?;
+    preflight::check(args, &ranges, &args.layout.workdir(project_id))?;
+    let mut sink = open_sink(args, &db_path)?;
+    for (start, end) in ranges {
+        cancel::check()?;
+        let start = start.format(windows::EXPORT_HOUR_FORMAT).to_string();
+        let end = end.format(windows::EXPORT_HOUR_FORMAT).to_string();
+        windows::sync_range(args, sink.as_mut(), &start, &end)?;
+        manifest::record_synced_range(&Connection::open(&db_path)?, project_id, &start, &end)?;
+    }
+    Ok(())
+}
+
+// Downloads, unzips and imports one export window; returns the archive's size in bytes
+fn sync_window(
+    args: &SyncArgs,
+    sink: &mut dyn EventSink,
+    start_date: &str,
+    end_date: &str,
+) -> AnyhowResult<u64> {
+    let required = |value: &Option<String>, flag: &str| {
+        value
+            .clone()
+            .ok_or_else(|| Error::Config(format!("{flag} is required")))
+    };
+    let api_key = required(&args.api_key, "--api-key")?;
+    let project_id = required(&args.project_id, "--project-id")?;
+
+    let workdir = args.layout.workdir(&project_id);
+    let archive = args.layout.archive(&project_id, start_date, end_date);
+    let output = &*archive.to_string_lossy();
+    let db_path = &args.layout.db_path(&project_id);
+    fs::create_dir_all(&workdir)?;
+
+    if let Some(uri) = &args.storage.input {
+        remote::download(uri, &archive).context("Failed to fetch export archive")?;
+    } else if !args.skip_download {
+        let secret_key =
+            secrets::resolve_secret_key(args.secret_key.as_deref(), &api_key, &args.secrets)
+                .context("Failed to resolve secret key")?;
+
+        let downloaded = start_amplitude_download(
+            &args.http,
+            &api_key,
+            &secret_key,
+            start_date,
+            end_date,
+            output,
+        )?;
+
+        let manifest_conn = Connection::open(db_path)?;
+        if !downloaded {
+            progress::info(format!("No data for {start_date}..{end_date}"));
+            manifest::record_empty_window(&manifest_conn, &project_id, start_date, end_date)
+                .context("Failed to record empty window in manifest")?;
+            return Ok(0);
+        }
+        manifest::record_download(&manifest_conn, &project_id, &archive, start_date, end_date)
+            .context("Failed to record download in manifest")?;
+    }
+
+    let size = fs::metadata(&archive)?.len();
+    record_created_dirs(
+        &workdir,
+        &[
+            args.layout.project_dir(&project_id),
+            args.layout.extract_dir(&project_id, start_date, end_date),
+        ],
+    )?;
+    let (layout, files) =
+        extract::extract_archive(&archive, &args.layout.project_dir(&project_id))?;
+    progress::info(format!("Extracted {files} export files ({layout} layout)"));
+    if let Some(store) = &args.archive_dir {
+        archive_export_files(
+            &project_id,
+            store,
+            &args.layout.project_dir(&project_id),
+            db_path,
+            start_date,
+            end_date,
+        )?;
+    }
+
+    import_export(
+        args,
+        sink,
+        &args.layout.project_dir(&project_id),
+        &args.layout.extract_dir(&project_id, start_date, end_date),
+        SourceFormat::Amplitude,
+    )?;
+
+    if let Some(uri) = &args.storage.upload_to {
+        let result = match args.db_engine {
+            DbEngine::Sqlite => remote::upload(db_path, uri),
+            DbEngine::Jsonl if args.chunks.is_enabled() => {
+                // Chunks are uploaded side by side under --upload-to as a prefix
+                jsonl::chunk_paths(Path::new(args.dsn.as_deref().unwrap_or_default()))
+                    .iter()
+                    .try_for_each(|chunk| {
+                        let name = chunk.file_name().unwrap_or_default().to_string_lossy();
+                        remote::upload(chunk, &format!("{}/{name}", uri.trim_end_matches('/')))
+                    })
+            }
+            DbEngine::Jsonl => {
+                remote::upload(Path::new(args.dsn.as_deref().unwrap_or_default()), uri)
+            }
+            _ => Err(Error::Config(
+                "--upload-to only applies to the sqlite and jsonl engines".to_string(),
+            )
+            .into()),
+        };
+        result.context("Failed to upload output")?;
+    }
+
+    Ok(size)
+}
+
+// Set once leftovers were confirmed away; later directories are this run's own
+static CLEANUP_CONFIRMED: AtomicBool = AtomicBool::new(false);
+
+// Lists, in the workdir, the extraction directories a sync created itself, so the ones a
+// crashed or interrupted run left behind can be removed without asking
+const CREATED_DIRS_FILE: &str = ".amplitude-things.created";
+
+fn created_dirs(workdir: &Path) -> io::Result<HashSet<PathBuf>> {
+    match fs::read_to_string(workdir.join(CREATED_DIRS_FILE)) {
+        Ok(contents) => Ok(contents.lines().map(PathBuf::from).collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(HashSet::new()),
+        Err(e) => Err(e),
+    }
+}
+
+// Notes the directories in `dirs` that do not exist yet; ones already there may be
+// someone else's, e.g. with --no-clean
+fn record_created_dirs(workdir: &Path, dirs: &[PathBuf]) -> io::Result<()> {
+    let mut created = created_dirs(workdir)?;
+    let count = created.len();
+    created.extend(dirs.iter().filter(|dir| !dir.exists()).cloned());
+    if created.len() > count {
+        write_created_dirs(workdir, &created)?;
+    }
+    Ok(())
+}
+
+fn write_created_dirs(workdir: &Path, dirs: &HashSet<PathBuf>) -> io::Result<()> {
+    let path = workdir.join(CREATED_DIRS_FILE);
+    if dirs.is_empty() {
+        return match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+    let mut lines: Vec<String> = dirs.iter().map(|dir| dir.display().to_string()).collect();
+    lines.sort();
+    fs::write(path, lines.join("\n") + "\n")
+}
+
+// Removes the directories sync_window extracts the archive into. Directories the tool
+// did not create may hold someone's files, so removing them needs --yes or an answer
+// on the terminal.
+fn remove_intermediates(args: &SyncArgs, start: &str, end: &str) -> io::Result<()> {
+    if args.no_clean {
+        return Ok(());
+    }
+    let project_id = args.project_id.as_deref().unwrap_or_default();
+    let workdir = args.layout.workdir(project_id);
+    let mut created = created_dirs(&workdir)?;
+    let existing: Vec<PathBuf> = [
+        args.layout.project_dir(project_id),
+        args.layout.extract_dir(project_id, start, end),
+    ]
+    .into_iter()
+    .filter(|path| path.exists())
+    .collect();
+
+    let foreign: Vec<&PathBuf> = existing
+        .iter()
+        .filter(|path| !created.contains(*path))
+        .collect();
+    if !foreign.is_empty() && !args.yes && !CLEANUP_CONFIRMED.load(Ordering::Relaxed) {
+        let names: Vec<String> = foreign.iter().map(|p| p.display().to_string()).collect();
+        if !progress::confirm(&format!("Remove existing {}?", names.join(" and ")))? {
+            return Err(io::Error::other(Error::Config(format!(
+                "{} already exists; pass --yes to remove it or --no-clean to import into it",
+                names.join(" and ")
+            ))));
+        }
+    }
+    CLEANUP_CONFIRMED.store(true, Ordering::Relaxed);
+
+    for path in existing {
+        fs::remove_dir_all(&path)?;
+        created.remove(&path);
+    }
+    created.retain(|path| path.exists());
+    write_created_dirs(&workdir, &created)
+}
+
+// Adds the export files unzipped from one window's archive to the --archive-dir store
+fn archive_export_files(
+    project_id: &str,
+    store: &Path,
+    files_dir: &Path,
+    db_path: &Path,
+    start_date: &str,
+    end_date: &str,
+) -> AnyhowResult<()> {
+    let conn = Connection::open(db_path)?;
+    let (mut stored, mut known) = (0, 0);
+    for path in fs_util::sorted_entries(files_dir)? {
+        if !path.is_file() {
+            continue;
+        }
+        if manifest::archive_file(&conn, project_id, store, &path, start_date, end_date)? {
+            stored += 1;
+        } else {
+            known += 1;
+        }
+    }
+    progress::info(format!(
+        "Archived {stored} export files in {} ({known} already there)",
+        store.display()
+    ));
+    Ok(())
+}
+
+// Imports every not-yet-imported export file in `compressed_dir`, extracting into `unzipped_dir`
+fn import_export(
+    args: &SyncArgs,
+    sink: &mut dyn EventSink,
+    compressed_dir: &Path,
+    unzipped_dir: &Path,
+    format: SourceFormat,
+) -> AnyhowResult<()> {
+    let db_path = &args
+        .layout
+        .db_path(args.project_id.as_deref().unwrap_or_default());
+
+    let imported_files = sink
+        .imported_files()
+        .context("Failed to read imported files")?;
+
+    progress::info("Decompressing export files...");
+    let all_files = decompress_files(compressed_dir, unzipped_dir)?;
+
+    // Filter only new files that haven’t been imported
+    let new_files: Vec<_> = all_files
+        .into_iter()
+        .filter(|f| !imported_files.contains(f))
+        .collect();
+
+    if new_files.is_empty() {
+        progress::info("No new files to process.");
+        return Ok(());
+    }
+
+    progress::info("Parsing JSON lines...");
+    let options = ParseOptions {
+        transform: args
+            .transform
+            .as_deref()
+            .map(EventTransform::from_file)
+            .transpose()
+            .context("Failed to load transform file")?,
+        id_mapping: (args.id_map.is_some() || args.id_prefix.is_some())
+            .then(|| IdMapping::from_file(args.id_map.as_deref(), args.id_prefix.clone()))
+            .transpose()
+            .context("Failed to load id mapping")?,
+        time_shift: args.shift_time,
+        mode: args.parse_mode,
+        errors_file: Some(args.parse_errors_file.clone()),
+        new_fields: NewFields::default(),
+        event_filter: (args.include_events.is_some() || args.exclude_events.is_some())
+            .then(|| {
+                EventFilter::from_files(
+                    args.include_events.as_deref(),
+                    args.exclude_events.as_deref(),
+                )
+            })
+            .transpose()
+            .context("Failed to load event type lists")?,
+        transform_cmd: args.transform_cmd.clone(),
+        format,
+    };
+    // The extraction directory may still hold files imported by an earlier run
+    let new_paths: Vec<PathBuf> = new_files
+        .iter()
+        .map(|f| unzipped_dir.join(output_name(f)))
+        .collect();
+    let mut parsed_items = parse_json_objects_in_files(&new_paths, &options)?;
+    if let Some(id_mapping) = &options.id_mapping {
+        id_mapping.report();
+    }
+    options.new_fields.report();
+    if let Some(event_filter) = &options.event_filter {
+        event_filter.report();
+    }
+
+    // A sampled run must not stop a later full run from importing the same files
+    let mut processed_files = new_files.as_slice();
+    if args.sample.is_enabled() {
+        parsed_items = args.sample.apply(parsed_items);
+        processed_files = &[];
+    }
+    args.sessions.apply(&mut parsed_items);
+    sink.record_setting("session_policy", &args.sessions.describe())
+        .context("Failed to record session policy")?;
+    sink.record_setting("report_timezone", &args.report_timezone.to_string())
+        .context("Failed to record report time zone")?;
+
+    progress::info("Writing parsed items to database...");
+    let inserted = match write_parsed_items(sink, &parsed_items, processed_files) {
+        Err(_) if cancel::requested() => return Err(Error::Cancelled.into()),
+        result => result.context("Failed to write to database")?,
+    };
+
+    let stats = ImportStats::from_items(&parsed_items, inserted, &args.report_timezone);
+    stats.print();
+    if args.db_engine == DbEngine::Sqlite {
+        let mut conn = Connection::open(db_path)?;
+        stats
+            .write(&mut conn, args.project_id.as_deref().unwrap_or_default())
+            .context("Failed to write import stats")?;
+    }
+
+    if args.user_properties {
+        progress::info("Updating user properties...");
+        let mut conn = Connection::open(db_path)?;
+        user_properties::update_user_properties(
+            &mut conn,
+            args.project_id.as_deref().unwrap_or_default(),
+            &parsed_items,
+        )
+        .context("Failed to update user properties")?;
+    }
+
+    progress::info("Done.");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::write_gzipped;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_end_to_end_multiple_files_and_rows() {
+        let compressed_dir = tempdir().unwrap();
+        let unzipped_dir = tempdir().unwrap();
+        let db_path = compressed_dir.path().join("test_multiple.sqlite");
+
+        // Two gzip files, each with 2 JSON objects
+        let fixture1 = r#"
+{ "user_id": "abc", "uuid": "uuid-0001", "data": {"path": "/test"}, "event_time": "2024-01-01 12:00:00.000000", "event_type": "test_event" }
+{ "user_id": null, "uuid": "uuid-0002", "data": {"path": "/"}, "event_time": "2024-01-01 12:01:00.000000", "event_type": "test_event" }
+"#;
+
+        let fixture2 = r#"
+{ "user_id": "def", "uuid": "uuid-0003", "data": {"path": "/test"}, "event_time": "2024-01-01 12:02:00.000000", "event_type": "test_event" }
+{ "user_id": "ghi", "uuid": "uuid-0004", "data": {"path": "/"}, "event_time": "2024-01-01 12:03:00.000000", "event_type": "test_event" }
+"#;
+
+        write_gzipped(compressed_dir.path(), "fixture1.gz", fixture1).expect("Failed fixture1");
+        write_gzipped(compressed_dir.path(), "fixture2.gz", fixture2).expect("Failed fixture2");
+
+        // Unzip all .gz files
+        let processed_files = decompress_files(compressed_dir.path(), unzipped_dir.path())
+            .expect("Failed to unzip files");
+
+        // Parse all JSON lines from unzipped files
+        let parsed_items = parse_json_objects_in_dir(unzipped_dir.path(), &ParseOptions::default())
+            .expect("Failed to parse");
+
+        // Write parsed data to SQLite
+        let mut sink =
+            SqliteSink::open(&db_path, "123", RawJson::Keep).expect("Failed to open SQLite");
+        write_parsed_items(&mut sink, &parsed_items, &processed_files)
+            .expect("Failed to write to SQLite");
+
+        // Verify SQLite contents
+        let conn = Connection::open(&db_path).unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT uuid, user_id, raw_json, source_file FROM amplitude_events ORDER BY uuid",
+            )
+            .unwrap();
+
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })
+            .unwrap();
+
+        let results: Vec<_> = rows.map(|r| r.unwrap()).collect();
+
+        // Expect 4 rows total
+        assert_eq!(results.len(), 4);
+
+        // Check some values for correctness and ordering by uuid
+        assert_eq!(results[0].0, "uuid-0001");
+        assert_eq!(results[0].1.as_deref(), Some("abc"));
+        assert!(results[0].2.contains("\"data\": {\"path\": \"/test\"}"));
+        assert!(results[0].3.contains("fixture1"));
+
+        assert_eq!(results[1].0, "uuid-0002");
+        assert_eq!(results[1].1, None);
+        assert!(results[1].2.contains("\"data\": {\"path\": \"/\"}"));
+        assert!(results[1].3.contains("fixture1"));
+
+        assert_eq!(results[2].0, "uuid-0003");
+        assert_eq!(results[2].1.as_deref(), Some("def"));
+        assert!(results[2].2.contains("\"data\": {\"path\": \"/test\"}"));
+        assert!(results[2].3.contains("fixture2"));
+
+        assert_eq!(results[3].0, "uuid-0004");
+        assert_eq!(results[3].1.as_deref(), Some("ghi"));
+        assert!(results[3].2.contains("\"data\": {\"path\": \"/\"}"));
+        assert!(results[3].3.contains("fixture2"));
+    }
+
+    // Sync flags for project 123 with everything kept in `workdir`
+    fn sync_args(workdir: &Path, extra: &[&str]) -> SyncArgs {
+        let mut argv = vec![
+            "amplitude-things",
+            "--api-key=key",
+            "--project-id=123",
+            "--start-date=20240101T00",
+            "--end-date=20240101T23",
+        ];
+        let workdir = format!("--workdir={}", workdir.display());
+        argv.push(&workdir);
+        argv.extend_from_slice(extra);
+        Cli::try_parse_from(argv).unwrap().sync
+    }
+
+    #[test]
+    fn test_rerun_skips_files_left_in_extract_dir() {
+        let workdir = tempdir().unwrap();
+        let args = sync_args(workdir.path(), &[]);
+        let mut sink = open_sink(&args, &args.layout.db_path("123")).unwrap();
+        let compressed = args.layout.project_dir("123");
+        let unzipped = args.layout.extract_dir("123", "20240101T00", "20240101T23");
+        fs::create_dir_all(&compressed).unwrap();
+        let event = |uuid: &str| {
+            format!(
+                r#"{{ "uuid": "{uuid}", "data": {{"path": "/"}}, "event_time": "2024-01-01 12:00:00.000000", "event_type": "test" }}"#
+            )
+        };
+
+        let mut import = || {
+            import_export(
+                &args,
+                sink.as_mut(),
+                &compressed,
+                &unzipped,
+                SourceFormat::Amplitude,
+            )
+            .unwrap()
+        };
+        write_gzipped(&compressed, "a.json.gz", &event("uuid-1")).unwrap();
+        import();
+        // a.json stays extracted, as it does between --window whole runs
+        write_gzipped(&compressed, "b.json.gz", &event("uuid-2")).unwrap();
+        import();
+
+        let conn = Connection::open(args.layout.db_path("123")).unwrap();
+        let count = |table: &str| -> i64 {
+            conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+        };
+        assert_eq!(count("amplitude_events"), 2);
+        assert_eq!(count("duplicate_events"), 0);
+    }
+
+    #[test]
+    fn test_parse_modes() {
+        let dir = tempdir().unwrap();
+        let out_dir = tempdir().unwrap();
+        let errors_file = out_dir.path().join("errors.jsonl");
+        fs::write(
+            dir.path().join("events.json"),
+            concat!(
+                r#"{ "uuid": "uuid-0001", "data": {"path": "/"}, "event_time": "2024-01-01 12:00:00.000000", "event_type": "ok" }"#,
+                "\n{ not json\n",
+                r#"{ "uuid": "uuid-0002", "data": {"path": "/"}, "event_time": "yesterday", "event_type": "bad" }"#,
+            ),
+        )
+        .unwrap();
+
+        let lenient = parse_json_objects_in_dir(dir.path(), &ParseOptions::default()).unwrap();
+        assert_eq!(lenient.len(), 1);
+
+        let strict = ParseOptions {
+            mode: ParseMode::Strict,
+            ..Default::default()
+        };
+        let error = parse_json_objects_in_dir(dir.path(), &strict).unwrap_err();
+        assert!(error.to_string().starts_with("events.json:2: Invalid JSON"));
+
+        let collect = ParseOptions {
+            mode: ParseMode::CollectErrors,
+            errors_file: Some(errors_file.clone()),
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_json_objects_in_dir(dir.path(), &collect)
+                .unwrap()
+                .len(),
+            1
+        );
+        let errors: Vec<Value> = fs::read_to_string(&errors_file)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[1]["line"], 3);
+        assert!(errors[1]["error"].as_str().unwrap().contains("yesterday"));
+    }
+}
@@ -0,0 +1,122 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result as AnyhowResult};
+
+use crate::progress;
+
+// Lock file guarding the working directory's archive, extraction directories and database
+pub const LOCK_FILE: &str = ".amplitude-things.lock";
+
+/// Advisory lock on a state directory and its database, held until dropped.
+///
+/// Two runs in one directory would overwrite each other's downloads and
+/// extracted files, and two runs on one database, even from different
+/// directories, would interleave their writes to it.
+pub struct StateLock {
+    _files: Vec<File>,
+}
+
+impl StateLock {
+    // Locks `dir` and `<db_path>.lock` beside the database, or fails naming the process
+    // holding either unless `force` is set. Forced, it keeps whichever of the two it got,
+    // so other runs are still kept out of those; None when it got neither.
+    pub fn acquire(dir: &Path, db_path: &Path, force: bool) -> AnyhowResult<Option<Self>> {
+        let mut db_lock = db_path.as_os_str().to_owned();
+        db_lock.push(".lock");
+        let mut files = Vec::new();
+        for path in [dir.join(LOCK_FILE), PathBuf::from(db_lock)] {
+            files.extend(lock(&path, force)?);
+        }
+        Ok((!files.is_empty()).then_some(Self { _files: files }))
+    }
+}
+
+// Takes the lock on one file, writing this process's id into it
+fn lock(path: &Path, force: bool) -> AnyhowResult<Option<File>> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            let mut holder = String::new();
+            file.read_to_string(&mut holder)?;
+            let holder = match holder.trim() {
+                "" => "another process".to_string(),
+                pid => format!("process {pid}"),
+            };
+            if force {
+                progress::error(format!(
+                    "{holder} holds {}; continuing because of --force",
+                    path.display()
+                ));
+                return Ok(None);
+            }
+            bail!(
+                "{holder} holds {}; wait for it to finish or pass --force",
+                path.display()
+            );
+        }
+        Err(TryLockError::Error(e)) => return Err(e.into()),
+    }
+
+    file.set_len(0)?;
+    file.rewind()?;
+    write!(file, "{}", std::process::id())?;
+    file.flush()?;
+    Ok(Some(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_second_lock_fails_unless_forced() {
+        let dir = tempdir().unwrap();
+        let db = dir.path().join("events.sqlite");
+        let held = StateLock::acquire(dir.path(), &db, false).unwrap();
+        assert!(held.is_some());
+
+        let error = StateLock::acquire(dir.path(), &db, false).err().unwrap();
+        assert!(error
+            .to_string()
+            .starts_with(&format!("process {}", std::process::id())));
+        assert!(StateLock::acquire(dir.path(), &db, true).unwrap().is_none());
+
+        drop(held);
+        assert!(StateLock::acquire(dir.path(), &db, false)
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn test_database_shared_by_two_directories_is_locked() {
+        let (first, second) = (tempdir().unwrap(), tempdir().unwrap());
+        let db = first.path().join("events.sqlite");
+        let _held = StateLock::acquire(first.path(), &db, false).unwrap();
+
+        let error = StateLock::acquire(second.path(), &db, false).err().unwrap();
+        assert!(error.to_string().contains("events.sqlite.lock"), "{error}");
+    }
+
+    #[test]
+    fn test_forced_lock_keeps_the_directory_it_got() {
+        let (first, second) = (tempdir().unwrap(), tempdir().unwrap());
+        let db = first.path().join("events.sqlite");
+        let _held = StateLock::acquire(first.path(), &db, false).unwrap();
+
+        // Only the database is held elsewhere; the directory lock is kept
+        let forced = StateLock::acquire(second.path(), &db, true).unwrap();
+        assert!(forced.is_some());
+        let other_db = second.path().join("other.sqlite");
+        assert!(StateLock::acquire(second.path(), &other_db, false).is_err());
+    }
+}
@@ -0,0 +1,478 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+use anyhow::{bail, Result as AnyhowResult};
+use rusqlite::{params, Connection, Result};
+use sha2::{Digest, Sha256};
+
+use crate::fs_util;
+use crate::progress;
+use crate::sink::sqlite::{has_column, has_table};
+
+const DOWNLOAD_MANIFEST: &str = "
+    CREATE TABLE IF NOT EXISTS download_manifest (
+        project_id TEXT NOT NULL DEFAULT '',
+        filename TEXT NOT NULL,
+        size_bytes INTEGER NOT NULL,
+        sha256 TEXT NOT NULL,
+        window_start TEXT NOT NULL,
+        window_end TEXT NOT NULL,
+        downloaded_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        removed_at DATETIME,
+        PRIMARY KEY (project_id, window_start, window_end)
+    );
+";
+
+const SYNCED_RANGES: &str = "
+    CREATE TABLE IF NOT EXISTS synced_ranges (
+        project_id TEXT NOT NULL DEFAULT '',
+        range_start TEXT NOT NULL,
+        range_end TEXT NOT NULL,
+        synced_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        PRIMARY KEY (project_id, range_start, range_end)
+    );
+";
+
+const ARCHIVED_FILES: &str = "
+    CREATE TABLE IF NOT EXISTS archived_files (
+        project_id TEXT NOT NULL DEFAULT '',
+        filename TEXT NOT NULL,
+        sha256 TEXT NOT NULL,
+        size_bytes INTEGER NOT NULL,
+        stored_path TEXT NOT NULL,
+        window_start TEXT NOT NULL,
+        window_end TEXT NOT NULL,
+        archived_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        PRIMARY KEY (project_id, filename, sha256)
+    );
+";
+
+const EMPTY_WINDOWS: &str = "
+    CREATE TABLE IF NOT EXISTS empty_windows (
+        project_id TEXT NOT NULL DEFAULT '',
+        window_start TEXT NOT NULL,
+        window_end TEXT NOT NULL,
+        checked_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        PRIMARY KEY (project_id, window_start, window_end)
+    );
+";
+
+// Ensures the download manifest tables exist, keyed by project so projects sharing a
+// database keep their own windows
+fn ensure_schema(conn: &Connection) -> Result<()> {
+    migrate_manifest_to_windows(conn)?;
+    migrate_to_projects(conn)?;
+    for create in [
+        DOWNLOAD_MANIFEST,
+        SYNCED_RANGES,
+        ARCHIVED_FILES,
+        EMPTY_WINDOWS,
+    ] {
+        conn.execute_batch(create)?;
+    }
+    Ok(())
+}
+
+// Manifests used to be keyed by filename, so every download to the same archive path
+// replaced the one before; rows are now kept per window
+fn migrate_manifest_to_windows(conn: &Connection) -> Result<()> {
+    if !has_table(conn, "download_manifest")?
+        || has_column(conn, "download_manifest", "removed_at")?
+    {
+        return Ok(());
+    }
+    conn.execute_batch(&format!(
+        "
+        BEGIN;
+        ALTER TABLE download_manifest RENAME TO download_manifest_by_file;
+        {DOWNLOAD_MANIFEST}
+        INSERT OR REPLACE INTO download_manifest
+            (filename, size_bytes, sha256, window_start, window_end, downloaded_at)
+            SELECT filename, size_bytes, sha256, window_start, window_end, downloaded_at
+            FROM download_manifest_by_file ORDER BY downloaded_at;
+        DROP TABLE download_manifest_by_file;
+        COMMIT;
+        "
+    ))
+}
+
+// Tables from before projects shared a database are rebuilt with the project in their
+// primary keys; their rows keep an empty project_id, as imported_files does
+fn migrate_to_projects(conn: &Connection) -> Result<()> {
+    for (table, create, columns) in [
+        (
+            "download_manifest",
+            DOWNLOAD_MANIFEST,
+            "filename, size_bytes, sha256, window_start, window_end, downloaded_at, removed_at",
+        ),
+        (
+            "synced_ranges",
+            SYNCED_RANGES,
+            "range_start, range_end, synced_at",
+        ),
+        (
+            "archived_files",
+            ARCHIVED_FILES,
+            "filename, sha256, size_bytes, stored_path, window_start, window_end, archived_at",
+        ),
+        (
+            "empty_windows",
+            EMPTY_WINDOWS,
+            "window_start, window_end, checked_at",
+        ),
+    ] {
+        if has_table(conn, table)? && !has_column(conn, table, "project_id")? {
+            conn.execute_batch(&format!(
+                "
+                BEGIN;
+                ALTER TABLE {table} RENAME TO {table}_unscoped;
+                {create}
+                INSERT INTO {table} ({columns}) SELECT {columns} FROM {table}_unscoped;
+                DROP TABLE {table}_unscoped;
+                COMMIT;
+                "
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+// Returns the size and hex SHA-256 of a file
+pub fn hash_file(path: &Path) -> io::Result<(u64, String)> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    let mut size = 0u64;
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+
+    Ok((size, format!("{:x}", hasher.finalize())))
+}
+
+// Hashes a freshly downloaded export file and records it with the hour window it covers.
+// Earlier downloads to the same path were overwritten by it and are marked removed.
+pub fn record_download(
+    conn: &Connection,
+    project_id: &str,
+    path: &Path,
+    window_start: &str,
+    window_end: &str,
+) -> AnyhowResult<()> {
+    ensure_schema(conn)?;
+    let (size, sha256) = hash_file(path)?;
+
+    record_removed(conn, project_id, path)?;
+    conn.execute(
+        "INSERT OR REPLACE INTO download_manifest (project_id, filename, size_bytes, sha256, window_start, window_end)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            project_id,
+            path.to_string_lossy(),
+            size as i64,
+            sha256,
+            window_start,
+            window_end
+        ],
+    )?;
+    Ok(())
+}
+
+// Notes that the download at `path` was deleted or overwritten after it was imported, so
+// verification stops expecting it on disk
+pub fn record_removed(conn: &Connection, project_id: &str, path: &Path) -> Result<()> {
+    ensure_schema(conn)?;
+    conn.execute(
+        "UPDATE download_manifest SET removed_at = CURRENT_TIMESTAMP
+         WHERE project_id = ?1 AND filename = ?2 AND removed_at IS NULL",
+        params![project_id, path.to_string_lossy()],
+    )?;
+    Ok(())
+}
+
+// Copies an export file into the content-addressed store as <sha256><extensions>, unless
+// a file with the same contents is already there, and records where it went. The store
+// is a flat directory of export files, so it can be imported again as it is. Returns
+// whether the contents were new to the store.
+pub fn archive_file(
+    conn: &Connection,
+    project_id: &str,
+    store: &Path,
+    path: &Path,
+    window_start: &str,
+    window_end: &str,
+) -> AnyhowResult<bool> {
+    ensure_schema(conn)?;
+    let (size, sha256) = hash_file(path)?;
+    let filename = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let extensions = filename.find('.').map_or("", |dot| &filename[dot..]);
+    let stored = store.join(format!("{sha256}{extensions}"));
+
+    let new = !stored.exists();
+    if new {
+        fs::create_dir_all(store)?;
+        let mut file = fs_util::temp_file_for(&stored)?;
+        io::copy(&mut File::open(path)?, &mut file)?;
+        fs_util::persist(file, &stored)?;
+    }
+    conn.execute(
+        "INSERT OR IGNORE INTO archived_files
+             (project_id, filename, sha256, size_bytes, stored_path, window_start, window_end)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            project_id,
+            filename,
+            sha256,
+            size as i64,
+            stored.to_string_lossy(),
+            window_start,
+            window_end
+        ],
+    )?;
+    Ok(new)
+}
+
+// Records an hour window the Export API reported as holding no data (404)
+pub fn record_empty_window(
+    conn: &Connection,
+    project_id: &str,
+    window_start: &str,
+    window_end: &str,
+) -> Result<()> {
+    ensure_schema(conn)?;
+    conn.execute(
+        "INSERT OR REPLACE INTO empty_windows (project_id, window_start, window_end)
+         VALUES (?1, ?2, ?3)",
+        params![project_id, window_start, window_end],
+    )?;
+    Ok(())
+}
+
+// Records an hour range (from --start-date/--end-date, --range or --range-file) as fully synced
+pub fn record_synced_range(
+    conn: &Connection,
+    project_id: &str,
+    range_start: &str,
+    range_end: &str,
+) -> Result<()> {
+    ensure_schema(conn)?;
+    conn.execute(
+        "INSERT OR REPLACE INTO synced_ranges (project_id, range_start, range_end)
+         VALUES (?1, ?2, ?3)",
+        params![project_id, range_start, range_end],
+    )?;
+    Ok(())
+}
+
+// Re-hashes every download still on disk and reports missing, truncated or corrupted
+// ones. Downloads removed after import are checked through their --archive-dir copies,
+// when there are any. With a project, only its downloads and those recorded before
+// projects were tracked are checked.
+pub fn verify_downloads(conn: &Connection, project_id: Option<&str>) -> AnyhowResult<()> {
+    ensure_schema(conn)?;
+    let mut stmt = conn.prepare(
+        "SELECT filename, size_bytes, sha256, window_start, window_end, removed_at IS NOT NULL,
+                project_id
+         FROM download_manifest
+         WHERE ?1 IS NULL OR project_id IN (?1, '')
+         ORDER BY window_start, window_end, project_id",
+    )?;
+    let rows = stmt.query_map(params![project_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, String>(4)?,
+            row.get::<_, bool>(5)?,
+            row.get::<_, String>(6)?,
+        ))
+    })?;
+
+    let mut checked = 0;
+    let mut failed = 0;
+    for row in rows {
+        let (filename, expected_size, expected_sha256, window_start, window_end, removed, project) =
+            row?;
+        checked += 1;
+
+        let (status, ok) = if removed {
+            verify_archived(conn, &project, &window_start, &window_end)?
+        } else {
+            match hash_file(Path::new(&filename)) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => ("MISSING".to_string(), false),
+                Err(e) => return Err(e.into()),
+                Ok((size, _)) if size as i64 != expected_size => (
+                    format!("TRUNCATED ({} of {} bytes)", size, expected_size),
+                    false,
+                ),
+                Ok((_, sha256)) if sha256 != expected_sha256 => {
+                    ("CORRUPTED (sha256 mismatch)".into(), false)
+                }
+                Ok(_) => ("OK".to_string(), true),
+            }
+        };
+        let line = format!("{status:<10} {filename} [{window_start}..{window_end}]");
+        if ok {
+            progress::info(line);
+        } else {
+            failed += 1;
+            progress::error(line);
+        }
+    }
+
+    progress::info(format!("Verified {checked} downloads, {failed} failed."));
+    if failed > 0 {
+        bail!("{} downloads failed verification", failed);
+    }
+    Ok(())
+}
+
+// Status of a window whose download is gone, from the export files kept in --archive-dir
+fn verify_archived(
+    conn: &Connection,
+    project_id: &str,
+    window_start: &str,
+    window_end: &str,
+) -> AnyhowResult<(String, bool)> {
+    let mut stmt = conn.prepare(
+        "SELECT stored_path, sha256 FROM archived_files
+         WHERE project_id = ?1 AND window_start = ?2 AND window_end = ?3",
+    )?;
+    let copies = stmt
+        .query_map(params![project_id, window_start, window_end], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    if copies.is_empty() {
+        return Ok(("REMOVED (after import)".to_string(), true));
+    }
+
+    let mut bad = 0;
+    for (stored_path, sha256) in &copies {
+        match hash_file(Path::new(stored_path)) {
+            Ok((_, actual)) if actual == *sha256 => {}
+            Ok(_) => bad += 1,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => bad += 1,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(if bad == 0 {
+        (format!("ARCHIVED ({} files OK)", copies.len()), true)
+    } else {
+        (
+            format!("ARCHIVE DAMAGED ({bad} of {} files)", copies.len()),
+            false,
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_verify_downloads_detects_truncated_file() {
+        let dir = tempdir().unwrap();
+        let archive = dir.path().join("export.zip");
+        fs::write(&archive, b"complete archive").unwrap();
+
+        let conn = Connection::open_in_memory().unwrap();
+        record_download(&conn, "123", &archive, "20250101T00", "20250101T23").unwrap();
+        verify_downloads(&conn, None).expect("untouched file should verify");
+
+        fs::write(&archive, b"complete").unwrap();
+        assert!(verify_downloads(&conn, None).is_err());
+    }
+
+    #[test]
+    fn test_manifest_keeps_each_window_of_a_reused_path() {
+        let dir = tempdir().unwrap();
+        let archive = dir.path().join("amplitude_export.zip");
+        let conn = Connection::open_in_memory().unwrap();
+
+        fs::write(&archive, b"first day").unwrap();
+        record_download(&conn, "123", &archive, "20250101T00", "20250101T23").unwrap();
+        fs::write(&archive, b"second day").unwrap();
+        record_download(&conn, "123", &archive, "20250102T00", "20250102T23").unwrap();
+        let windows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM download_manifest", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(windows, 2);
+        verify_downloads(&conn, None).expect("overwritten download is not a failure");
+
+        // As --remove-archives and the daemon do after importing
+        fs::remove_file(&archive).unwrap();
+        record_removed(&conn, "123", &archive).unwrap();
+        verify_downloads(&conn, None).expect("removed download is not a failure");
+    }
+
+    #[test]
+    fn test_projects_keep_their_own_downloads_of_a_window() {
+        let dir = tempdir().unwrap();
+        let (first, second) = (dir.path().join("123.zip"), dir.path().join("456.zip"));
+        fs::write(&first, b"project 123").unwrap();
+        fs::write(&second, b"project 456").unwrap();
+
+        let conn = Connection::open_in_memory().unwrap();
+        record_download(&conn, "123", &first, "20250101T00", "20250101T23").unwrap();
+        record_download(&conn, "456", &second, "20250101T00", "20250101T23").unwrap();
+        record_removed(&conn, "456", &first).unwrap();
+
+        let rows: Vec<(String, bool)> = conn
+            .prepare("SELECT project_id, removed_at IS NOT NULL FROM download_manifest ORDER BY project_id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [("123".to_string(), false), ("456".to_string(), false)]
+        );
+
+        fs::write(&first, b"damaged").unwrap();
+        verify_downloads(&conn, Some("456")).expect("other project's download is not checked");
+        assert!(verify_downloads(&conn, Some("123")).is_err());
+    }
+
+    #[test]
+    fn test_archive_stores_identical_contents_once() {
+        let dir = tempdir().unwrap();
+        let store = dir.path().join("store");
+        let (first, copy) = (
+            dir.path().join("1_a#0.json.gz"),
+            dir.path().join("1_b#0.json.gz"),
+        );
+        fs::write(&first, b"same bytes").unwrap();
+        fs::write(&copy, b"same bytes").unwrap();
+
+        let conn = Connection::open_in_memory().unwrap();
+        assert!(archive_file(&conn, "123", &store, &first, "20250101T00", "20250101T23").unwrap());
+        assert!(!archive_file(&conn, "123", &store, &copy, "20250101T00", "20250101T23").unwrap());
+        assert!(!archive_file(&conn, "123", &store, &first, "20250101T00", "20250101T23").unwrap());
+
+        let stored = fs_util::sorted_entries(&store).unwrap();
+        assert_eq!(stored.len(), 1);
+        assert!(stored[0].to_string_lossy().ends_with(".json.gz"));
+        assert_eq!(fs::read(&stored[0]).unwrap(), b"same bytes");
+        let rows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM archived_files", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rows, 2);
+    }
+}
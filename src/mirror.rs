@@ -0,0 +1,81 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result as AnyhowResult};
+
+use crate::notify;
+use crate::plan::format_bytes;
+use crate::progress::{self, COUNTERS};
+use crate::{sync, windows, SyncArgs};
+
+#[derive(clap::Args, Debug)]
+pub struct MirrorArgs {
+    /// First hour to mirror, YYYYMMDDTHH
+    #[arg(long)]
+    start: String,
+
+    /// Last hour to mirror, YYYYMMDDTHH
+    #[arg(long)]
+    end: String,
+
+    /// SQLite database to mirror into, relative to --workdir unless absolute
+    /// [default: --db-name]
+    #[arg(long)]
+    db: Option<PathBuf>,
+}
+
+impl MirrorArgs {
+    pub fn db(&self) -> Option<&Path> {
+        self.db.as_deref()
+    }
+}
+
+// A sync of one range that keeps nothing but the database: each window's archive and
+// extracted files are deleted as soon as it is imported
+pub fn run(args: &mut SyncArgs, options: &MirrorArgs) -> AnyhowResult<()> {
+    if args.api_key.is_none() {
+        bail!("mirror needs --api-key before the command name");
+    }
+    if args.project_id.is_none() {
+        bail!("mirror needs --project-id before the command name");
+    }
+    if args.no_clean || args.skip_download {
+        bail!("mirror always downloads and cleans up; drop --no-clean and --skip-download");
+    }
+    windows::parse_range(&format!("{}..{}", options.start, options.end))?;
+
+    args.start_date = Some(options.start.clone());
+    args.end_date = Some(options.end.clone());
+    args.ranges.clear();
+    args.range_file = None;
+    args.remove_archives = true;
+    if let Some(db) = &options.db {
+        args.layout.set_db(db);
+    }
+
+    let db_path = args
+        .layout
+        .db_path(args.project_id.as_deref().unwrap_or_default());
+    progress::info(format!(
+        "Mirroring {}..{} into {}",
+        options.start,
+        options.end,
+        db_path.display()
+    ));
+    let result = sync(args);
+    let counters = COUNTERS.snapshot();
+    let error = result.as_ref().err().map(|e| format!("{e:#}"));
+    let run = format!("Mirror of {}..{}", options.start, options.end);
+    notify::send_summary(&args.notify, &args.http, &run, &counters, error.as_deref());
+    result?;
+
+    progress::info(format!(
+        "Mirrored {} new events ({} duplicates skipped) from {} of exports; {} is {}",
+        counters.events_inserted,
+        counters.duplicates_skipped,
+        format_bytes(counters.bytes_downloaded),
+        db_path.display(),
+        format_bytes(fs::metadata(&db_path).map_or(0, |m| m.len())),
+    ));
+    Ok(())
+}
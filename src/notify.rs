@@ -0,0 +1,88 @@
+use anyhow::Result as AnyhowResult;
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::CONTENT_TYPE;
+use serde_json::{json, Value};
+
+use crate::http::HttpOptions;
+use crate::progress::{self, CounterSnapshot};
+
+// Where to announce that a run finished or aborted.
+#[derive(clap::Args, Debug, Clone)]
+pub struct NotifyOptions {
+    /// Slack incoming-webhook URL to post a run summary to
+    #[arg(long, env = "AMPLITUDE_NOTIFY_SLACK_WEBHOOK")]
+    pub notify_slack: Option<String>,
+
+    /// Generic HTTP endpoint that receives the run summary as a JSON POST
+    #[arg(long, env = "AMPLITUDE_NOTIFY_WEBHOOK")]
+    pub notify_webhook: Option<String>,
+}
+
+// Posts a summary of the counters for a finished (or aborted) run to the configured endpoints.
+// `counters` covers only this run, which matters for a daemon's repeated cycles.
+// Notification failures are reported but never fail the run itself.
+pub fn send_summary(
+    options: &NotifyOptions,
+    http: &HttpOptions,
+    run: &str,
+    counters: &CounterSnapshot,
+    error: Option<&str>,
+) {
+    if options.notify_slack.is_none() && options.notify_webhook.is_none() {
+        return;
+    }
+    if let Err(e) = try_send(options, http, run, counters, error) {
+        progress::error(format!("Failed to send notification: {e:#}"));
+    }
+}
+
+fn try_send(
+    options: &NotifyOptions,
+    http: &HttpOptions,
+    run: &str,
+    counters: &CounterSnapshot,
+    error: Option<&str>,
+) -> AnyhowResult<()> {
+    let client = http.build_client()?;
+
+    if let Some(url) = &options.notify_slack {
+        let text = match error {
+            None => format!(
+                ":white_check_mark: {run} finished: {} events imported, {} duplicates skipped, {} parse errors, {} failed batches",
+                counters.events_inserted,
+                counters.duplicates_skipped,
+                counters.parse_errors,
+                counters.failed_batches
+            ),
+            Some(error) => format!(
+                ":x: {run} aborted after importing {} events: {error}",
+                counters.events_inserted
+            ),
+        };
+        http.send(post_json(&client, url, &json!({ "text": text })))?;
+    }
+
+    if let Some(url) = &options.notify_webhook {
+        let payload = json!({
+            "run": run,
+            "status": if error.is_none() { "success" } else { "failure" },
+            "error": error.map(|e| Value::String(e.to_string())),
+            "bytes_downloaded": counters.bytes_downloaded,
+            "events_parsed": counters.events_parsed,
+            "events_inserted": counters.events_inserted,
+            "duplicates_skipped": counters.duplicates_skipped,
+            "parse_errors": counters.parse_errors,
+            "failed_batches": counters.failed_batches,
+        });
+        http.send(post_json(&client, url, &payload))?;
+    }
+
+    Ok(())
+}
+
+fn post_json(client: &Client, url: &str, payload: &Value) -> RequestBuilder {
+    client
+        .post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(payload.to_string())
+}
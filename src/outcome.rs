@@ -0,0 +1,90 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result as AnyhowResult};
+use clap::ValueEnum;
+use serde_json::json;
+
+use crate::fs_util;
+use crate::progress::{self, CounterSnapshot, COUNTERS};
+
+/// Problems that make a run exit non-zero even though it completed.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailOn {
+    /// Events that were already stored
+    Duplicates,
+    /// Export lines that could not be parsed
+    ParseErrors,
+    /// Batches a sink rejected
+    FailedBatches,
+}
+
+impl FailOn {
+    fn count(self, counters: &CounterSnapshot) -> u64 {
+        match self {
+            FailOn::Duplicates => counters.duplicates_skipped,
+            FailOn::ParseErrors => counters.parse_errors,
+            FailOn::FailedBatches => counters.failed_batches,
+        }
+    }
+}
+
+// How a finished command is reported to scripts and CI pipelines
+#[derive(clap::Args, Debug, Clone)]
+pub struct OutcomeOptions {
+    /// Exit non-zero when any of these occurred, e.g. duplicates,parse-errors
+    #[arg(long, global = true, value_enum, value_delimiter = ',')]
+    pub fail_on: Vec<FailOn>,
+
+    /// Write the command's result and counters to this file as JSON
+    #[arg(long, global = true)]
+    pub summary_json: Option<PathBuf>,
+}
+
+impl OutcomeOptions {
+    // Applies --fail-on to a command's result and writes --summary-json. A summary that
+    // cannot be written is reported, but the command's own result decides the exit status.
+    pub fn finish(&self, command: &str, result: AnyhowResult<()>) -> AnyhowResult<()> {
+        let counters = COUNTERS.snapshot();
+        let result = result.and_then(|()| {
+            let failed: Vec<String> = self
+                .fail_on
+                .iter()
+                .filter(|check| check.count(&counters) > 0)
+                .filter_map(|check| {
+                    let name = check.to_possible_value()?;
+                    Some(format!("{} {}", check.count(&counters), name.get_name()))
+                })
+                .collect();
+            if !failed.is_empty() {
+                bail!("--fail-on: {}", failed.join(", "));
+            }
+            Ok(())
+        });
+
+        let error = result.as_ref().err().map(|e| format!("{e:#}"));
+        if let Err(e) = self.write_summary(command, &counters, error.as_deref()) {
+            progress::error(format!("Failed to write --summary-json: {e:#}"));
+        }
+        result
+    }
+
+    pub fn write_summary(
+        &self,
+        command: &str,
+        counters: &CounterSnapshot,
+        error: Option<&str>,
+    ) -> AnyhowResult<()> {
+        let Some(path) = &self.summary_json else {
+            return Ok(());
+        };
+        let summary = json!({
+            "command": command,
+            "success": error.is_none(),
+            "error": error,
+            "counters": counters,
+        });
+        let json = serde_json::to_string_pretty(&summary)? + "\n";
+        fs_util::write_checksummed(path, json.as_bytes())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result as AnyhowResult};
+use chrono::TimeDelta;
+use tempfile::tempdir;
+
+use crate::sink::sqlite::SqliteSink;
+use crate::sink::write_parsed_items;
+use crate::windows::{self, parse_export_hour, Granularity};
+use crate::{
+    decompress_files, parse_json_objects_in_dir, secrets, start_amplitude_download, ParseOptions,
+    SyncArgs,
+};
+
+#[derive(clap::Args, Debug)]
+pub struct PlanArgs {
+    /// First hour of the backfill (YYYYMMDDTHH)
+    #[arg(long)]
+    start: String,
+
+    /// Last hour of the backfill (YYYYMMDDTHH)
+    #[arg(long)]
+    end: String,
+
+    /// Hour to download as a sample of the project's volume; defaults to --start
+    #[arg(long, conflicts_with = "archive")]
+    probe_hour: Option<String>,
+
+    /// Measure this local one-hour export archive instead of downloading a probe
+    #[arg(long)]
+    archive: Option<PathBuf>,
+}
+
+// What one probe hour took to fetch and import
+struct Probe {
+    bytes: u64,
+    events: usize,
+    download: Option<Duration>,
+    parse: Duration,
+    insert: Duration,
+}
+
+// Estimates a backfill's size and duration by importing one hour and scaling it up
+pub fn run(args: &SyncArgs, plan: &PlanArgs) -> AnyhowResult<()> {
+    let (start, end) = (
+        parse_export_hour(&plan.start)?,
+        parse_export_hour(&plan.end)?,
+    );
+    if start > end {
+        bail!("--start must not be after --end");
+    }
+    let hours = (end - start).num_hours() as u64 + 1;
+
+    let probe = probe(args, plan)?;
+    let scale = |duration: Duration| duration.mul_f64(hours as f64);
+    let requests = match args.window {
+        Granularity::Whole => 1,
+        Granularity::Hour => hours,
+        Granularity::Day => windows::split(start, end, TimeDelta::days(1)).len() as u64,
+        Granularity::Auto if probe.bytes * 24 >= args.window_max_bytes => hours,
+        Granularity::Auto => windows::split(start, end, TimeDelta::days(1)).len() as u64,
+    };
+
+    println!(
+        "Probe: {} archive, {} events",
+        format_bytes(probe.bytes),
+        probe.events
+    );
+    println!(
+        "Range: {}..{}, {hours} hours in {requests} export requests (--window {})",
+        plan.start,
+        plan.end,
+        format!("{:?}", args.window).to_lowercase()
+    );
+    println!();
+    println!("{:<20} {:>12}", "events", probe.events as u64 * hours);
+    println!(
+        "{:<20} {:>12}",
+        "download size",
+        format_bytes(probe.bytes * hours)
+    );
+
+    let mut total = scale(probe.parse + probe.insert);
+    match probe.download {
+        Some(download) => {
+            println!(
+                "{:<20} {:>12}",
+                "download time",
+                format_duration(scale(download))
+            );
+            total += scale(download);
+        }
+        None => println!("{:<20} {:>12}", "download time", "unknown"),
+    }
+    println!(
+        "{:<20} {:>12}",
+        "parse time",
+        format_duration(scale(probe.parse))
+    );
+    println!(
+        "{:<20} {:>12}",
+        "insert time",
+        format_duration(scale(probe.insert))
+    );
+    println!("{:<20} {:>12}", "total", format_duration(total));
+    Ok(())
+}
+
+fn probe(args: &SyncArgs, plan: &PlanArgs) -> AnyhowResult<Probe> {
+    let dir = tempdir()?;
+    let compressed_dir = dir.path().join("compressed");
+    let unzipped_dir = dir.path().join("data");
+    fs::create_dir_all(&compressed_dir)?;
+    let mut archive = compressed_dir.join("probe.zip");
+
+    let download = match &plan.archive {
+        Some(path) => {
+            archive = compressed_dir.join(path.file_name().unwrap_or_default());
+            fs::copy(path, &archive)?;
+            None
+        }
+        None => {
+            let hour = plan.probe_hour.as_deref().unwrap_or(&plan.start);
+            let api_key = args
+                .api_key
+                .as_deref()
+                .ok_or_else(|| anyhow!("--api-key is required to download a probe"))?;
+            let secret_key =
+                secrets::resolve_secret_key(args.secret_key.as_deref(), api_key, &args.secrets)?;
+            let started = Instant::now();
+            let found = start_amplitude_download(
+                &args.http,
+                api_key,
+                &secret_key,
+                hour,
+                hour,
+                &archive.to_string_lossy(),
+            )?;
+            if !found {
+                bail!("{hour} has no data; pick a typical hour with --probe-hour");
+            }
+            Some(started.elapsed())
+        }
+    };
+    let bytes = fs::metadata(&archive)?.len();
+
+    let started = Instant::now();
+    decompress_files(&compressed_dir, &unzipped_dir)?;
+    let items = parse_json_objects_in_dir(&unzipped_dir, &ParseOptions::default())?;
+    let parse = started.elapsed();
+
+    let mut sink = SqliteSink::open(dir.path().join("probe.sqlite"), "probe", args.raw_json)?;
+    let started = Instant::now();
+    write_parsed_items(&mut sink, &items, &[])?;
+    let insert = started.elapsed();
+
+    Ok(Probe {
+        bytes,
+        events: items.len(),
+        download,
+        parse,
+        insert,
+    })
+}
+
+fn format_duration(duration: Duration) -> String {
+    humantime::format_duration(Duration::from_secs(duration.as_secs().max(1))).to_string()
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / 1_048_576.0)
+}
@@ -0,0 +1,158 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Result as AnyhowResult};
+use chrono::NaiveDateTime;
+use tempfile::tempdir;
+
+use crate::error::Error;
+use crate::plan::format_bytes;
+use crate::windows::EXPORT_HOUR_FORMAT;
+use crate::{decompress_files, progress, secrets, start_amplitude_download, SyncArgs};
+
+/// Size checks run before a sync downloads anything.
+#[derive(clap::Args, Debug)]
+pub struct PreflightOptions {
+    /// Before syncing, download the first hour, extrapolate the range's size from it
+    /// and stop early if it would exceed --max-download-bytes or the free disk space
+    #[arg(long)]
+    preflight: bool,
+
+    /// With --preflight, the largest estimated download (in bytes) to go ahead with
+    #[arg(long, requires = "preflight")]
+    max_download_bytes: Option<u64>,
+}
+
+// Probes the first requested hour and asks before going on with a sync that looks too
+// big; --yes goes on without asking
+pub fn check(
+    args: &SyncArgs,
+    ranges: &[(NaiveDateTime, NaiveDateTime)],
+    workdir: &Path,
+) -> AnyhowResult<()> {
+    let options = &args.preflight;
+    // Nothing is downloaded from Amplitude in these modes
+    if args.skip_download || args.storage.input.is_some() {
+        return Ok(());
+    }
+    let Some(&(first, _)) = ranges.first().filter(|_| options.preflight) else {
+        return Ok(());
+    };
+    let hours: u64 = ranges
+        .iter()
+        .map(|(start, end)| (*end - *start).num_hours() as u64 + 1)
+        .sum();
+
+    let hour = first.format(EXPORT_HOUR_FORMAT).to_string();
+    progress::info(format!("Preflight: probing {hour}..."));
+    let Some((archive, extracted)) = probe(args, &hour)? else {
+        progress::info(format!(
+            "Preflight: {hour} has no data, so the range's size cannot be estimated"
+        ));
+        return Ok(());
+    };
+
+    let problems = assess(
+        archive * hours,
+        (archive + extracted) * hours,
+        options.max_download_bytes,
+        available_space(workdir),
+    );
+    progress::info(format!(
+        "Preflight: about {} to download over {hours} hours",
+        format_bytes(archive * hours)
+    ));
+    if problems.is_empty() {
+        return Ok(());
+    }
+    for problem in &problems {
+        progress::error(format!("Preflight: {problem}"));
+    }
+    if args.yes || progress::confirm("Sync anyway?")? {
+        return Ok(());
+    }
+    Err(Error::Config(format!(
+        "Preflight failed: {}; pass --yes to sync anyway",
+        problems.join("; ")
+    ))
+    .into())
+}
+
+// Archive and decompressed bytes of one hour, or None when it holds no data
+fn probe(args: &SyncArgs, hour: &str) -> AnyhowResult<Option<(u64, u64)>> {
+    let Some(api_key) = args.api_key.as_deref() else {
+        bail!("--api-key is required for --preflight");
+    };
+    let secret_key =
+        secrets::resolve_secret_key(args.secret_key.as_deref(), api_key, &args.secrets)?;
+    let dir = tempdir()?;
+    let compressed_dir = dir.path().join("compressed");
+    let unzipped_dir = dir.path().join("data");
+    fs::create_dir_all(&compressed_dir)?;
+
+    let archive = compressed_dir.join("probe.zip");
+    if !start_amplitude_download(
+        &args.http,
+        api_key,
+        &secret_key,
+        hour,
+        hour,
+        &archive.to_string_lossy(),
+    )? {
+        return Ok(None);
+    }
+    decompress_files(&compressed_dir, &unzipped_dir)?;
+    let mut extracted = 0;
+    for entry in fs::read_dir(&unzipped_dir)? {
+        extracted += entry?.metadata()?.len();
+    }
+    Ok(Some((fs::metadata(&archive)?.len(), extracted)))
+}
+
+// Why the estimated sync should not go ahead unattended
+fn assess(
+    download: u64,
+    disk_needed: u64,
+    max_download: Option<u64>,
+    available: Option<u64>,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Some(max) = max_download.filter(|max| download > *max) {
+        problems.push(format!(
+            "estimated download of {} exceeds --max-download-bytes ({})",
+            format_bytes(download),
+            format_bytes(max)
+        ));
+    }
+    if let Some(available) = available.filter(|available| disk_needed > *available) {
+        problems.push(format!(
+            "archive and extracted files need about {} but only {} is free",
+            format_bytes(disk_needed),
+            format_bytes(available)
+        ));
+    }
+    problems
+}
+
+// Free space for unprivileged users on the filesystem holding `path`
+fn available_space(path: &Path) -> Option<u64> {
+    fs2::available_space(path).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assess_flags_size_and_disk() {
+        let gb = 1 << 30;
+        assert!(assess(gb, 3 * gb, Some(2 * gb), Some(10 * gb)).is_empty());
+
+        let problems = assess(40 * gb, 120 * gb, Some(20 * gb), Some(50 * gb));
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains("--max-download-bytes"));
+        assert!(problems[1].contains("free"));
+
+        assert!(available_space(Path::new(".")).is_some() || cfg!(not(unix)));
+    }
+}
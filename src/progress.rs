@@ -0,0 +1,309 @@
+use std::fmt::Write as _;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use chrono::Utc;
+
+/// Running totals for the current pipeline run, readable from other threads.
+pub struct Counters {
+    pub bytes_downloaded: AtomicU64,
+    pub files_unzipped: AtomicU64,
+    pub events_parsed: AtomicU64,
+    pub events_inserted: AtomicU64,
+    pub duplicates_skipped: AtomicU64,
+    pub parse_errors: AtomicU64,
+    pub failed_batches: AtomicU64,
+    pub http_throttled: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, Default, serde::Serialize)]
+pub struct CounterSnapshot {
+    pub bytes_downloaded: u64,
+    pub files_unzipped: u64,
+    pub events_parsed: u64,
+    pub events_inserted: u64,
+    pub duplicates_skipped: u64,
+    pub parse_errors: u64,
+    pub failed_batches: u64,
+    pub http_throttled: u64,
+}
+
+pub static COUNTERS: Counters = Counters {
+    bytes_downloaded: AtomicU64::new(0),
+    files_unzipped: AtomicU64::new(0),
+    events_parsed: AtomicU64::new(0),
+    events_inserted: AtomicU64::new(0),
+    duplicates_skipped: AtomicU64::new(0),
+    parse_errors: AtomicU64::new(0),
+    failed_batches: AtomicU64::new(0),
+    http_throttled: AtomicU64::new(0),
+};
+
+/// Seconds between now and the newest fully imported hour; negative until known.
+pub static SYNC_LAG_SECONDS: AtomicI64 = AtomicI64::new(-1);
+
+impl Counters {
+    pub fn snapshot(&self) -> CounterSnapshot {
+        CounterSnapshot {
+            bytes_downloaded: self.bytes_downloaded.load(Ordering::Relaxed),
+            files_unzipped: self.files_unzipped.load(Ordering::Relaxed),
+            events_parsed: self.events_parsed.load(Ordering::Relaxed),
+            events_inserted: self.events_inserted.load(Ordering::Relaxed),
+            duplicates_skipped: self.duplicates_skipped.load(Ordering::Relaxed),
+            parse_errors: self.parse_errors.load(Ordering::Relaxed),
+            failed_batches: self.failed_batches.load(Ordering::Relaxed),
+            http_throttled: self.http_throttled.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn reset(&self) {
+        for counter in [
+            &self.bytes_downloaded,
+            &self.files_unzipped,
+            &self.events_parsed,
+            &self.events_inserted,
+            &self.duplicates_skipped,
+            &self.parse_errors,
+            &self.failed_batches,
+            &self.http_throttled,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+impl CounterSnapshot {
+    // What was counted between `earlier` and this snapshot
+    pub fn since(&self, earlier: &CounterSnapshot) -> CounterSnapshot {
+        CounterSnapshot {
+            bytes_downloaded: self
+                .bytes_downloaded
+                .saturating_sub(earlier.bytes_downloaded),
+            files_unzipped: self.files_unzipped.saturating_sub(earlier.files_unzipped),
+            events_parsed: self.events_parsed.saturating_sub(earlier.events_parsed),
+            events_inserted: self.events_inserted.saturating_sub(earlier.events_inserted),
+            duplicates_skipped: self
+                .duplicates_skipped
+                .saturating_sub(earlier.duplicates_skipped),
+            parse_errors: self.parse_errors.saturating_sub(earlier.parse_errors),
+            failed_batches: self.failed_batches.saturating_sub(earlier.failed_batches),
+            http_throttled: self.http_throttled.saturating_sub(earlier.http_throttled),
+        }
+    }
+}
+
+pub fn bump(counter: &AtomicU64, amount: u64) {
+    counter.fetch_add(amount, Ordering::Relaxed);
+}
+
+// Renders the counters in the Prometheus text exposition format
+pub fn render_prometheus() -> String {
+    let snapshot = COUNTERS.snapshot();
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: i64| {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} {kind}");
+        let _ = writeln!(out, "{name} {value}");
+    };
+
+    let counters = [
+        (
+            "amplitude_bytes_downloaded_total",
+            "Bytes of export archives downloaded.",
+            snapshot.bytes_downloaded,
+        ),
+        (
+            "amplitude_files_unzipped_total",
+            "Hourly export files decompressed.",
+            snapshot.files_unzipped,
+        ),
+        (
+            "amplitude_events_parsed_total",
+            "Export events parsed.",
+            snapshot.events_parsed,
+        ),
+        (
+            "amplitude_events_inserted_total",
+            "Events newly stored by the sink.",
+            snapshot.events_inserted,
+        ),
+        (
+            "amplitude_duplicates_skipped_total",
+            "Events skipped because the sink already held them.",
+            snapshot.duplicates_skipped,
+        ),
+        (
+            "amplitude_parse_errors_total",
+            "Export lines that could not be parsed.",
+            snapshot.parse_errors,
+        ),
+        (
+            "amplitude_failed_batches_total",
+            "Sink batches that failed to write.",
+            snapshot.failed_batches,
+        ),
+        (
+            "amplitude_http_throttled_total",
+            "HTTP 429 responses received.",
+            snapshot.http_throttled,
+        ),
+    ];
+    for (name, help, value) in counters {
+        metric(name, "counter", help, value as i64);
+    }
+
+    let lag = SYNC_LAG_SECONDS.load(Ordering::Relaxed);
+    if lag >= 0 {
+        metric(
+            "amplitude_sync_lag_seconds",
+            "gauge",
+            "Seconds between now and the end of the newest imported hour.",
+            lag,
+        );
+    }
+    out
+}
+
+// Route for status_server serving the metrics above on /metrics
+pub fn metrics_route(path: &str) -> Option<(u16, &'static str, String)> {
+    (path == "/metrics").then(|| (200, "text/plain; version=0.0.4", render_prometheus()))
+}
+
+// Status lines are buffered here instead of printed while a TUI owns the terminal
+static CAPTURED: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+// Starts buffering status lines; they are handed out by `drain_captured`
+pub fn capture_output() {
+    *CAPTURED.lock().unwrap() = Some(Vec::new());
+}
+
+pub fn drain_captured() -> Vec<String> {
+    CAPTURED
+        .lock()
+        .unwrap()
+        .as_mut()
+        .map(std::mem::take)
+        .unwrap_or_default()
+}
+
+// Size-capped log file that shifts `log` → `log.1` → … → `log.<keep>` when full
+struct RotatingLog {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    file: File,
+    written: u64,
+}
+
+impl RotatingLog {
+    fn open(path: &Path, max_bytes: u64, keep: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes,
+            keep,
+            file,
+            written,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.written >= self.max_bytes {
+            self.rotate()?;
+        }
+        let entry = format!("{} {}\n", Utc::now().to_rfc3339(), line);
+        self.file.write_all(entry.as_bytes())?;
+        self.written += entry.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let numbered = |n: usize| PathBuf::from(format!("{}.{}", self.path.display(), n));
+        for n in (1..self.keep).rev() {
+            if numbered(n).exists() {
+                fs::rename(numbered(n), numbered(n + 1))?;
+            }
+        }
+        if self.keep > 0 {
+            fs::rename(&self.path, numbered(1))?;
+        }
+        self.file = File::create(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+static LOG_FILE: Mutex<Option<RotatingLog>> = Mutex::new(None);
+
+// Additionally appends every status line, timestamped, to a rotating log file
+pub fn log_to_file(path: &Path, max_bytes: u64, keep: usize) -> io::Result<()> {
+    *LOG_FILE.lock().unwrap() = Some(RotatingLog::open(path, max_bytes, keep)?);
+    Ok(())
+}
+
+fn write_log(line: &str) {
+    if let Some(log) = LOG_FILE.lock().unwrap().as_mut() {
+        if let Err(e) = log.write_line(line) {
+            eprintln!("Failed to write log file: {e}");
+        }
+    }
+}
+
+// Reports pipeline progress on stdout, or to the capture buffer when one is active
+pub fn info(line: impl Into<String>) {
+    let line = line.into();
+    write_log(&line);
+    match CAPTURED.lock().unwrap().as_mut() {
+        Some(buffer) => buffer.push(line),
+        None => println!("{line}"),
+    }
+}
+
+// Reports a recoverable problem on stderr, or to the capture buffer when one is active
+pub fn error(line: impl Into<String>) {
+    let line = line.into();
+    write_log(&format!("ERROR: {line}"));
+    match CAPTURED.lock().unwrap().as_mut() {
+        Some(buffer) => buffer.push(format!("ERROR: {line}")),
+        None => eprintln!("{line}"),
+    }
+}
+
+// Asks a yes/no question on the terminal; false when nobody can answer (no terminal,
+// or a TUI owns it)
+pub fn confirm(question: &str) -> io::Result<bool> {
+    if CAPTURED.lock().unwrap().is_some() || !io::stdin().is_terminal() {
+        return Ok(false);
+    }
+    eprint!("{question} [y/N] ");
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_since_counts_one_run() {
+        let earlier = CounterSnapshot {
+            events_inserted: 10,
+            parse_errors: 1,
+            ..Default::default()
+        };
+        let now = CounterSnapshot {
+            events_inserted: 25,
+            parse_errors: 1,
+            ..Default::default()
+        };
+        let run = now.since(&earlier);
+        assert_eq!(run.events_inserted, 15);
+        assert_eq!(run.parse_errors, 0);
+    }
+}
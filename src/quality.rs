@@ -0,0 +1,387 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result as AnyhowResult};
+use chrono::{TimeDelta, Utc};
+use regex::Regex;
+use rusqlite::{params, Connection};
+use serde::Deserialize;
+use serde_json::Value;
+use tempfile::tempdir;
+
+use crate::db::raw_json;
+use crate::decompress_files;
+use crate::fs_util::sorted_entries;
+use crate::sink::sqlite::{has_column, raw_archive_path};
+use crate::timestamp::parse_amplitude_time;
+use crate::SyncArgs;
+
+#[derive(clap::Args, Debug)]
+pub struct QualityArgs {
+    /// TOML or JSON file of rules to check
+    #[arg(long)]
+    rules: PathBuf,
+
+    /// Check the export files in this directory instead of the database's events
+    #[arg(long)]
+    dir: Option<PathBuf>,
+
+    /// SQLite database to check, and where quality_violations is written; relative to
+    /// --workdir unless absolute [default: --db-name]
+    #[arg(long)]
+    db: Option<PathBuf>,
+}
+
+impl QualityArgs {
+    pub fn db(&self) -> Option<&Path> {
+        self.db.as_deref()
+    }
+}
+
+/// One expectation about every event, as written in the rules file, e.g.
+///
+/// ```toml
+/// [[rules]]
+/// rule = "required_property"
+/// event_type = "Order Completed"
+/// property = "revenue"
+///
+/// [[rules]]
+/// rule = "max_future"
+/// within = "1h"
+///
+/// [[rules]]
+/// rule = "pattern"
+/// field = "user_id"
+/// regex = "^[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}$"
+/// ```
+///
+/// Fields are top-level export fields or dotted paths such as `event_properties.plan`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case", deny_unknown_fields)]
+enum RuleSpec {
+    /// Events (of one type, or all) must carry an event property
+    RequiredProperty {
+        event_type: Option<String>,
+        property: String,
+    },
+    /// event_time must not be later than now plus `within`
+    MaxFuture { within: String },
+    /// A field, when present, must match a regular expression
+    Pattern { field: String, regex: String },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    rules: Vec<RuleSpec>,
+}
+
+enum Rule {
+    RequiredProperty {
+        event_type: Option<String>,
+        property: String,
+    },
+    MaxFuture(TimeDelta),
+    Pattern {
+        field: String,
+        regex: Regex,
+    },
+}
+
+impl Rule {
+    fn compile(spec: RuleSpec) -> AnyhowResult<Self> {
+        Ok(match spec {
+            RuleSpec::RequiredProperty {
+                event_type,
+                property,
+            } => Self::RequiredProperty {
+                event_type,
+                property,
+            },
+            RuleSpec::MaxFuture { within } => {
+                let within: Duration = humantime::parse_duration(&within)
+                    .with_context(|| format!("Invalid max_future within {within:?}"))?;
+                Self::MaxFuture(TimeDelta::from_std(within)?)
+            }
+            RuleSpec::Pattern { field, regex } => Self::Pattern {
+                field,
+                regex: Regex::new(&regex)?,
+            },
+        })
+    }
+
+    // Name used in the report and the quality_violations table
+    fn name(&self) -> String {
+        match self {
+            Self::RequiredProperty {
+                event_type: Some(event_type),
+                property,
+            } => format!("{event_type} has {property}"),
+            Self::RequiredProperty {
+                event_type: None,
+                property,
+            } => format!("every event has {property}"),
+            Self::MaxFuture(within) => format!(
+                "event_time <= now + {}",
+                humantime::format_duration(within.to_std().unwrap_or_default())
+            ),
+            Self::Pattern { field, regex } => format!("{field} matches {regex}"),
+        }
+    }
+
+    // Why the event breaks this rule, if it does
+    fn check(&self, event: &Value) -> Option<String> {
+        match self {
+            Self::RequiredProperty {
+                event_type,
+                property,
+            } => {
+                let applies = event_type
+                    .as_deref()
+                    .is_none_or(|wanted| event["event_type"].as_str() == Some(wanted));
+                let present = event["event_properties"]
+                    .get(property)
+                    .is_some_and(|value| !value.is_null());
+                (applies && !present).then(|| format!("missing {property}"))
+            }
+            Self::MaxFuture(within) => {
+                let time = parse_amplitude_time(&event["event_time"]).ok()?;
+                (time > Utc::now() + *within).then(|| format!("event_time {time}"))
+            }
+            Self::Pattern { field, regex } => {
+                let value = lookup(event, field)?;
+                let text = value
+                    .as_str()
+                    .map(str::to_string)
+                    .unwrap_or(value.to_string());
+                (!value.is_null() && !regex.is_match(&text)).then(|| format!("{field} = {value}"))
+            }
+        }
+    }
+}
+
+// Value at a dotted path such as event_properties.plan
+fn lookup<'a>(event: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(event, |value, key| value.get(key))
+}
+
+fn load_rules(path: &Path) -> AnyhowResult<Vec<Rule>> {
+    let contents = fs::read_to_string(path)?;
+    let file: RulesFile = match path.extension().and_then(|s| s.to_str()) {
+        Some("toml") => toml::from_str(&contents)?,
+        Some("json") => serde_json::from_str(&contents)?,
+        _ => bail!(
+            "Unsupported rules file {}: expected .toml or .json",
+            path.display()
+        ),
+    };
+    file.rules.into_iter().map(Rule::compile).collect()
+}
+
+// (rule, event uuid or file:line, detail)
+type Violation = (String, String, String);
+
+// Evaluates the rules over the --project-id project's stored events (or export files)
+// and records violations under that project
+pub fn run(args: &SyncArgs, options: &QualityArgs) -> AnyhowResult<()> {
+    let project_id = args.project_id.as_deref();
+    let db = args.layout.db_path(project_id.unwrap_or_default());
+    let rules = load_rules(&options.rules)?;
+    let mut violations: Vec<Violation> = Vec::new();
+    let mut check = |event: &Value, source: String| {
+        for rule in &rules {
+            if let Some(detail) = rule.check(event) {
+                violations.push((rule.name(), source.clone(), detail));
+            }
+        }
+    };
+
+    let checked = match &options.dir {
+        Some(dir) => check_export_dir(dir, &mut check)?,
+        None => check_database(&db, project_id, &mut check)?,
+    };
+
+    let mut conn = Connection::open(&db)?;
+    write_violations(&mut conn, project_id.unwrap_or_default(), &violations)?;
+
+    let mut by_rule: BTreeMap<String, usize> = rules.iter().map(|r| (r.name(), 0)).collect();
+    for (rule, _, _) in &violations {
+        *by_rule.entry(rule.clone()).or_default() += 1;
+    }
+    println!("Checked {checked} events against {} rules", rules.len());
+    for (rule, count) in &by_rule {
+        println!("  {count:>10}  {rule}");
+    }
+    if !violations.is_empty() {
+        bail!(
+            "{} violations, listed in quality_violations in {}",
+            violations.len(),
+            db.display()
+        );
+    }
+    Ok(())
+}
+
+// Events stored before projects were tracked are checked along with the project's
+fn check_database(
+    db: &Path,
+    project_id: Option<&str>,
+    check: &mut impl FnMut(&Value, String),
+) -> AnyhowResult<usize> {
+    if !db.exists() {
+        bail!("{} does not exist", db.display());
+    }
+    let conn = Connection::open(db)?;
+    let archive = raw_archive_path(db);
+    let archived = if archive.exists() {
+        conn.execute(
+            "ATTACH DATABASE ?1 AS raw",
+            params![archive.to_string_lossy()],
+        )?;
+        "(SELECT raw_json FROM raw.amplitude_raw_json r WHERE r.uuid = e.uuid)"
+    } else {
+        "NULL"
+    };
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT e.uuid, e.raw_json, {archived} FROM amplitude_events e
+         WHERE ?1 IS NULL OR e.project_id IN (?1, '')"
+    ))?;
+    let mut rows = stmt.query(params![project_id])?;
+    let mut checked = 0;
+    while let Some(row) = rows.next()? {
+        let uuid: String = row.get(0)?;
+        let Some(raw) = raw_json(row.get(1)?, row.get(2)?)? else {
+            continue;
+        };
+        if let Ok(event) = serde_json::from_slice::<Value>(&raw) {
+            check(&event, uuid);
+            checked += 1;
+        }
+    }
+    Ok(checked)
+}
+
+fn check_export_dir(dir: &Path, check: &mut impl FnMut(&Value, String)) -> AnyhowResult<usize> {
+    let staging = tempdir()?;
+    decompress_files(dir, staging.path())?;
+
+    let mut checked = 0;
+    for path in sorted_entries(staging.path())? {
+        let file_name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        for (index, line) in BufReader::new(File::open(&path)?).lines().enumerate() {
+            if let Ok(event) = serde_json::from_str::<Value>(&line?) {
+                check(&event, format!("{file_name}:{}", index + 1));
+                checked += 1;
+            }
+        }
+    }
+    Ok(checked)
+}
+
+fn write_violations(
+    conn: &mut Connection,
+    project_id: &str,
+    violations: &[Violation],
+) -> AnyhowResult<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS quality_violations (
+            project_id TEXT NOT NULL DEFAULT '',
+            checked_at DATETIME NOT NULL,
+            rule TEXT NOT NULL,
+            event TEXT NOT NULL,
+            detail TEXT NOT NULL
+        );
+        ",
+    )?;
+    if !has_column(conn, "quality_violations", "project_id")? {
+        conn.execute_batch(
+            "ALTER TABLE quality_violations ADD COLUMN project_id TEXT NOT NULL DEFAULT '';",
+        )?;
+    }
+    let checked_at = Utc::now().to_rfc3339();
+    let tx = conn.transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO quality_violations (project_id, checked_at, rule, event, detail)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for (rule, event, detail) in violations {
+            stmt.execute(params![project_id, checked_at, rule, event, detail])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_rules_flag_violations() {
+        let file: RulesFile = toml::from_str(
+            r#"
+            [[rules]]
+            rule = "required_property"
+            event_type = "Order Completed"
+            property = "revenue"
+
+            [[rules]]
+            rule = "max_future"
+            within = "1h"
+
+            [[rules]]
+            rule = "pattern"
+            field = "user_id"
+            regex = "^u[0-9]+$"
+            "#,
+        )
+        .unwrap();
+        let rules: Vec<Rule> = file
+            .rules
+            .into_iter()
+            .map(|spec| Rule::compile(spec).unwrap())
+            .collect();
+
+        let good = json!({
+            "event_type": "Order Completed",
+            "event_time": "2025-01-31 05:06:07.000000",
+            "user_id": "u1",
+            "event_properties": { "revenue": 10 }
+        });
+        assert!(rules.iter().all(|rule| rule.check(&good).is_none()));
+
+        let future = (Utc::now() + TimeDelta::hours(2))
+            .format("%Y-%m-%d %H:%M:%S%.6f")
+            .to_string();
+        let bad = json!({
+            "event_type": "Order Completed",
+            "event_time": future,
+            "user_id": "someone@example.com",
+            "event_properties": {}
+        });
+        let failed: Vec<String> = rules
+            .iter()
+            .filter_map(|rule| rule.check(&bad).map(|_| rule.name()))
+            .collect();
+        assert_eq!(
+            failed,
+            [
+                "Order Completed has revenue",
+                "event_time <= now + 1h",
+                "user_id matches ^u[0-9]+$"
+            ]
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result as AnyhowResult};
+use chrono::NaiveDate;
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+
+use crate::{progress, secrets, SyncArgs};
+
+const SEGMENTATION_URL: &str = "https://amplitude.com/api/2/events/segmentation";
+
+#[derive(clap::Args, Debug)]
+pub struct ReconcileArgs {
+    /// First day to compare (YYYYMMDD)
+    #[arg(long)]
+    start: String,
+
+    /// Last day to compare (YYYYMMDD)
+    #[arg(long)]
+    end: String,
+
+    /// Relative difference from Amplitude's count above which a day is flagged
+    #[arg(long, default_value_t = 0.01)]
+    threshold: f64,
+
+    /// Event types to compare; defaults to every type stored for the range
+    #[arg(long = "event-type")]
+    event_types: Vec<String>,
+
+    /// SQLite database to compare, relative to --workdir unless absolute
+    /// [default: --db-name]
+    #[arg(long)]
+    db: Option<PathBuf>,
+}
+
+impl ReconcileArgs {
+    pub fn db(&self) -> Option<&Path> {
+        self.db.as_deref()
+    }
+}
+
+// Compares daily event totals from the Dashboard REST API with the local database.
+// Local days start at midnight in --report-timezone, which should be the project's.
+pub fn run(args: &SyncArgs, options: &ReconcileArgs) -> AnyhowResult<()> {
+    let start = parse_day(&options.start)?;
+    let end = parse_day(&options.end)?;
+    if start > end {
+        bail!("--start must not be after --end");
+    }
+
+    let local = local_counts(args, start, end)?;
+    let event_types: BTreeSet<String> = if options.event_types.is_empty() {
+        local
+            .keys()
+            .map(|(event_type, _)| event_type.clone())
+            .collect()
+    } else {
+        options.event_types.iter().cloned().collect()
+    };
+
+    let api_key = args
+        .api_key
+        .as_deref()
+        .ok_or_else(|| anyhow!("--api-key is required to query the Dashboard REST API"))?;
+    let secret_key =
+        secrets::resolve_secret_key(args.secret_key.as_deref(), api_key, &args.secrets)?;
+    let client = args.http.build_client()?;
+
+    println!(
+        "{:<10}  {:<30} {:>10} {:>10} {:>8}",
+        "day", "event type", "amplitude", "local", "diff"
+    );
+    let (mut compared, mut flagged) = (0, 0);
+    for event_type in &event_types {
+        progress::info(format!("Fetching daily totals for {event_type}..."));
+        let request = client
+            .get(SEGMENTATION_URL)
+            .basic_auth(api_key, Some(&secret_key))
+            .query(&[
+                ("e", json!({ "event_type": event_type }).to_string()),
+                ("m", "totals".to_string()),
+                ("i", "1".to_string()),
+                ("start", options.start.clone()),
+                ("end", options.end.clone()),
+            ]);
+        let response: Value = serde_json::from_slice(&args.http.send(request)?.bytes()?)?;
+        let remote = parse_segmentation(&response)
+            .with_context(|| format!("Unexpected segmentation response for {event_type}"))?;
+
+        for day in start.iter_days().take_while(|day| *day <= end) {
+            let expected = remote.get(&day).copied().unwrap_or(0);
+            let actual = local.get(&(event_type.clone(), day)).copied().unwrap_or(0);
+            let deviation = deviation(expected, actual);
+            let marker = if deviation > options.threshold {
+                flagged += 1;
+                "  <-"
+            } else {
+                ""
+            };
+            compared += 1;
+            if expected != 0 || actual != 0 {
+                println!(
+                    "{day}  {event_type:<30} {expected:>10} {actual:>10} {:>7.2}%{marker}",
+                    deviation * 100.0
+                );
+            }
+        }
+    }
+
+    if flagged > 0 {
+        bail!(
+            "{flagged} of {compared} days deviate from Amplitude by more than {}%",
+            options.threshold * 100.0
+        );
+    }
+    progress::info(format!(
+        "All {compared} days are within {}% of Amplitude.",
+        options.threshold * 100.0
+    ));
+    Ok(())
+}
+
+fn parse_day(value: &str) -> AnyhowResult<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y%m%d")
+        .with_context(|| format!("Invalid day {value:?}, expected YYYYMMDD"))
+}
+
+// (event type, local day) -> stored events, counting those stored before projects were
+// tracked, which the SQLite sink backfills with an empty project_id
+fn local_counts(
+    args: &SyncArgs,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> AnyhowResult<BTreeMap<(String, NaiveDate), u64>> {
+    let db = args
+        .layout
+        .db_path(args.project_id.as_deref().unwrap_or_default());
+    if !db.exists() {
+        bail!("{} does not exist", db.display());
+    }
+    let conn = Connection::open(&db)?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT event_name, {} AS day, COUNT(*)
+         FROM amplitude_events
+         WHERE (?1 IS NULL OR project_id IN (?1, '')) AND day BETWEEN ?2 AND ?3
+         GROUP BY event_name, day",
+        args.report_timezone.sqlite_date("event_time")
+    ))?;
+    let rows = stmt.query_map(
+        params![args.project_id, start.to_string(), end.to_string()],
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get(2)?,
+            ))
+        },
+    )?;
+
+    let mut counts = BTreeMap::new();
+    for row in rows {
+        let (event_type, day, events) = row?;
+        counts.insert((event_type, day.parse()?), events);
+    }
+    Ok(counts)
+}
+
+// Daily totals of the first series in an events/segmentation response
+fn parse_segmentation(response: &Value) -> AnyhowResult<BTreeMap<NaiveDate, u64>> {
+    let data = response.get("data").ok_or_else(|| anyhow!("no data"))?;
+    let days = data
+        .get("xValues")
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| anyhow!("no xValues"))?;
+    let Some(series) = data
+        .get("series")
+        .and_then(|v| v.as_array())
+        .and_then(|series| series.first())
+        .and_then(|v| v.as_array())
+    else {
+        return Ok(BTreeMap::new());
+    };
+
+    days.iter()
+        .zip(series)
+        .map(|(day, count)| {
+            let day = day.as_str().ok_or_else(|| anyhow!("non-string day"))?;
+            let count = count.as_f64().ok_or_else(|| anyhow!("non-numeric count"))?;
+            Ok((day.parse()?, count as u64))
+        })
+        .collect()
+}
+
+// Relative difference from Amplitude's count; any local events on an empty day count fully
+fn deviation(expected: u64, actual: u64) -> f64 {
+    expected.abs_diff(actual) as f64 / expected.max(1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_segmentation_totals() {
+        let response = json!({
+            "data": {
+                "series": [[120, 0, 98.0]],
+                "seriesLabels": [0],
+                "xValues": ["2025-01-01", "2025-01-02", "2025-01-03"]
+            }
+        });
+        let totals = parse_segmentation(&response).unwrap();
+        assert_eq!(totals.len(), 3);
+        assert_eq!(totals[&"2025-01-03".parse().unwrap()], 98);
+
+        assert_eq!(deviation(100, 99), 0.01);
+        assert_eq!(deviation(0, 0), 0.0);
+        assert_eq!(deviation(0, 3), 3.0);
+    }
+}
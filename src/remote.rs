@@ -0,0 +1,98 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use anyhow::{bail, Result as AnyhowResult};
+use futures::StreamExt;
+use object_store::aws::AmazonS3Builder;
+use object_store::gcp::GoogleCloudStorageBuilder;
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, WriteMultipart};
+use tokio::runtime::{Builder, Runtime};
+use url::Url;
+
+use crate::error::Error;
+use crate::progress::{self, bump, COUNTERS};
+
+// Upload part size; S3 requires at least 5 MiB per part
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
+// Where to read exports from and publish results to, for runs without large local disks.
+//
+// Credentials come from the usual environment: `AWS_ACCESS_KEY_ID`,
+// `AWS_SECRET_ACCESS_KEY`, `AWS_REGION`, ... for `s3://`, and
+// `GOOGLE_SERVICE_ACCOUNT` / `GOOGLE_APPLICATION_CREDENTIALS` for `gs://`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct StorageOptions {
+    /// Import this export archive (s3://bucket/key.zip or gs://bucket/key.zip) instead of
+    /// downloading from Amplitude
+    #[arg(long, conflicts_with = "skip_download")]
+    pub input: Option<String>,
+
+    /// Upload the SQLite database or JSONL output here (s3:// or gs://) after a successful run
+    #[arg(long)]
+    pub upload_to: Option<String>,
+}
+
+// Copies an object to a local file
+pub fn download(uri: &str, dst: &Path) -> AnyhowResult<()> {
+    let (store, path) = open(uri)?;
+    let mut file = File::create(dst)?;
+    runtime()?.block_on(async {
+        // Streamed chunk by chunk so archives larger than memory can be fetched
+        let mut stream = store.get(&path).await?.into_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            file.write_all(&chunk)?;
+            bump(&COUNTERS.bytes_downloaded, chunk.len() as u64);
+        }
+        Ok::<_, anyhow::Error>(())
+    })?;
+    progress::info(format!("Fetched {uri} to {}", dst.display()));
+    Ok(())
+}
+
+// Copies a local file to an object, in parts so large databases don't need to fit in memory
+pub fn upload(src: &Path, uri: &str) -> AnyhowResult<()> {
+    let (store, path) = open(uri)?;
+    let mut file = File::open(src)?;
+    let uploaded = runtime()?.block_on(async {
+        let mut writer =
+            WriteMultipart::new_with_chunk_size(store.put_multipart(&path).await?, PART_SIZE);
+        let mut buffer = vec![0u8; PART_SIZE];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            writer.wait_for_capacity(4).await?;
+            writer.write(&buffer[..read]);
+        }
+        writer.finish().await?;
+        Ok::<_, anyhow::Error>(())
+    });
+    uploaded.map_err(|e| Error::Upload {
+        code: None,
+        response: format!("{e:#}"),
+    })?;
+    progress::info(format!("Uploaded {} to {uri}", src.display()));
+    Ok(())
+}
+
+fn open(uri: &str) -> AnyhowResult<(Box<dyn ObjectStore>, ObjectPath)> {
+    let url = Url::parse(uri)?;
+    let store: Box<dyn ObjectStore> = match url.scheme() {
+        "s3" => Box::new(AmazonS3Builder::from_env().with_url(uri).build()?),
+        "gs" => Box::new(
+            GoogleCloudStorageBuilder::from_env()
+                .with_url(uri)
+                .build()?,
+        ),
+        scheme => bail!("Unsupported storage URI {uri}: expected s3:// or gs://, got {scheme}://"),
+    };
+    Ok((store, ObjectPath::from_url_path(url.path())?))
+}
+
+fn runtime() -> std::io::Result<Runtime> {
+    Builder::new_current_thread().enable_all().build()
+}
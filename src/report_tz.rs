@@ -0,0 +1,194 @@
+use std::fmt;
+
+use chrono::{DateTime, FixedOffset, NaiveDate, Offset, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+
+// Years whose daylight saving changes are spelled out in SQL; outside them the first or
+// last offset applies
+const SQL_TRANSITION_YEARS: (i32, i32) = (2000, 2050);
+
+/// Time zone that reports split days in: a fixed offset from UTC or an IANA zone.
+///
+/// Amplitude projects report in one zone (e.g. Asia/Kuala_Lumpur), so daily counts
+/// only line up with the UI when days start at its midnight, including across
+/// daylight saving changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportTimezone {
+    Fixed(FixedOffset),
+    Named(Tz),
+}
+
+impl Default for ReportTimezone {
+    fn default() -> Self {
+        Self::Fixed(FixedOffset::east_opt(0).unwrap())
+    }
+}
+
+impl ReportTimezone {
+    // Parses UTC, an offset such as +08:00, -05:30 or +8, or a zone name such as
+    // Asia/Kuala_Lumpur or America/New_York
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("utc") || value == "Z" {
+            return Ok(Self::default());
+        }
+        let invalid = || {
+            format!("'{value}' is not a UTC offset or time zone name; use e.g. +08:00 or Asia/Kuala_Lumpur")
+        };
+        let (sign, rest) = match value.as_bytes().first() {
+            Some(b'+') => (1, &value[1..]),
+            Some(b'-') => (-1, &value[1..]),
+            _ => return value.parse::<Tz>().map(Self::Named).map_err(|_| invalid()),
+        };
+        let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+        let hours: i32 = hours.parse().map_err(|_| invalid())?;
+        let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+        if hours > 14 || minutes >= 60 {
+            return Err(invalid());
+        }
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+            .map(Self::Fixed)
+            .ok_or_else(invalid)
+    }
+
+    pub fn is_utc(&self) -> bool {
+        matches!(self, Self::Fixed(offset) if offset.local_minus_utc() == 0)
+    }
+
+    // Calendar day an event falls on in this zone
+    pub fn date(&self, time: &DateTime<Utc>) -> NaiveDate {
+        match self {
+            Self::Fixed(offset) => time.with_timezone(offset).date_naive(),
+            Self::Named(tz) => time.with_timezone(tz).date_naive(),
+        }
+    }
+
+    // SQLite expression for the day a UTC RFC 3339 timestamp column falls on in this zone.
+    // A zone with daylight saving gets a CASE over its offset changes, so the SQL still
+    // works in any SQLite client.
+    pub fn sqlite_date(&self, column: &str) -> String {
+        let modifier = |seconds: i32| format!("'{:+} minutes'", seconds / 60);
+        let tz = match self {
+            Self::Fixed(offset) => {
+                return format!("date({column}, {})", modifier(offset.local_minus_utc()))
+            }
+            Self::Named(tz) => tz,
+        };
+        let (first, changes) = offset_changes(tz);
+        if changes.is_empty() {
+            return format!("date({column}, {})", modifier(first));
+        }
+        let mut case = String::from("CASE");
+        let mut offset = first;
+        for (at, next) in changes {
+            case += &format!(
+                " WHEN {column} < '{}' THEN {}",
+                at.to_rfc3339(),
+                modifier(offset)
+            );
+            offset = next;
+        }
+        format!("date({column}, {case} ELSE {} END)", modifier(offset))
+    }
+}
+
+fn offset_at(tz: &Tz, time: DateTime<Utc>) -> i32 {
+    tz.offset_from_utc_datetime(&time.naive_utc())
+        .fix()
+        .local_minus_utc()
+}
+
+// The zone's offset at the start of SQL_TRANSITION_YEARS, and each change within them
+fn offset_changes(tz: &Tz) -> (i32, Vec<(DateTime<Utc>, i32)>) {
+    let (first_year, last_year) = SQL_TRANSITION_YEARS;
+    let mut day = Utc.with_ymd_and_hms(first_year, 1, 1, 0, 0, 0).unwrap();
+    let end = Utc.with_ymd_and_hms(last_year + 1, 1, 1, 0, 0, 0).unwrap();
+    let first = offset_at(tz, day);
+
+    let mut changes = Vec::new();
+    let mut offset = first;
+    while day < end {
+        let next_day = day + TimeDelta::days(1);
+        let next = offset_at(tz, next_day);
+        if next != offset {
+            // The first second of the day with the new offset
+            let (mut before, mut after) = (day, next_day);
+            while after - before > TimeDelta::seconds(1) {
+                let middle = before + (after - before) / 2;
+                if offset_at(tz, middle) == offset {
+                    before = middle;
+                } else {
+                    after = middle;
+                }
+            }
+            changes.push((after, next));
+            offset = next;
+        }
+        day = next_day;
+    }
+    (first, changes)
+}
+
+impl fmt::Display for ReportTimezone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            _ if self.is_utc() => write!(f, "UTC"),
+            Self::Fixed(offset) => write!(f, "{offset}"),
+            Self::Named(tz) => write!(f, "{}", tz.name()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_parses_offsets_and_shifts_days() {
+        let kl = ReportTimezone::parse("+08:00").unwrap();
+        assert_eq!(kl, ReportTimezone::parse("+8").unwrap());
+        assert_eq!(kl.to_string(), "+08:00");
+        assert_eq!(kl.sqlite_date("t"), "date(t, '+480 minutes')");
+        assert_eq!(
+            ReportTimezone::parse("-05:30").unwrap().sqlite_date("t"),
+            "date(t, '-330 minutes')"
+        );
+        assert!(ReportTimezone::parse("UTC").unwrap().is_utc());
+        assert!(ReportTimezone::parse("Mars/Olympus_Mons").is_err());
+
+        let time = Utc.with_ymd_and_hms(2025, 1, 31, 17, 0, 0).unwrap();
+        assert_eq!(kl.date(&time).to_string(), "2025-02-01");
+        assert_eq!(
+            ReportTimezone::default().date(&time).to_string(),
+            "2025-01-31"
+        );
+
+        let named = ReportTimezone::parse("Asia/Kuala_Lumpur").unwrap();
+        assert_eq!(named.to_string(), "Asia/Kuala_Lumpur");
+        assert_eq!(named.date(&time).to_string(), "2025-02-01");
+        assert_eq!(named.sqlite_date("t"), "date(t, '+480 minutes')");
+    }
+
+    #[test]
+    fn test_days_follow_daylight_saving_in_sql() {
+        let new_york = ReportTimezone::parse("America/New_York").unwrap();
+        let conn = Connection::open_in_memory().unwrap();
+        // 04:30 UTC is 23:30 the day before in winter (-5) and 00:30 in summer (-4)
+        for (utc, day) in [
+            ("2024-01-15T04:30:00+00:00", "2024-01-14"),
+            ("2024-07-15T04:30:00+00:00", "2024-07-15"),
+        ] {
+            let time = DateTime::parse_from_rfc3339(utc).unwrap().to_utc();
+            assert_eq!(new_york.date(&time).to_string(), day);
+            let sql_day: String = conn
+                .query_row(
+                    &format!("SELECT {}", new_york.sqlite_date("?1")),
+                    [utc],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(sql_day, day);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::{BTreeSet, HashSet};
+use std::sync::{Arc, Mutex};
+
+use sha2::{Digest, Sha256};
+
+use crate::progress;
+use crate::sessions::user_key;
+use crate::ParsedItem;
+
+// Deterministic user-level sampling, for trying a migration on a subset first.
+//
+// Users are picked by hashing their `user_id` (or `device_id` for anonymous
+// events), so every run over the same export keeps the same users and each
+// kept user comes with all of their events.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct SampleOptions {
+    /// Keep only this share of users, e.g. 1% or 0.5%
+    #[arg(long, value_parser = parse_percent, conflicts_with = "sample_users")]
+    pub sample: Option<f64>,
+
+    /// Keep only this many users over the whole run
+    #[arg(long)]
+    pub sample_users: Option<usize>,
+
+    // Users picked for --sample-users so far in this run, by hash; a run syncing
+    // several windows fills it from the first ones and keeps to it afterwards
+    #[arg(skip)]
+    chosen: Arc<Mutex<HashSet<u64>>>,
+}
+
+impl SampleOptions {
+    pub fn is_enabled(&self) -> bool {
+        self.sample.is_some() || self.sample_users.is_some()
+    }
+
+    // Drops every event whose user is outside the sample
+    pub fn apply(&self, items: Vec<ParsedItem>) -> Vec<ParsedItem> {
+        let before = items.len();
+        let keyed: Vec<(u64, ParsedItem)> = items
+            .into_iter()
+            .filter_map(|item| Some((user_hash(&item)?, item)))
+            .collect();
+
+        let kept: Vec<ParsedItem> = if let Some(percent) = self.sample {
+            let threshold = (percent / 100.0 * u64::MAX as f64) as u64;
+            keyed
+                .into_iter()
+                .filter(|(hash, _)| *hash <= threshold)
+                .map(|(_, item)| item)
+                .collect()
+        } else if let Some(users) = self.sample_users {
+            // New users are taken lowest hash first while there is room, a stable
+            // sample that only grows with `users`
+            let mut chosen = self.chosen.lock().unwrap();
+            let room = users.saturating_sub(chosen.len());
+            let new: Vec<u64> = keyed
+                .iter()
+                .map(|(hash, _)| *hash)
+                .filter(|hash| !chosen.contains(hash))
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .take(room)
+                .collect();
+            chosen.extend(new);
+            keyed
+                .into_iter()
+                .filter(|(hash, _)| chosen.contains(hash))
+                .map(|(_, item)| item)
+                .collect()
+        } else {
+            keyed.into_iter().map(|(_, item)| item).collect()
+        };
+
+        progress::info(format!("Sampled {} of {} events.", kept.len(), before));
+        kept
+    }
+}
+
+fn parse_percent(value: &str) -> Result<f64, String> {
+    let percent: f64 = value
+        .trim_end_matches('%')
+        .parse()
+        .map_err(|_| format!("{value:?} is not a percentage like 1% or 0.5%"))?;
+    if !(0.0..=100.0).contains(&percent) {
+        return Err(format!("{value:?} must be between 0% and 100%"));
+    }
+    Ok(percent)
+}
+
+// Hash of the user an event belongs to; None for events without any user or device
+fn user_hash(item: &ParsedItem) -> Option<u64> {
+    let digest = Sha256::digest(user_key(item)?.as_bytes());
+    Some(u64::from_be_bytes(digest[..8].try_into().unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::parsed_item;
+
+    fn event(user: u32, n: u32) -> ParsedItem {
+        ParsedItem {
+            user_id: Some(format!("user-{user}")),
+            event_name: "Viewed".into(),
+            ..parsed_item(&format!("{user}-{n}"))
+        }
+    }
+
+    #[test]
+    fn test_sampling_keeps_whole_users_deterministically() {
+        let items = || {
+            (0..1000)
+                .flat_map(|user| (0..3).map(move |n| event(user, n)))
+                .collect::<Vec<_>>()
+        };
+        let users = |items: &[ParsedItem]| {
+            items
+                .iter()
+                .map(|item| item.user_id.clone().unwrap())
+                .collect::<BTreeSet<_>>()
+        };
+
+        let by_count = || SampleOptions {
+            sample_users: Some(10),
+            ..SampleOptions::default()
+        };
+        let first = by_count().apply(items());
+        assert_eq!(users(&first).len(), 10);
+        assert_eq!(first.len(), 30);
+        assert_eq!(users(&by_count().apply(items())), users(&first));
+
+        let by_share = SampleOptions {
+            sample: Some(10.0),
+            ..SampleOptions::default()
+        };
+        let kept = users(&by_share.apply(items())).len();
+        assert!((50..150).contains(&kept), "kept {kept} of 1000 users");
+    }
+
+    #[test]
+    fn test_user_count_holds_across_windows() {
+        let sample = SampleOptions {
+            sample_users: Some(10),
+            ..SampleOptions::default()
+        };
+        // Two windows with different users, then one with a user from each
+        let first = sample.apply((0..20).map(|user| event(user, 0)).collect());
+        let second = sample.apply((20..40).map(|user| event(user, 0)).collect());
+        assert_eq!(first.len(), 10);
+        assert!(second.is_empty());
+
+        let chosen: u32 = first[0].user_id.as_deref().unwrap()["user-".len()..]
+            .parse()
+            .unwrap();
+        let third = sample.apply(vec![event(chosen, 1), event(20, 1)]);
+        assert_eq!(third.len(), 1);
+        assert_eq!(third[0].user_id, first[0].user_id);
+    }
+}
@@ -0,0 +1,300 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result as AnyhowResult};
+use chrono::NaiveDate;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::db::raw_json;
+use crate::error::Error;
+use crate::sink::sqlite::raw_archive_path;
+use crate::SyncArgs;
+
+#[derive(clap::Args, Debug)]
+pub struct SchemaDiffArgs {
+    /// Baseline days, START..END (inclusive, YYYY-MM-DD); defaults to every stored day
+    #[arg(long, value_parser = parse_range)]
+    before: Option<(NaiveDate, NaiveDate)>,
+
+    /// Days compared with the baseline, START..END; defaults to every stored day
+    #[arg(long, value_parser = parse_range)]
+    after: Option<(NaiveDate, NaiveDate)>,
+
+    /// Database holding the baseline; defaults to --db
+    #[arg(long)]
+    before_db: Option<PathBuf>,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+
+    /// SQLite database compared with the baseline, relative to --workdir unless absolute
+    /// [default: --db-name]
+    #[arg(long)]
+    db: Option<PathBuf>,
+}
+
+impl SchemaDiffArgs {
+    pub fn db(&self) -> Option<&Path> {
+        self.db.as_deref()
+    }
+}
+
+fn parse_range(value: &str) -> Result<(NaiveDate, NaiveDate), String> {
+    let (start, end) = value
+        .split_once("..")
+        .ok_or_else(|| format!("expected START..END, got {value:?}"))?;
+    let day = |s: &str| {
+        s.parse::<NaiveDate>()
+            .map_err(|e| format!("invalid day {s:?}: {e}"))
+    };
+    let (start, end) = (day(start)?, day(end)?);
+    if start > end {
+        return Err(format!("{start} is after {end}"));
+    }
+    Ok((start, end))
+}
+
+// event type -> property key -> JSON types seen for it
+type Schema = BTreeMap<String, BTreeMap<String, BTreeSet<&'static str>>>;
+
+#[derive(Debug, Default, Serialize, PartialEq)]
+struct Report {
+    added_events: Vec<String>,
+    removed_events: Vec<String>,
+    added_properties: Vec<PropertyChange>,
+    removed_properties: Vec<PropertyChange>,
+    changed_properties: Vec<PropertyChange>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct PropertyChange {
+    event_type: String,
+    property: String,
+    before: Vec<&'static str>,
+    after: Vec<&'static str>,
+}
+
+// Compares event types and property keys/types of the --project-id project's events seen
+// in two ranges or two databases
+pub fn run(args: &SyncArgs, options: &SchemaDiffArgs) -> AnyhowResult<()> {
+    let project_id = args.project_id.as_deref();
+    let db = args.layout.db_path(project_id.unwrap_or_default());
+    let before_db = options.before_db.as_deref().unwrap_or(&db);
+    if options.before_db.is_none() && options.before.is_none() && options.after.is_none() {
+        return Err(Error::Config(
+            "schema-diff needs --before, --after or --before-db to have something to compare"
+                .into(),
+        )
+        .into());
+    }
+
+    let day = args.report_timezone.sqlite_date("e.event_time");
+    let before = observe(before_db, project_id, options.before, &day)?;
+    let after = observe(&db, project_id, options.after, &day)?;
+    let report = diff(&before, &after);
+
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    for event_type in &report.added_events {
+        println!("+ {event_type}");
+    }
+    for event_type in &report.removed_events {
+        println!("- {event_type}");
+    }
+    for change in &report.added_properties {
+        println!(
+            "+ {}.{} ({})",
+            change.event_type,
+            change.property,
+            change.after.join("|")
+        );
+    }
+    for change in &report.removed_properties {
+        println!(
+            "- {}.{} ({})",
+            change.event_type,
+            change.property,
+            change.before.join("|")
+        );
+    }
+    for change in &report.changed_properties {
+        println!(
+            "~ {}.{}: {} -> {}",
+            change.event_type,
+            change.property,
+            change.before.join("|"),
+            change.after.join("|")
+        );
+    }
+    if report == Report::default() {
+        println!("No schema changes.");
+    }
+    Ok(())
+}
+
+fn observe(
+    db: &Path,
+    project_id: Option<&str>,
+    range: Option<(NaiveDate, NaiveDate)>,
+    day: &str,
+) -> AnyhowResult<Schema> {
+    if !db.exists() {
+        bail!("{} does not exist", db.display());
+    }
+    let conn = Connection::open(db)?;
+    let archive = raw_archive_path(db);
+    let archived = if archive.exists() {
+        conn.execute(
+            "ATTACH DATABASE ?1 AS raw",
+            params![archive.to_string_lossy()],
+        )?;
+        "(SELECT raw_json FROM raw.amplitude_raw_json r WHERE r.uuid = e.uuid)"
+    } else {
+        "NULL"
+    };
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT e.event_name, e.raw_json, {archived}
+         FROM amplitude_events e
+         WHERE (?1 IS NULL OR {day} BETWEEN ?1 AND ?2)
+           AND (?3 IS NULL OR e.project_id IN (?3, ''))"
+    ))?;
+    let (start, end) = range.map(|(s, e)| (s.to_string(), e.to_string())).unzip();
+    let mut rows = stmt.query(params![start, end, project_id])?;
+
+    let mut schema = Schema::new();
+    while let Some(row) = rows.next()? {
+        let properties = schema.entry(row.get(0)?).or_default();
+        let Some(event) = raw_json(row.get(1)?, row.get(2)?)?
+            .and_then(|raw| serde_json::from_slice::<Value>(&raw).ok())
+        else {
+            continue;
+        };
+        for (prefix, field) in [("", "event_properties"), ("user.", "user_properties")] {
+            if let Some(Value::Object(map)) = event.get(field) {
+                for (key, value) in map {
+                    properties
+                        .entry(format!("{prefix}{key}"))
+                        .or_default()
+                        .insert(json_type(value));
+                }
+            }
+        }
+    }
+    Ok(schema)
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+// Properties of added or removed event types are implied by the event and not listed
+fn diff(before: &Schema, after: &Schema) -> Report {
+    let mut report = Report::default();
+    for event_type in before.keys().filter(|e| !after.contains_key(*e)) {
+        report.removed_events.push(event_type.clone());
+    }
+    for (event_type, after_properties) in after {
+        let Some(before_properties) = before.get(event_type) else {
+            report.added_events.push(event_type.clone());
+            continue;
+        };
+        let change = |property: &str, before: Option<&BTreeSet<_>>, after: Option<&BTreeSet<_>>| {
+            PropertyChange {
+                event_type: event_type.clone(),
+                property: property.to_string(),
+                before: before.into_iter().flatten().copied().collect(),
+                after: after.into_iter().flatten().copied().collect(),
+            }
+        };
+        for (property, types) in before_properties {
+            if !after_properties.contains_key(property) {
+                report
+                    .removed_properties
+                    .push(change(property, Some(types), None));
+            }
+        }
+        for (property, types) in after_properties {
+            match before_properties.get(property) {
+                None => report
+                    .added_properties
+                    .push(change(property, None, Some(types))),
+                Some(old) if old != types => {
+                    report
+                        .changed_properties
+                        .push(change(property, Some(old), Some(types)))
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema(entries: &[(&str, &str, &[&'static str])]) -> Schema {
+        let mut schema = Schema::new();
+        for (event_type, property, types) in entries {
+            let properties = schema.entry(event_type.to_string()).or_default();
+            if !property.is_empty() {
+                properties
+                    .entry(property.to_string())
+                    .or_default()
+                    .extend(types.iter());
+            }
+        }
+        schema
+    }
+
+    #[test]
+    fn test_diff_reports_added_removed_and_changed() {
+        let before = schema(&[
+            ("Login", "method", &["string"]),
+            ("Login", "legacy", &["boolean"]),
+            ("Purchase", "price", &["number"]),
+            ("Old Event", "", &[]),
+        ]);
+        let after = schema(&[
+            ("Login", "method", &["string"]),
+            ("Login", "user.plan", &["string"]),
+            ("Purchase", "price", &["number", "string"]),
+            ("New Event", "", &[]),
+        ]);
+
+        let report = diff(&before, &after);
+        assert_eq!(report.added_events, ["New Event"]);
+        assert_eq!(report.removed_events, ["Old Event"]);
+        assert_eq!(report.added_properties[0].property, "user.plan");
+        assert_eq!(report.removed_properties[0].property, "legacy");
+        assert_eq!(
+            report.changed_properties,
+            [PropertyChange {
+                event_type: "Purchase".into(),
+                property: "price".into(),
+                before: vec!["number"],
+                after: vec!["number", "string"],
+            }]
+        );
+
+        assert_eq!(
+            parse_range("2025-01-01..2025-01-31").unwrap().1,
+            NaiveDate::from_ymd_opt(2025, 1, 31).unwrap()
+        );
+        assert!(parse_range("2025-02-01..2025-01-01").is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{bail, Context, Result as AnyhowResult};
+
+// Where the project secret key can come from besides `--secret-key`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct SecretOptions {
+    /// KEY=VALUE file loaded into the environment before arguments are read
+    #[arg(long)]
+    pub env_file: Option<String>,
+
+    /// Shell command whose stdout is the secret key (e.g. "op read op://vault/amplitude/secret")
+    #[arg(long, conflicts_with = "keyring_service")]
+    pub secret_cmd: Option<String>,
+
+    /// OS keyring service holding the secret key, stored under the API key as user name
+    #[arg(long)]
+    pub keyring_service: Option<String>,
+}
+
+// Loads `--env-file` ahead of argument parsing so env-backed flags can pick the values up.
+// Variables already set in the environment win over the file.
+pub fn load_env_file_from_args() -> AnyhowResult<()> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let path = match arg.strip_prefix("--env-file") {
+            Some("") => args.next(),
+            Some(rest) => rest.strip_prefix('=').map(str::to_string),
+            None => None,
+        };
+        if let Some(path) = path {
+            load_env_file(Path::new(&path))?;
+        }
+    }
+    Ok(())
+}
+
+fn load_env_file(path: &Path) -> AnyhowResult<()> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read env file {}", path.display()))?;
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            bail!("Malformed line in env file {}: {}", path.display(), line);
+        };
+        let value = value.trim().trim_matches('"').trim_matches('\'');
+        if env::var_os(key.trim()).is_none() {
+            env::set_var(key.trim(), value);
+        }
+    }
+    Ok(())
+}
+
+// Resolves the secret key from the flag/env var, a secret command, or the OS keyring
+pub fn resolve_secret_key(
+    secret_key: Option<&str>,
+    api_key: &str,
+    options: &SecretOptions,
+) -> AnyhowResult<String> {
+    if let Some(secret_key) = secret_key {
+        return Ok(secret_key.to_string());
+    }
+
+    if let Some(cmd) = &options.secret_cmd {
+        let output = shell(cmd)
+            .output()
+            .with_context(|| format!("Failed to run secret command `{}`", cmd))?;
+        if !output.status.success() {
+            bail!(
+                "Secret command `{}` failed: {}",
+                cmd,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        return Ok(String::from_utf8(output.stdout)?.trim().to_string());
+    }
+
+    if let Some(service) = &options.keyring_service {
+        let entry = keyring::Entry::new(service, api_key)?;
+        return entry.get_password().with_context(|| {
+            format!(
+                "No secret key in keyring service `{}` for this API key",
+                service
+            )
+        });
+    }
+
+    bail!("No secret key: pass --secret-key, AMPLITUDE_PROJECT_SECRET_KEY, --secret-cmd or --keyring-service")
+}
+
+#[cfg(unix)]
+pub(crate) fn shell(cmd: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(cmd);
+    command
+}
+
+#[cfg(windows)]
+pub(crate) fn shell(cmd: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(cmd);
+    command
+}
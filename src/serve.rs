@@ -0,0 +1,300 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Result as AnyhowResult};
+use chrono::{DateTime, TimeDelta, Utc};
+use rusqlite::{params, Connection, OpenFlags};
+use serde_json::{json, Value};
+
+use crate::progress;
+use crate::status_server::{self, Response};
+use crate::SyncArgs;
+
+#[derive(clap::Args, Debug)]
+pub struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+
+    /// SQLite database to serve, relative to --workdir unless absolute
+    /// [default: --db-name]
+    #[arg(long)]
+    db: Option<PathBuf>,
+}
+
+impl ServeArgs {
+    pub fn db(&self) -> Option<&Path> {
+        self.db.as_deref()
+    }
+}
+
+// Most events /events returns, whatever limit is asked for
+const MAX_EVENTS: u32 = 10_000;
+
+// Serves a read-only JSON API over the --project-id project's events until killed:
+//
+//   GET /events?user=<user_id>[&limit=100]      a user's events, newest first
+//   GET /counts[?start=YYYY-MM-DD&end=...]      events per event type, days in --report-timezone
+//   GET /funnel?step=A&step=B[&window=1d]       users reaching each step in order
+//
+// Every request opens the database read-only, so syncs can keep writing meanwhile.
+// Events stored before projects were tracked are served with every project's.
+pub fn run(args: &SyncArgs, options: &ServeArgs) -> AnyhowResult<()> {
+    let db = args
+        .layout
+        .db_path(args.project_id.as_deref().unwrap_or_default());
+    if !db.exists() {
+        bail!("{} does not exist", db.display());
+    }
+    let listener = TcpListener::bind(options.listen)?;
+    progress::info(format!(
+        "Serving {} on http://{}",
+        db.display(),
+        options.listen
+    ));
+    let project_id = args.project_id.clone();
+    let day = args.report_timezone.sqlite_date("event_time");
+    status_server::serve(listener, move |path| {
+        route(&db, project_id.as_deref(), &day, path)
+    });
+    Ok(())
+}
+
+// `day` is the SQL expression for an event's day in the report time zone
+fn route(db: &Path, project_id: Option<&str>, day: &str, path: &str) -> Option<Response> {
+    let (path, query) = path.split_once('?').unwrap_or((path, ""));
+    let query: Vec<(String, String)> = url::form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect();
+    let handler: &dyn Fn(&Connection) -> AnyhowResult<Value> = match path {
+        "/events" => &|conn| events(conn, project_id, &query),
+        "/counts" => &|conn| counts(conn, project_id, day, &query),
+        "/funnel" => &|conn| funnel(conn, project_id, &query),
+        _ => return None,
+    };
+    let result = Connection::open_with_flags(db, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(anyhow::Error::from)
+        .and_then(|conn| handler(&conn));
+    Some(match result {
+        Ok(body) => (200, "application/json", format!("{body}\n")),
+        Err(e) if e.is::<BadRequest>() => (400, "application/json", error_body(&e)),
+        Err(e) => (500, "application/json", error_body(&e)),
+    })
+}
+
+fn error_body(error: &anyhow::Error) -> String {
+    format!("{}\n", json!({ "error": error.to_string() }))
+}
+
+// A problem with the request rather than the server
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+struct BadRequest(String);
+
+fn param<'a>(query: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    query
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+// Matches the events of one project, and those stored before projects were tracked
+const IN_PROJECT: &str = "(?1 IS NULL OR project_id IN (?1, ''))";
+
+fn events(
+    conn: &Connection,
+    project_id: Option<&str>,
+    query: &[(String, String)],
+) -> AnyhowResult<Value> {
+    let user = param(query, "user").ok_or_else(|| BadRequest("user is required".into()))?;
+    let limit = match param(query, "limit") {
+        Some(limit) => limit
+            .parse::<u32>()
+            .map_err(|_| BadRequest(format!("invalid limit {limit:?}")))?,
+        None => 100,
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT event_time, event_name, uuid, device_id, session_id
+         FROM amplitude_events WHERE {IN_PROJECT} AND user_id = ?2
+         ORDER BY event_time DESC, uuid DESC LIMIT ?3"
+    ))?;
+    let rows = stmt.query_map(params![project_id, user, limit.min(MAX_EVENTS)], |row| {
+        Ok(json!({
+            "event_time": row.get::<_, String>(0)?,
+            "event_type": row.get::<_, String>(1)?,
+            "uuid": row.get::<_, String>(2)?,
+            "device_id": row.get::<_, Option<String>>(3)?,
+            "session_id": row.get::<_, Option<i64>>(4)?,
+        }))
+    })?;
+    Ok(Value::Array(rows.collect::<rusqlite::Result<_>>()?))
+}
+
+fn counts(
+    conn: &Connection,
+    project_id: Option<&str>,
+    day: &str,
+    query: &[(String, String)],
+) -> AnyhowResult<Value> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT event_name, COUNT(*) FROM amplitude_events
+         WHERE {IN_PROJECT}
+           AND (?2 IS NULL OR {day} >= ?2) AND (?3 IS NULL OR {day} <= ?3)
+         GROUP BY event_name"
+    ))?;
+    let rows = stmt.query_map(
+        params![project_id, param(query, "start"), param(query, "end")],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?)),
+    )?;
+    let counts: BTreeMap<String, u64> = rows.collect::<rusqlite::Result<_>>()?;
+    Ok(json!(counts))
+}
+
+fn funnel(
+    conn: &Connection,
+    project_id: Option<&str>,
+    query: &[(String, String)],
+) -> AnyhowResult<Value> {
+    let steps: Vec<&str> = query
+        .iter()
+        .filter(|(key, _)| key == "step")
+        .map(|(_, value)| value.as_str())
+        .collect();
+    if steps.len() < 2 {
+        return Err(BadRequest("a funnel needs at least two step parameters".into()).into());
+    }
+    let window = param(query, "window").unwrap_or("1d");
+    let window = humantime::parse_duration(window)
+        .map_err(|e| BadRequest(format!("invalid window {window:?}: {e}")))?;
+
+    let placeholders: Vec<String> = (2..steps.len() + 2).map(|i| format!("?{i}")).collect();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT user_id, event_name, event_time FROM amplitude_events
+         WHERE {IN_PROJECT} AND user_id IS NOT NULL AND event_name IN ({})
+         ORDER BY user_id, event_time",
+        placeholders.join(", ")
+    ))?;
+    let mut values = vec![project_id];
+    values.extend(steps.iter().map(|step| Some(*step)));
+    let mut rows = stmt.query(rusqlite::params_from_iter(values))?;
+    let mut journeys: HashMap<String, Vec<(String, DateTime<Utc>)>> = HashMap::new();
+    while let Some(row) = rows.next()? {
+        let time: String = row.get(2)?;
+        let time = DateTime::parse_from_rfc3339(&time)
+            .map_err(|e| anyhow!("Unexpected event_time {time:?}: {e}"))?
+            .to_utc();
+        journeys
+            .entry(row.get(0)?)
+            .or_default()
+            .push((row.get(1)?, time));
+    }
+
+    let reached = funnel_counts(&steps, journeys.values(), TimeDelta::from_std(window)?);
+    Ok(Value::Array(
+        steps
+            .iter()
+            .zip(reached)
+            .map(|(step, users)| json!({ "step": step, "users": users }))
+            .collect(),
+    ))
+}
+
+// Users reaching each step, in order, within `window` of their first event of step one
+fn funnel_counts<'a>(
+    steps: &[&str],
+    journeys: impl Iterator<Item = &'a Vec<(String, DateTime<Utc>)>>,
+    window: TimeDelta,
+) -> Vec<u64> {
+    let mut reached = vec![0; steps.len()];
+    for events in journeys {
+        let Some(start) = events
+            .iter()
+            .find(|(name, _)| name == steps[0])
+            .map(|(_, time)| *time)
+        else {
+            continue;
+        };
+        let mut step = 0;
+        for (name, time) in events {
+            if *time < start || *time > start + window {
+                continue;
+            }
+            if step < steps.len() && name == steps[step] {
+                reached[step] += 1;
+                step += 1;
+            }
+        }
+    }
+    reached
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report_tz::ReportTimezone;
+
+    #[test]
+    fn test_funnel_counts_ordered_steps_within_window() {
+        let at = |hour: u32| {
+            DateTime::parse_from_rfc3339(&format!("2025-01-01T{hour:02}:00:00Z"))
+                .unwrap()
+                .to_utc()
+        };
+        let journeys = [
+            vec![
+                ("Signup".to_string(), at(1)),
+                ("Trial".to_string(), at(2)),
+                ("Purchase".to_string(), at(3)),
+            ],
+            // Purchase before trial does not count
+            vec![
+                ("Signup".to_string(), at(1)),
+                ("Purchase".to_string(), at(2)),
+                ("Trial".to_string(), at(3)),
+            ],
+            // Outside the window
+            vec![("Signup".to_string(), at(0)), ("Trial".to_string(), at(5))],
+        ];
+        let steps = ["Signup", "Trial", "Purchase"];
+        assert_eq!(
+            funnel_counts(&steps, journeys.iter(), TimeDelta::hours(4)),
+            [3, 2, 1]
+        );
+    }
+
+    #[test]
+    fn test_counts_only_the_projects_events() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "
+            CREATE TABLE amplitude_events (project_id TEXT NOT NULL, event_name TEXT, event_time TEXT);
+            INSERT INTO amplitude_events VALUES
+                ('123', 'Login', '2025-01-01T00:00:00Z'),
+                ('456', 'Login', '2025-01-01T00:00:00Z'),
+                ('', 'Signup', '2025-01-01T00:00:00Z');
+            ",
+        )
+        .unwrap();
+        let utc = ReportTimezone::default().sqlite_date("event_time");
+        assert_eq!(
+            counts(&conn, Some("123"), &utc, &[]).unwrap(),
+            json!({ "Login": 1, "Signup": 1 })
+        );
+        assert_eq!(
+            counts(&conn, None, &utc, &[]).unwrap(),
+            json!({ "Login": 2, "Signup": 1 })
+        );
+
+        // Days are taken in the report time zone, where these events fall on Dec 31
+        let query = [("end".to_string(), "2024-12-31".to_string())];
+        assert_eq!(counts(&conn, None, &utc, &query).unwrap(), json!({}));
+        let new_york = ReportTimezone::parse("America/New_York")
+            .unwrap()
+            .sqlite_date("event_time");
+        assert_eq!(
+            counts(&conn, None, &new_york, &query).unwrap(),
+            json!({ "Login": 2, "Signup": 1 })
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use clap::ValueEnum;
+
+use crate::ParsedItem;
+
+// Amplitude's marker for events sent outside a session
+const NO_SESSION: i64 = -1;
+
+/// What to store for events without a session (`session_id` of -1 or missing).
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SessionPolicy {
+    /// Store NULL
+    #[default]
+    Null,
+    /// Store -1 as exported
+    Keep,
+    /// Group each user's session-less events into sessions split by --session-gap of inactivity
+    Synthesize,
+}
+
+// How session-less events are handled; the policy in use is recorded in the database
+#[derive(clap::Args, Debug, Clone)]
+pub struct SessionOptions {
+    /// What to store for events with a session_id of -1 or none at all
+    #[arg(long, value_enum, default_value_t = SessionPolicy::Null)]
+    pub session_policy: SessionPolicy,
+
+    /// Inactivity that ends a synthesized session
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "30m")]
+    pub session_gap: Duration,
+}
+
+impl SessionOptions {
+    // Value recorded as the `session_policy` setting of the database
+    pub fn describe(&self) -> String {
+        match self.session_policy {
+            SessionPolicy::Null => "null".to_string(),
+            SessionPolicy::Keep => "keep".to_string(),
+            SessionPolicy::Synthesize => format!(
+                "synthesize (gap {})",
+                humantime::format_duration(self.session_gap)
+            ),
+        }
+    }
+
+    pub fn apply(&self, items: &mut [ParsedItem]) {
+        match self.session_policy {
+            SessionPolicy::Keep => {}
+            SessionPolicy::Null => {
+                for item in items.iter_mut() {
+                    if item.session_id == Some(NO_SESSION) {
+                        item.session_id = None;
+                    }
+                }
+            }
+            SessionPolicy::Synthesize => self.synthesize(items),
+        }
+    }
+
+    // Like Amplitude, a session is identified by its first event's time in milliseconds
+    fn synthesize(&self, items: &mut [ParsedItem]) {
+        let gap = self.session_gap.as_millis() as i64;
+
+        let mut by_user: HashMap<String, Vec<usize>> = HashMap::new();
+        for (index, item) in items.iter().enumerate() {
+            if matches!(item.session_id, None | Some(NO_SESSION)) {
+                if let Some(user) = user_key(item) {
+                    by_user.entry(user).or_default().push(index);
+                }
+            }
+        }
+
+        for mut indices in by_user.into_values() {
+            indices.sort_by_key(|&index| items[index].event_time);
+            let mut session: Option<(i64, i64)> = None;
+            for index in indices {
+                let time = items[index].event_time.timestamp_millis();
+                let session_id = match session {
+                    Some((id, last)) if time - last <= gap => id,
+                    _ => time,
+                };
+                session = Some((session_id, time));
+                items[index].session_id = Some(session_id);
+            }
+        }
+
+        // Events without any user or device can't be grouped
+        for item in items.iter_mut() {
+            if item.session_id == Some(NO_SESSION) {
+                item.session_id = None;
+            }
+        }
+    }
+}
+
+// The user an event belongs to, falling back to its device for anonymous events; None
+// for events with neither
+pub fn user_key(item: &ParsedItem) -> Option<String> {
+    match &item.user_id {
+        Some(user_id) => Some(format!("user:{user_id}")),
+        None => Some(format!("device:{}", item.device_id.as_deref()?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::parsed_item;
+    use chrono::{TimeZone, Utc};
+
+    fn event(user: &str, minute: u32, session_id: Option<i64>) -> ParsedItem {
+        ParsedItem {
+            user_id: Some(user.to_string()),
+            event_time: Utc.with_ymd_and_hms(2024, 1, 1, 12, minute, 0).unwrap(),
+            session_id,
+            ..parsed_item(&format!("{user}-{minute}"))
+        }
+    }
+
+    #[test]
+    fn test_synthesizes_sessions_from_inactivity_gaps() {
+        let options = SessionOptions {
+            session_policy: SessionPolicy::Synthesize,
+            session_gap: Duration::from_secs(30 * 60),
+        };
+        let mut items = vec![
+            event("a", 40, Some(-1)),
+            event("a", 0, None),
+            event("a", 20, Some(-1)),
+            event("a", 10, Some(7)),
+            event("b", 5, Some(-1)),
+        ];
+        options.apply(&mut items);
+
+        let start = |minute| Utc.with_ymd_and_hms(2024, 1, 1, 12, minute, 0).unwrap();
+        let ids: Vec<_> = items.iter().map(|item| item.session_id).collect();
+        assert_eq!(
+            ids,
+            [
+                Some(start(0).timestamp_millis()),
+                Some(start(0).timestamp_millis()),
+                Some(start(0).timestamp_millis()),
+                Some(7),
+                Some(start(5).timestamp_millis()),
+            ]
+        );
+    }
+}
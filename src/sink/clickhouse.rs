@@ -0,0 +1,214 @@
+use std::collections::HashSet;
+
+use anyhow::Result as AnyhowResult;
+use chrono::Utc;
+use reqwest::blocking::Client;
+use serde_json::json;
+
+use super::EventSink;
+use crate::error::Error;
+use crate::http::HttpOptions;
+use crate::ParsedItem;
+
+pub struct ClickhouseSink {
+    client: Client,
+    http: HttpOptions,
+    url: String,
+    project_id: String,
+    created_at: String,
+}
+
+impl ClickhouseSink {
+    // Connects and ensures the event and bookkeeping tables exist. Rows are deduplicated by
+    // ReplacingMergeTree on uuid during background merges, like the other sinks' default
+    // dedup; $insert_id is only unique per device, so keying on it alone merged events
+    // from different devices.
+    pub fn connect(url: &str, project_id: &str, http: &HttpOptions) -> AnyhowResult<Self> {
+        let client = http.build_client()?;
+        let sink = Self {
+            client,
+            http: http.clone(),
+            url: url.to_string(),
+            project_id: project_id.to_string(),
+            created_at: String::new(),
+        };
+
+        sink.run_query(
+            "CREATE TABLE IF NOT EXISTS amplitude_events (
+                insert_id String,
+                uuid String,
+                user_id Nullable(String),
+                event_screen Nullable(String),
+                server_event UInt8,
+                event_time DateTime64(6, 'UTC'),
+                event_name String,
+                session_id Nullable(Int64),
+                raw_json String,
+                source_file String,
+                created_at DateTime64(6, 'UTC'),
+                project_id String DEFAULT ''
+            ) ENGINE = ReplacingMergeTree(created_at)
+            ORDER BY uuid",
+            String::new(),
+        )?;
+        sink.migrate_sorting_key()?;
+        sink.run_query(
+            "CREATE TABLE IF NOT EXISTS imported_files (
+                project_id String DEFAULT '',
+                filename String,
+                imported_at DateTime DEFAULT now()
+            ) ENGINE = ReplacingMergeTree
+            ORDER BY (project_id, filename)",
+            String::new(),
+        )?;
+        sink.migrate_to_projects()?;
+
+        Ok(sink)
+    }
+
+    // Copies a table created when events were sorted (and so merged) by insert_id into
+    // one sorted by uuid. Events already merged away by the old key stay lost.
+    fn migrate_sorting_key(&self) -> AnyhowResult<()> {
+        let key = self.run_query(
+            "SELECT sorting_key FROM system.tables
+             WHERE database = currentDatabase() AND name = 'amplitude_events'
+             FORMAT TabSeparated",
+            String::new(),
+        )?;
+        if key.trim() != "insert_id" {
+            return Ok(());
+        }
+        for statement in [
+            "DROP TABLE IF EXISTS amplitude_events_by_uuid",
+            "CREATE TABLE amplitude_events_by_uuid AS amplitude_events
+             ENGINE = ReplacingMergeTree(created_at) ORDER BY uuid",
+            "INSERT INTO amplitude_events_by_uuid SELECT * FROM amplitude_events",
+            "RENAME TABLE amplitude_events TO amplitude_events_by_insert_id,
+                          amplitude_events_by_uuid TO amplitude_events",
+            "DROP TABLE amplitude_events_by_insert_id",
+        ] {
+            self.run_query(statement, String::new())?;
+        }
+        Ok(())
+    }
+
+    // Tables created before events were scoped by project get project_id columns, as in
+    // the SQLite sink; files recorded before keep an empty one and count as imported for
+    // every project
+    fn migrate_to_projects(&self) -> AnyhowResult<()> {
+        self.run_query(
+            "ALTER TABLE amplitude_events ADD COLUMN IF NOT EXISTS project_id String DEFAULT ''",
+            String::new(),
+        )?;
+        let scoped = self.run_query(
+            "SELECT count() FROM system.columns
+             WHERE database = currentDatabase() AND table = 'imported_files'
+               AND name = 'project_id'
+             FORMAT TabSeparated",
+            String::new(),
+        )?;
+        if scoped.trim() == "0" {
+            // Without the column in the sorting key, merges would collapse the same file
+            // name imported for two projects
+            self.run_query(
+                "ALTER TABLE imported_files
+                 ADD COLUMN project_id String DEFAULT '',
+                 MODIFY ORDER BY (filename, project_id)",
+                String::new(),
+            )?;
+        }
+        Ok(())
+    }
+
+    // Runs a statement against the ClickHouse HTTP interface, optionally streaming a body after it.
+    // Retried inserts are safe because ReplacingMergeTree collapses the repeated rows.
+    // Statements can refer to the project as {project_id:String}.
+    fn run_query(&self, query: &str, body: String) -> AnyhowResult<String> {
+        let response = self
+            .http
+            .send_retrying(
+                self.client
+                    .post(&self.url)
+                    .query(&[("query", query), ("param_project_id", &self.project_id)])
+                    .body(body),
+            )
+            .map_err(|e| Error::Upload {
+                code: e
+                    .downcast_ref::<reqwest::Error>()
+                    .and_then(|e| e.status())
+                    .map(|status| status.as_u16()),
+                response: format!("{e:#}"),
+            })?;
+
+        Ok(response.text()?)
+    }
+}
+
+impl EventSink for ClickhouseSink {
+    // Reads filenames already processed (recorded in imported_files)
+    fn imported_files(&mut self) -> AnyhowResult<HashSet<String>> {
+        let body = self.run_query(
+            "SELECT DISTINCT filename FROM imported_files
+             WHERE project_id IN ({project_id:String}, '')
+             FORMAT TabSeparated",
+            String::new(),
+        )?;
+        Ok(body.lines().map(|line| line.to_string()).collect())
+    }
+
+    // ClickHouse has no multi-statement transactions; each batch insert is atomic on its own
+    fn begin(&mut self) -> AnyhowResult<()> {
+        self.created_at = Utc::now().format("%Y-%m-%d %H:%M:%S%.6f").to_string();
+        Ok(())
+    }
+
+    // Duplicates collapse asynchronously, so every row sent is reported as inserted
+    fn write_batch(&mut self, items: &[ParsedItem]) -> AnyhowResult<usize> {
+        let mut body = String::new();
+        for item in items {
+            let row = json!({
+                "insert_id": item.insert_id.as_deref().unwrap_or(&item.uuid),
+                "uuid": item.uuid,
+                "user_id": item.user_id,
+                "event_screen": item.screen_name,
+                "server_event": u8::from(item.server_event),
+                "event_time": item.event_time.format("%Y-%m-%d %H:%M:%S%.6f").to_string(),
+                "event_name": item.event_name,
+                "session_id": item.session_id,
+                "raw_json": item.raw_json,
+                "source_file": item.source_file,
+                "created_at": self.created_at,
+                "project_id": self.project_id,
+            });
+            body.push_str(&row.to_string());
+            body.push('\n');
+        }
+        self.run_query("INSERT INTO amplitude_events FORMAT JSONEachRow", body)?;
+        Ok(items.len())
+    }
+
+    fn mark_imported(&mut self, filenames: &[String]) -> AnyhowResult<()> {
+        if filenames.is_empty() {
+            return Ok(());
+        }
+        let body: String = filenames
+            .iter()
+            .map(|filename| {
+                let row = json!({ "project_id": self.project_id, "filename": filename });
+                format!("{row}\n")
+            })
+            .collect();
+        self.run_query(
+            "INSERT INTO imported_files (project_id, filename) FORMAT JSONEachRow",
+            body,
+        )?;
+        Ok(())
+    }
+
+    fn commit(&mut self) -> AnyhowResult<()> {
+        Ok(())
+    }
+
+    // Rows already sent stay and collapse with the retried file's copies on merge
+    fn rollback(&mut self) {}
+}
@@ -0,0 +1,237 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result as AnyhowResult;
+
+use super::EventSink;
+use crate::ParsedItem;
+
+/// Writes each event's raw JSON as one line to any writer (a file or stdout).
+///
+/// There is no persistent bookkeeping, so duplicates are only skipped within a run.
+pub struct JsonlSink<W: Write> {
+    writer: W,
+    seen: HashSet<String>,
+}
+
+impl<W: Write> JsonlSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            seen: HashSet::new(),
+        }
+    }
+}
+
+impl JsonlSink<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl JsonlSink<ChunkedFile> {
+    pub fn create_chunked<P: AsRef<Path>>(path: P, options: &ChunkOptions) -> io::Result<Self> {
+        Ok(Self::new(ChunkedFile::create(path.as_ref(), options)?))
+    }
+}
+
+impl JsonlSink<io::Stdout> {
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl<W: Write> EventSink for JsonlSink<W> {
+    fn imported_files(&mut self) -> AnyhowResult<HashSet<String>> {
+        Ok(HashSet::new())
+    }
+
+    fn begin(&mut self) -> AnyhowResult<()> {
+        Ok(())
+    }
+
+    fn write_batch(&mut self, items: &[ParsedItem]) -> AnyhowResult<usize> {
+        let mut inserted = 0;
+        for item in items {
+            if self.seen.insert(item.uuid.clone()) {
+                writeln!(self.writer, "{}", item.raw_json)?;
+                inserted += 1;
+            }
+        }
+        Ok(inserted)
+    }
+
+    fn mark_imported(&mut self, _filenames: &[String]) -> AnyhowResult<()> {
+        Ok(())
+    }
+
+    fn commit(&mut self) -> AnyhowResult<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    // Lines already written stay; the output has no transactions to undo
+    fn rollback(&mut self) {}
+}
+
+// Limits that split JSONL output into numbered files, so editors and jq can cope
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct ChunkOptions {
+    /// Start a new JSONL output file once one reaches this size, e.g. 100MB
+    #[arg(long, value_parser = parse_size)]
+    pub max_file_size: Option<u64>,
+
+    /// Start a new JSONL output file after this many events
+    #[arg(long)]
+    pub max_events_per_file: Option<u64>,
+}
+
+impl ChunkOptions {
+    pub fn is_enabled(&self) -> bool {
+        self.max_file_size.is_some() || self.max_events_per_file.is_some()
+    }
+}
+
+/// A file that continues in the next numbered file once a limit is reached:
+/// `events.jsonl` becomes `events-00001.jsonl`, `events-00002.jsonl`, ...
+///
+/// Files are only switched between lines, so a line longer than the size
+/// limit still ends up whole in one file.
+pub struct ChunkedFile {
+    base: PathBuf,
+    max_bytes: Option<u64>,
+    max_lines: Option<u64>,
+    index: usize,
+    current: BufWriter<File>,
+    bytes: u64,
+    lines: u64,
+    at_line_start: bool,
+}
+
+impl ChunkedFile {
+    // Starts over at the first chunk, deleting chunks an earlier run left behind
+    pub fn create(base: &Path, options: &ChunkOptions) -> io::Result<Self> {
+        for stale in chunk_paths(base) {
+            fs::remove_file(stale)?;
+        }
+        Ok(Self {
+            base: base.to_path_buf(),
+            max_bytes: options.max_file_size,
+            max_lines: options.max_events_per_file,
+            index: 1,
+            current: BufWriter::new(File::create(chunk_path(base, 1))?),
+            bytes: 0,
+            lines: 0,
+            at_line_start: true,
+        })
+    }
+
+    fn is_full(&self) -> bool {
+        self.max_bytes.is_some_and(|max| self.bytes >= max)
+            || self.max_lines.is_some_and(|max| self.lines >= max)
+    }
+}
+
+impl Write for ChunkedFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.at_line_start && self.is_full() {
+            self.current.flush()?;
+            self.index += 1;
+            self.current = BufWriter::new(File::create(chunk_path(&self.base, self.index))?);
+            self.bytes = 0;
+            self.lines = 0;
+        }
+        let written = self.current.write(buf)?;
+        let written_bytes = &buf[..written];
+        self.bytes += written as u64;
+        self.lines += written_bytes.iter().filter(|&&b| b == b'\n').count() as u64;
+        if let Some(&last) = written_bytes.last() {
+            self.at_line_start = last == b'\n';
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.current.flush()
+    }
+}
+
+// Path of the `index`th chunk of `base`: events.jsonl -> events-00003.jsonl
+fn chunk_path(base: &Path, index: usize) -> PathBuf {
+    let stem = base.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match base.extension() {
+        Some(extension) => format!("{stem}-{index:05}.{}", extension.to_string_lossy()),
+        None => format!("{stem}-{index:05}"),
+    };
+    base.with_file_name(name)
+}
+
+// Chunk files written for `base`, in order
+pub fn chunk_paths(base: &Path) -> Vec<PathBuf> {
+    (1..)
+        .map(|index| chunk_path(base, index))
+        .take_while(|path| fs::metadata(path).is_ok())
+        .collect()
+}
+
+// Parses a byte count with an optional KB, MB or GB suffix (powers of 1024)
+fn parse_size(value: &str) -> Result<u64, String> {
+    let upper = value.trim().to_ascii_uppercase();
+    let (digits, multiplier) = [("GB", 1 << 30), ("MB", 1 << 20), ("KB", 1 << 10), ("B", 1)]
+        .iter()
+        .find_map(|(suffix, multiplier)| Some((upper.strip_suffix(suffix)?, *multiplier)))
+        .unwrap_or((&upper, 1));
+    let count: u64 = digits
+        .trim()
+        .parse()
+        .map_err(|_| format!("{value:?} is not a size like 100MB"))?;
+    count
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("{value:?} is too large a size"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_chunks_split_between_lines() {
+        let dir = tempdir().unwrap();
+        let base = dir.path().join("events.jsonl");
+        let options = ChunkOptions {
+            max_file_size: Some(parse_size("10B").unwrap()),
+            max_events_per_file: Some(2),
+        };
+        let mut file = ChunkedFile::create(&base, &options).unwrap();
+        for line in ["{}", "{}", "{}", "{\"long\": \"line\"}", "{}"] {
+            writeln!(file, "{line}").unwrap();
+        }
+        file.flush().unwrap();
+
+        let chunks = || -> Vec<String> {
+            chunk_paths(&base)
+                .iter()
+                .map(|path| fs::read_to_string(path).unwrap())
+                .collect()
+        };
+        assert_eq!(chunks(), ["{}\n{}\n", "{}\n{\"long\": \"line\"}\n", "{}\n"]);
+
+        // A shorter run leaves none of the earlier chunks behind
+        let mut file = ChunkedFile::create(&base, &options).unwrap();
+        writeln!(file, "{{}}").unwrap();
+        file.flush().unwrap();
+        assert_eq!(chunks(), ["{}\n"]);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("100MB"), Ok(100 << 20));
+        assert_eq!(parse_size(" 2 gb "), Ok(2 << 30));
+        assert_eq!(parse_size("512"), Ok(512));
+        assert!(parse_size("lots").is_err());
+        assert!(parse_size("99999999999999GB").is_err());
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result as AnyhowResult;
+
+use crate::cancel;
+use crate::progress::{self, bump, COUNTERS};
+use crate::{output_name, ParsedItem};
+
+pub mod clickhouse;
+pub mod jsonl;
+pub mod postgres;
+pub mod sqlite;
+
+// Rows handed to a sink per write_batch call
+const BATCH_SIZE: usize = 10_000;
+
+/// A destination for parsed events.
+///
+/// Implementors only deal with storage; batching, import bookkeeping and
+/// reporting are shared through [`write_parsed_items`].
+pub trait EventSink {
+    /// Filenames that were already imported by a previous run.
+    fn imported_files(&mut self) -> AnyhowResult<HashSet<String>>;
+
+    /// Starts a unit of work; nothing written before `commit` needs to be visible.
+    fn begin(&mut self) -> AnyhowResult<()>;
+
+    /// Writes a batch of events, skipping ones the sink already holds.
+    /// Returns how many events were newly stored.
+    fn write_batch(&mut self, items: &[ParsedItem]) -> AnyhowResult<usize>;
+
+    /// Records source files as imported.
+    fn mark_imported(&mut self, filenames: &[String]) -> AnyhowResult<()>;
+
+    /// Makes everything since `begin` durable.
+    fn commit(&mut self) -> AnyhowResult<()>;
+
+    /// Discards everything since `begin` after a failure, so the sink can start the
+    /// next unit of work. Errors are ignored: there may be nothing left to roll back.
+    fn rollback(&mut self);
+
+    /// Records how the data was imported, e.g. the session policy in use.
+    /// Sinks without a place for such settings ignore them.
+    fn record_setting(&mut self, _key: &str, _value: &str) -> AnyhowResult<()> {
+        Ok(())
+    }
+}
+
+// Writes parsed items through a sink in batches and tracks import metadata.
+//
+// Each source file is written in its own transaction, together with its imported_files
+// row, so an interrupted import keeps the files it finished and the next run only
+// redoes the rest. Items of one file are expected next to each other, as parsed.
+pub fn write_parsed_items(
+    sink: &mut dyn EventSink,
+    items: &[ParsedItem],
+    processed_files: &[String],
+) -> AnyhowResult<usize> {
+    let runs: Vec<&[ParsedItem]> = items
+        .chunk_by(|a, b| a.source_file == b.source_file)
+        .collect();
+    let last_run: HashMap<&str, usize> = runs
+        .iter()
+        .enumerate()
+        .map(|(index, run)| (run[0].source_file.as_str(), index))
+        .collect();
+
+    // processed_files holds archive entry names (x.json.gz), items the extracted name (x.json)
+    let mut unmarked: Vec<&String> = processed_files.iter().collect();
+    let mut inserted = 0;
+    for (index, run) in runs.iter().enumerate() {
+        let file = run[0].source_file.as_str();
+        let mut done = Vec::new();
+        if last_run[file] == index {
+            unmarked.retain(|name| {
+                let matches = output_name(name) == file;
+                if matches {
+                    done.push(name.to_string());
+                }
+                !matches
+            });
+        }
+        inserted += write_file(sink, run, &done)?;
+    }
+
+    // Files without any events left, e.g. after --exclude-events
+    if !unmarked.is_empty() {
+        let rest: Vec<String> = unmarked.into_iter().cloned().collect();
+        write_file(sink, &[], &rest)?;
+    }
+
+    progress::info(format!(
+        "Inserted {} new items. Skipped {} duplicates.",
+        inserted,
+        items.len() - inserted
+    ));
+
+    Ok(inserted)
+}
+
+// Writes one source file's items and marks it imported in a single transaction
+fn write_file(
+    sink: &mut dyn EventSink,
+    items: &[ParsedItem],
+    processed_files: &[String],
+) -> AnyhowResult<usize> {
+    // Between files everything written so far is committed, so stopping here is clean
+    cancel::check()?;
+    sink.begin()?;
+
+    let result = write_in_transaction(sink, items, processed_files);
+    if result.is_err() {
+        // Long-running commands keep the sink, and its next begin() would fail otherwise
+        sink.rollback();
+    }
+    result
+}
+
+fn write_in_transaction(
+    sink: &mut dyn EventSink,
+    items: &[ParsedItem],
+    processed_files: &[String],
+) -> AnyhowResult<usize> {
+    let mut inserted = 0;
+    for batch in items.chunks(BATCH_SIZE) {
+        let batch_inserted = sink
+            .write_batch(batch)
+            .inspect_err(|_| bump(&COUNTERS.failed_batches, 1))?;
+        bump(&COUNTERS.events_inserted, batch_inserted as u64);
+        bump(
+            &COUNTERS.duplicates_skipped,
+            (batch.len() - batch_inserted) as u64,
+        );
+        inserted += batch_inserted;
+    }
+    sink.mark_imported(processed_files)?;
+
+    sink.commit()?;
+    Ok(inserted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::parsed_item;
+
+    // Records commits, fails writing any item from `fail_on`, and like a database
+    // refuses to begin while a transaction is open
+    #[derive(Default)]
+    struct RecordingSink {
+        fail_on: &'static str,
+        in_transaction: bool,
+        pending: Vec<String>,
+        committed: Vec<String>,
+    }
+
+    impl EventSink for RecordingSink {
+        fn imported_files(&mut self) -> AnyhowResult<HashSet<String>> {
+            Ok(HashSet::new())
+        }
+        fn begin(&mut self) -> AnyhowResult<()> {
+            anyhow::ensure!(!self.in_transaction, "transaction already open");
+            self.in_transaction = true;
+            Ok(())
+        }
+        fn write_batch(&mut self, items: &[ParsedItem]) -> AnyhowResult<usize> {
+            if items.iter().any(|item| item.source_file == self.fail_on) {
+                anyhow::bail!("disk full");
+            }
+            Ok(items.len())
+        }
+        fn mark_imported(&mut self, filenames: &[String]) -> AnyhowResult<()> {
+            self.pending.extend_from_slice(filenames);
+            Ok(())
+        }
+        fn commit(&mut self) -> AnyhowResult<()> {
+            self.committed.append(&mut self.pending);
+            self.in_transaction = false;
+            Ok(())
+        }
+        fn rollback(&mut self) {
+            self.pending.clear();
+            self.in_transaction = false;
+        }
+    }
+
+    fn event(file: &str) -> ParsedItem {
+        ParsedItem {
+            source_file: file.to_string(),
+            ..parsed_item(file)
+        }
+    }
+
+    #[test]
+    fn test_files_commit_separately() {
+        let items = [event("a.json"), event("a.json"), event("b.json")];
+        let processed = ["a.json.gz", "b.json.gz", "empty.json"].map(String::from);
+
+        let mut sink = RecordingSink::default();
+        assert_eq!(
+            write_parsed_items(&mut sink, &items, &processed).unwrap(),
+            3
+        );
+        assert_eq!(sink.committed, processed);
+
+        let mut failing = RecordingSink {
+            fail_on: "b.json",
+            ..Default::default()
+        };
+        assert!(write_parsed_items(&mut failing, &items, &processed).is_err());
+        assert_eq!(failing.committed, ["a.json.gz"]);
+
+        // The failed file was rolled back, so the same sink takes the next one
+        let retry = [event("c.json")];
+        let processed = ["c.json.gz".to_string()];
+        assert_eq!(
+            write_parsed_items(&mut failing, &retry, &processed).unwrap(),
+            1
+        );
+        assert_eq!(failing.committed, ["a.json.gz", "c.json.gz"]);
+    }
+}
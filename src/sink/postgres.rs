@@ -0,0 +1,202 @@
+use std::collections::HashSet;
+
+use anyhow::Result as AnyhowResult;
+use chrono::Utc;
+use native_tls::TlsConnector;
+use postgres::{Client, Config};
+use postgres_native_tls::MakeTlsConnector;
+
+use super::EventSink;
+use crate::ParsedItem;
+
+pub struct PostgresSink {
+    client: Client,
+    project_id: String,
+}
+
+impl PostgresSink {
+    // Connects and ensures the same tables the SQLite writer maintains exist in Postgres
+    pub fn connect(dsn: &str, project_id: &str) -> AnyhowResult<Self> {
+        let config: Config = dsn.parse()?;
+        // As with libpq, sslmode=prefer (the default) tries TLS and only falls back to
+        // plaintext when the server refuses it, sslmode=require insists on TLS, and
+        // neither checks the certificate, which managed databases often sign themselves
+        let tls = TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .build()?;
+        let mut client = config.connect(MakeTlsConnector::new(tls))?;
+        client.batch_execute(
+            "
+            CREATE TABLE IF NOT EXISTS amplitude_events (
+                uuid TEXT PRIMARY KEY,
+                user_id TEXT,
+                event_screen TEXT,
+                server_event BOOLEAN,
+                event_time TIMESTAMPTZ NOT NULL,
+                event_name TEXT NOT NULL,
+                session_id BIGINT,
+                raw_json TEXT NOT NULL,
+                source_file TEXT NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL,
+                project_id TEXT NOT NULL DEFAULT ''
+            );
+
+            CREATE TABLE IF NOT EXISTS imported_files (
+                project_id TEXT NOT NULL DEFAULT '',
+                filename TEXT NOT NULL,
+                imported_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (project_id, filename)
+            );
+            ",
+        )?;
+        migrate_to_projects(&mut client)?;
+
+        Ok(Self {
+            client,
+            project_id: project_id.to_string(),
+        })
+    }
+}
+
+// Tables created before events were scoped by project get project_id columns, as in
+// the SQLite sink; events and files recorded before keep an empty one, and those files
+// count as imported for every project
+fn migrate_to_projects(client: &mut Client) -> Result<(), postgres::Error> {
+    // Earlier versions of this migration added the events column as nullable
+    let nullable: bool = client
+        .query_one(
+            "SELECT EXISTS (
+                SELECT 1 FROM information_schema.columns
+                WHERE table_schema = current_schema() AND table_name = 'amplitude_events'
+                  AND column_name = 'project_id' AND is_nullable = 'YES'
+            )",
+            &[],
+        )?
+        .get(0);
+    if nullable {
+        client.batch_execute(
+            "
+            UPDATE amplitude_events SET project_id = '' WHERE project_id IS NULL;
+            ALTER TABLE amplitude_events ALTER COLUMN project_id SET DEFAULT '';
+            ALTER TABLE amplitude_events ALTER COLUMN project_id SET NOT NULL;
+            ",
+        )?;
+    }
+
+    let scoped: bool = client
+        .query_one(
+            "SELECT EXISTS (
+                SELECT 1 FROM information_schema.columns
+                WHERE table_schema = current_schema() AND table_name = 'imported_files'
+                  AND column_name = 'project_id'
+            )",
+            &[],
+        )?
+        .get(0);
+    if scoped {
+        return Ok(());
+    }
+    client.batch_execute(
+        "
+        ALTER TABLE amplitude_events ADD COLUMN IF NOT EXISTS project_id TEXT NOT NULL DEFAULT '';
+        ALTER TABLE imported_files ADD COLUMN project_id TEXT NOT NULL DEFAULT '';
+        ALTER TABLE imported_files DROP CONSTRAINT imported_files_pkey;
+        ALTER TABLE imported_files ADD PRIMARY KEY (project_id, filename);
+        ",
+    )
+}
+
+impl EventSink for PostgresSink {
+    // Reads filenames already processed (recorded in imported_files)
+    fn imported_files(&mut self) -> AnyhowResult<HashSet<String>> {
+        let rows = self.client.query(
+            "SELECT filename FROM imported_files WHERE project_id IN ($1, '')",
+            &[&self.project_id],
+        )?;
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    fn begin(&mut self) -> AnyhowResult<()> {
+        self.client.batch_execute("BEGIN")?;
+        Ok(())
+    }
+
+    fn write_batch(&mut self, items: &[ParsedItem]) -> AnyhowResult<usize> {
+        let stmt = self.client.prepare(
+            "INSERT INTO amplitude_events (uuid, user_id, raw_json, source_file, created_at, event_screen, server_event, event_time, event_name, session_id, project_id)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+         ON CONFLICT (uuid) DO NOTHING",
+        )?;
+
+        let mut inserted = 0;
+        for item in items {
+            let rows = self.client.execute(
+                &stmt,
+                &[
+                    &item.uuid,
+                    &item.user_id,
+                    &item.raw_json,
+                    &item.source_file,
+                    &Utc::now(),
+                    &item.screen_name,
+                    &item.server_event,
+                    &item.event_time,
+                    &item.event_name,
+                    &item.session_id,
+                    &self.project_id,
+                ],
+            )?;
+            inserted += rows as usize;
+        }
+        Ok(inserted)
+    }
+
+    fn mark_imported(&mut self, filenames: &[String]) -> AnyhowResult<()> {
+        let stmt = self.client.prepare(
+            "INSERT INTO imported_files (project_id, filename) VALUES ($1, $2)
+             ON CONFLICT (project_id, filename) DO NOTHING",
+        )?;
+        for filename in filenames {
+            self.client.execute(&stmt, &[&self.project_id, filename])?;
+        }
+        Ok(())
+    }
+
+    fn rollback(&mut self) {
+        let _ = self.client.batch_execute("ROLLBACK");
+    }
+
+    fn commit(&mut self) -> AnyhowResult<()> {
+        self.client.batch_execute("COMMIT")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_sslmode_require_starts_a_tls_handshake() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // Agrees to TLS, then reports the first byte the client sends
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut ssl_request = [0; 8];
+            stream.read_exact(&mut ssl_request).unwrap();
+            stream.write_all(b"S").unwrap();
+            let mut record_type = [0; 1];
+            stream.read_exact(&mut record_type).unwrap();
+            record_type[0]
+        });
+
+        let dsn = format!("postgres://user@127.0.0.1:{port}/db?sslmode=require&connect_timeout=5");
+        assert!(PostgresSink::connect(&dsn, "123").is_err());
+        // 0x16 opens a TLS handshake record
+        assert_eq!(server.join().unwrap(), 0x16);
+    }
+}
@@ -0,0 +1,1070 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result as AnyhowResult;
+use chrono::Utc;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde_json::Value;
+
+use super::EventSink;
+use crate::error::Error;
+use crate::export_name::export_hour;
+use crate::report_tz::ReportTimezone;
+use crate::ParsedItem;
+
+/// How the SQLite sink stores each event's original JSON.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RawJson {
+    /// Plain text in amplitude_events.raw_json
+    #[default]
+    Keep,
+    /// zstd-compressed BLOB in amplitude_events.raw_json
+    Compress,
+    /// Not stored; raw_json is left empty
+    Drop,
+    /// Plain text in a separate <db>_raw.sqlite file, attached as `raw`
+    Archive,
+}
+
+/// How the SQLite sink decides an event is already stored.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dedup {
+    /// Same uuid
+    #[default]
+    Uuid,
+    /// Same uuid, or same $insert_id and device_id within 7 days, matching what Amplitude counts
+    Amplitude,
+}
+
+// How far apart events with the same $insert_id and device_id can be for Amplitude to drop one
+const INSERT_ID_WINDOW_DAYS: f64 = 7.0;
+
+// Ready-made perspectives for people who would rather not write the SQL; days start at
+// midnight in --report-timezone
+const ANALYSIS_VIEWS: &str = "
+    CREATE VIEW IF NOT EXISTS daily_event_counts AS
+        SELECT project_id, {day} AS day, event_name,
+               COUNT(*) AS events, COUNT(DISTINCT user_id) AS users
+        FROM amplitude_events
+        GROUP BY project_id, day, event_name;
+
+    CREATE VIEW IF NOT EXISTS events_per_user AS
+        SELECT project_id, user_id, COUNT(*) AS events,
+               COUNT(DISTINCT event_name) AS event_types,
+               COUNT(DISTINCT session_id) AS sessions
+        FROM amplitude_events
+        WHERE user_id IS NOT NULL
+        GROUP BY project_id, user_id;
+
+    CREATE VIEW IF NOT EXISTS first_last_seen AS
+        SELECT project_id, user_id,
+               MIN(event_time) AS first_seen, MAX(event_time) AS last_seen
+        FROM amplitude_events
+        WHERE user_id IS NOT NULL
+        GROUP BY project_id, user_id;
+
+    CREATE VIEW IF NOT EXISTS server_vs_client_events AS
+        SELECT project_id, {day} AS day,
+               SUM(server_event) AS server_events,
+               SUM(1 - server_event) AS client_events
+        FROM amplitude_events
+        GROUP BY project_id, day;
+";
+
+fn analysis_views(timezone: &ReportTimezone) -> String {
+    ANALYSIS_VIEWS.replace("{day}", &timezone.sqlite_date("event_time"))
+}
+
+// Where --raw-json archive keeps raw JSON: amplitude_data.sqlite -> amplitude_data_raw.sqlite
+pub fn raw_archive_path(db_path: &Path) -> PathBuf {
+    db_path.with_file_name(format!(
+        "{}_raw.sqlite",
+        db_path.file_stem().unwrap_or_default().to_string_lossy()
+    ))
+}
+
+pub struct SqliteSink {
+    conn: Connection,
+    project_id: String,
+    raw_json: RawJson,
+    dedup: Dedup,
+    fts: bool,
+    // Export fields stored in columns of their own: (field, column)
+    columns: Vec<(String, String)>,
+}
+
+impl SqliteSink {
+    // Opens the database for one project's events; several projects can share a file
+    pub fn open<P: AsRef<Path>>(db_path: P, project_id: &str, raw_json: RawJson) -> Result<Self> {
+        let db_path = db_path.as_ref();
+        let conn = Connection::open(db_path)?;
+
+        // Ensure required tables exist
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS amplitude_events (
+                uuid TEXT PRIMARY KEY,
+                project_id TEXT NOT NULL DEFAULT '',
+                user_id TEXT,
+                event_screen TEXT,
+                server_event INTEGER,
+                event_time DATETIME NOT NULL,
+                event_name TEXT NOT NULL,
+                session_id INTEGER,
+                raw_json TEXT NOT NULL,
+                source_file TEXT NOT NULL,
+                source_line INTEGER,
+                export_hour DATETIME,
+                insert_id TEXT,
+                device_id TEXT,
+                amplitude_id INTEGER,
+                event_id INTEGER,
+                created_at DATETIME NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS event_groups (
+                project_id TEXT NOT NULL DEFAULT '',
+                event_uuid TEXT NOT NULL,
+                group_type TEXT NOT NULL,
+                group_value TEXT NOT NULL,
+                PRIMARY KEY (project_id, event_uuid, group_type, group_value)
+            );
+
+            CREATE TABLE IF NOT EXISTS group_properties (
+                project_id TEXT NOT NULL DEFAULT '',
+                group_type TEXT NOT NULL,
+                group_value TEXT NOT NULL,
+                properties TEXT NOT NULL,
+                updated_at DATETIME NOT NULL,
+                PRIMARY KEY (project_id, group_type, group_value)
+            );
+
+            CREATE TABLE IF NOT EXISTS imported_files (
+                project_id TEXT NOT NULL DEFAULT '',
+                filename TEXT NOT NULL,
+                export_hour DATETIME,
+                imported_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (project_id, filename)
+            );
+
+            CREATE TABLE IF NOT EXISTS duplicate_events (
+                uuid TEXT NOT NULL,
+                duplicate_of TEXT NOT NULL,
+                project_id TEXT NOT NULL,
+                reason TEXT NOT NULL,
+                source_file TEXT NOT NULL,
+                source_line INTEGER NOT NULL,
+                seen_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS import_settings (
+                project_id TEXT NOT NULL,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (project_id, key)
+            );
+            ",
+        )?;
+        migrate_to_projects(&conn)?;
+        add_missing_columns(&conn)?;
+        // uuid stays the only unique key: Amplitude reuses $insert_id after 7 days and
+        // event_id is a per-device counter, so neither identifies an event on its own
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS amplitude_events_by_project
+                 ON amplitude_events (project_id, event_time);
+             CREATE INDEX IF NOT EXISTS duplicate_events_by_uuid
+                 ON duplicate_events (project_id, uuid);
+             CREATE INDEX IF NOT EXISTS event_groups_by_group
+                 ON event_groups (project_id, group_type, group_value);
+             CREATE INDEX IF NOT EXISTS amplitude_events_by_insert_id
+                 ON amplitude_events (insert_id, device_id) WHERE insert_id IS NOT NULL;
+             CREATE INDEX IF NOT EXISTS amplitude_events_by_amplitude_id
+                 ON amplitude_events (amplitude_id, event_id) WHERE amplitude_id IS NOT NULL;",
+        )?;
+        conn.execute_batch(&analysis_views(&ReportTimezone::default()))?;
+
+        if raw_json == RawJson::Archive {
+            let archive = raw_archive_path(db_path);
+            conn.execute(
+                "ATTACH DATABASE ?1 AS raw",
+                params![archive.to_string_lossy()],
+            )?;
+            conn.execute_batch(
+                "
+                CREATE TABLE IF NOT EXISTS raw.amplitude_raw_json (
+                    uuid TEXT PRIMARY KEY,
+                    raw_json TEXT NOT NULL
+                );
+                ",
+            )?;
+        }
+
+        // An index created by an earlier --enable-fts run is kept complete without the flag
+        let fts = has_table(&conn, "amplitude_events_fts")?;
+        Ok(Self {
+            conn,
+            project_id: project_id.to_string(),
+            raw_json,
+            dedup: Dedup::Uuid,
+            fts,
+            columns: Vec::new(),
+        })
+    }
+
+    pub fn with_dedup(mut self, dedup: Dedup) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    // Creates a full-text index of raw JSON, filled from already stored events; once it
+    // exists every later write keeps it up to date
+    pub fn with_fts(mut self, enabled: bool) -> Result<Self> {
+        if enabled && !self.fts {
+            self.conn.execute_batch(
+                "
+                CREATE VIRTUAL TABLE amplitude_events_fts USING fts5(uuid UNINDEXED, raw_json);
+                INSERT INTO amplitude_events_fts (uuid, raw_json)
+                    SELECT uuid, raw_json FROM amplitude_events
+                    WHERE typeof(raw_json) = 'text' AND raw_json != '';
+                ",
+            )?;
+            self.fts = true;
+        }
+        Ok(self)
+    }
+
+    // Stores the given top-level export fields in amplitude_events columns of their own.
+    // Columns of fields no longer given are dropped and new ones are filled from the
+    // raw JSON already stored; None keeps the selection the database already has.
+    pub fn with_columns(mut self, fields: Option<&[String]>) -> AnyhowResult<Self> {
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS event_columns (
+                field TEXT PRIMARY KEY,
+                column_name TEXT NOT NULL
+            );",
+        )?;
+        let selected = |conn: &Connection| -> Result<Vec<(String, String)>> {
+            conn.prepare("SELECT field, column_name FROM event_columns ORDER BY field")?
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect()
+        };
+
+        if let Some(fields) = fields {
+            let wanted: HashSet<&str> = fields
+                .iter()
+                .map(String::as_str)
+                .filter(|f| !f.is_empty())
+                .collect();
+            check_column_names(&wanted)?;
+            for (field, column) in selected(&self.conn)? {
+                if !wanted.contains(field.as_str()) {
+                    self.conn.execute_batch(&format!(
+                        "ALTER TABLE amplitude_events DROP COLUMN \"{column}\""
+                    ))?;
+                    self.conn
+                        .execute("DELETE FROM event_columns WHERE field = ?1", params![field])?;
+                }
+            }
+            let current = selected(&self.conn)?;
+            let mut added: Vec<&str> = wanted
+                .into_iter()
+                .filter(|field| !current.iter().any(|(f, _)| f == field))
+                .collect();
+            added.sort();
+            for field in added {
+                let column = column_name(field);
+                self.conn.execute_batch(&format!(
+                    "ALTER TABLE amplitude_events ADD COLUMN \"{column}\""
+                ))?;
+                self.conn.execute(
+                    "INSERT INTO event_columns (field, column_name) VALUES (?1, ?2)",
+                    params![field, column],
+                )?;
+                self.backfill_column(field, &column)?;
+            }
+        }
+        self.columns = selected(&self.conn)?;
+        Ok(self)
+    }
+
+    // Fills a newly added column from raw JSON stored inline, compressed or in the archive
+    fn backfill_column(&self, field: &str, column: &str) -> AnyhowResult<()> {
+        let path = format!("$.\"{field}\"");
+        self.conn.execute(
+            &format!(
+                "UPDATE amplitude_events SET \"{column}\" = json_extract(raw_json, ?1)
+                 WHERE typeof(raw_json) = 'text' AND raw_json != ''"
+            ),
+            params![path],
+        )?;
+        if self.raw_json == RawJson::Archive {
+            self.conn.execute(
+                &format!(
+                    "UPDATE amplitude_events SET \"{column}\" = (
+                         SELECT json_extract(r.raw_json, ?1) FROM raw.amplitude_raw_json r
+                         WHERE r.uuid = amplitude_events.uuid)
+                     WHERE raw_json = ''"
+                ),
+                params![path],
+            )?;
+        }
+
+        let mut compressed = self.conn.prepare(
+            "SELECT uuid, raw_json FROM amplitude_events WHERE typeof(raw_json) = 'blob'",
+        )?;
+        let mut update = self.conn.prepare(&format!(
+            "UPDATE amplitude_events SET \"{column}\" = ?1 WHERE uuid = ?2"
+        ))?;
+        let mut rows = compressed.query([])?;
+        while let Some(row) = rows.next()? {
+            let uuid: String = row.get(0)?;
+            let json: Value =
+                serde_json::from_slice(&zstd::decode_all(row.get_ref(1)?.as_blob()?)?)?;
+            update.execute(params![json_to_sql(json.get(field)), uuid])?;
+        }
+        Ok(())
+    }
+
+    // Recreates the analysis views for the time zone and, if asked, adds a virtual
+    // local_date column holding each event's day in it
+    pub fn with_report_timezone(self, timezone: ReportTimezone, local_date: bool) -> Result<Self> {
+        self.conn.execute_batch(
+            "DROP VIEW IF EXISTS daily_event_counts;
+             DROP VIEW IF EXISTS events_per_user;
+             DROP VIEW IF EXISTS first_last_seen;
+             DROP VIEW IF EXISTS server_vs_client_events;",
+        )?;
+
+        let definition = format!(
+            "local_date DATE GENERATED ALWAYS AS ({}) VIRTUAL",
+            timezone.sqlite_date("event_time")
+        );
+        let schema: String = self.conn.query_row(
+            "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'amplitude_events'",
+            [],
+            |row| row.get(0),
+        )?;
+        let up_to_date = schema.contains(&definition);
+        if has_column(&self.conn, "amplitude_events", "local_date")? && !(local_date && up_to_date)
+        {
+            self.conn
+                .execute_batch("ALTER TABLE amplitude_events DROP COLUMN local_date")?;
+        }
+        if local_date && !up_to_date {
+            self.conn.execute_batch(&format!(
+                "ALTER TABLE amplitude_events ADD COLUMN {definition}"
+            ))?;
+        }
+
+        self.conn.execute_batch(&analysis_views(&timezone))?;
+        Ok(self)
+    }
+
+    // Notes where a skipped event came from, next to the stored event's own source_file/source_line
+    fn record_duplicate(&self, item: &ParsedItem, duplicate_of: &str, reason: &str) -> Result<()> {
+        self.conn
+            .prepare_cached(
+                "INSERT INTO duplicate_events (uuid, duplicate_of, project_id, reason, source_file, source_line)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?
+            .execute(params![
+                item.uuid,
+                duplicate_of,
+                self.project_id,
+                reason,
+                item.source_file,
+                item.source_line
+            ])?;
+        Ok(())
+    }
+
+    // The stored event Amplitude would consider this one a resend of, if any; the event
+    // itself when it is stored already
+    fn insert_id_original(&self, item: &ParsedItem) -> Result<Option<String>> {
+        let Some(insert_id) = &item.insert_id else {
+            return Ok(None);
+        };
+        self.conn
+            .prepare_cached(
+                "SELECT uuid FROM amplitude_events
+                 WHERE insert_id = ?1 AND device_id IS ?2 AND project_id = ?3
+                   AND ABS(julianday(event_time) - julianday(?4)) <= ?5
+                 ORDER BY uuid = ?6 DESC
+                 LIMIT 1",
+            )?
+            .query_row(
+                params![
+                    insert_id,
+                    item.device_id,
+                    self.project_id,
+                    item.event_time.to_rfc3339(),
+                    INSERT_ID_WINDOW_DAYS,
+                    item.uuid
+                ],
+                |row| row.get(0),
+            )
+            .optional()
+    }
+
+    // The value stored in amplitude_events.raw_json for an event
+    fn raw_json_value(&self, item: &ParsedItem) -> AnyhowResult<SqlValue> {
+        Ok(match self.raw_json {
+            RawJson::Keep => SqlValue::Text(item.raw_json.clone()),
+            RawJson::Compress => SqlValue::Blob(zstd::encode_all(item.raw_json.as_bytes(), 3)?),
+            RawJson::Drop | RawJson::Archive => SqlValue::Text(String::new()),
+        })
+    }
+
+    // Fills the columns chosen with --columns for a newly inserted event
+    fn write_columns(&self, item: &ParsedItem) -> AnyhowResult<()> {
+        if self.columns.is_empty() {
+            return Ok(());
+        }
+        let json: Value = serde_json::from_str(&item.raw_json)?;
+        let assignments: Vec<String> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, (_, column))| format!("\"{column}\" = ?{}", i + 1))
+            .collect();
+        let mut values: Vec<SqlValue> = self
+            .columns
+            .iter()
+            .map(|(field, _)| json_to_sql(json.get(field)))
+            .collect();
+        values.push(SqlValue::Text(item.uuid.clone()));
+        self.conn
+            .prepare_cached(&format!(
+                "UPDATE amplitude_events SET {} WHERE uuid = ?{}",
+                assignments.join(", "),
+                values.len()
+            ))?
+            .execute(rusqlite::params_from_iter(values))?;
+        Ok(())
+    }
+
+    // Records the groups an event belongs to and the latest known properties of each group
+    fn write_groups(&self, item: &ParsedItem) -> AnyhowResult<()> {
+        if !item.raw_json.contains("\"groups\"") {
+            return Ok(());
+        }
+        let json: Value = serde_json::from_str(&item.raw_json)?;
+        let groups = group_memberships(&json);
+        if groups.is_empty() {
+            return Ok(());
+        }
+
+        let mut membership = self.conn.prepare_cached(
+            "INSERT OR IGNORE INTO event_groups (project_id, event_uuid, group_type, group_value)
+             VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for (group_type, group_value) in &groups {
+            membership.execute(params![self.project_id, item.uuid, group_type, group_value])?;
+        }
+
+        let mut properties = self.conn.prepare_cached(
+            "INSERT INTO group_properties (project_id, group_type, group_value, properties, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT (project_id, group_type, group_value) DO UPDATE SET properties = ?4, updated_at = ?5
+             WHERE updated_at <= ?5",
+        )?;
+        for (group_type, group_value, props) in group_properties(&json, &groups) {
+            properties.execute(params![
+                self.project_id,
+                group_type,
+                group_value,
+                props.to_string(),
+                item.event_time.to_rfc3339(),
+            ])?;
+        }
+        Ok(())
+    }
+}
+
+// Adds the project columns to databases created before several projects could share one.
+// Events stored before then get an empty project_id, like the other tables' rows.
+fn migrate_to_projects(conn: &Connection) -> Result<()> {
+    if !has_column(conn, "amplitude_events", "project_id")? {
+        conn.execute_batch(
+            "ALTER TABLE amplitude_events ADD COLUMN project_id TEXT NOT NULL DEFAULT '';",
+        )?;
+    }
+    // Earlier versions of this migration added the column as nullable
+    conn.execute(
+        "UPDATE amplitude_events SET project_id = '' WHERE project_id IS NULL",
+        [],
+    )?;
+    if !has_column(conn, "imported_files", "project_id")? {
+        // The primary key changes, so the table has to be rebuilt
+        conn.execute_batch(
+            "
+            BEGIN;
+            ALTER TABLE imported_files RENAME TO imported_files_unscoped;
+            CREATE TABLE imported_files (
+                project_id TEXT NOT NULL DEFAULT '',
+                filename TEXT NOT NULL,
+                imported_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (project_id, filename)
+            );
+            INSERT INTO imported_files (filename, imported_at)
+                SELECT filename, imported_at FROM imported_files_unscoped;
+            DROP TABLE imported_files_unscoped;
+            COMMIT;
+            ",
+        )?;
+    }
+    if !has_column(conn, "event_groups", "project_id")? {
+        // Memberships take the project of their event
+        conn.execute_batch(
+            "
+            BEGIN;
+            DROP INDEX IF EXISTS event_groups_by_group;
+            ALTER TABLE event_groups RENAME TO event_groups_unscoped;
+            CREATE TABLE event_groups (
+                project_id TEXT NOT NULL DEFAULT '',
+                event_uuid TEXT NOT NULL,
+                group_type TEXT NOT NULL,
+                group_value TEXT NOT NULL,
+                PRIMARY KEY (project_id, event_uuid, group_type, group_value)
+            );
+            INSERT INTO event_groups (project_id, event_uuid, group_type, group_value)
+                SELECT COALESCE(e.project_id, ''), g.event_uuid, g.group_type, g.group_value
+                FROM event_groups_unscoped g
+                LEFT JOIN amplitude_events e ON e.uuid = g.event_uuid;
+            DROP TABLE event_groups_unscoped;
+            COMMIT;
+            ",
+        )?;
+    }
+    if !has_column(conn, "group_properties", "project_id")? {
+        conn.execute_batch(
+            "
+            BEGIN;
+            ALTER TABLE group_properties RENAME TO group_properties_unscoped;
+            CREATE TABLE group_properties (
+                project_id TEXT NOT NULL DEFAULT '',
+                group_type TEXT NOT NULL,
+                group_value TEXT NOT NULL,
+                properties TEXT NOT NULL,
+                updated_at DATETIME NOT NULL,
+                PRIMARY KEY (project_id, group_type, group_value)
+            );
+            INSERT INTO group_properties (group_type, group_value, properties, updated_at)
+                SELECT group_type, group_value, properties, updated_at FROM group_properties_unscoped;
+            DROP TABLE group_properties_unscoped;
+            COMMIT;
+            ",
+        )?;
+    }
+    Ok(())
+}
+
+// Columns added after the first release: (table, column, type)
+const ADDED_COLUMNS: [(&str, &str, &str); 7] = [
+    ("amplitude_events", "export_hour", "DATETIME"),
+    ("imported_files", "export_hour", "DATETIME"),
+    ("amplitude_events", "insert_id", "TEXT"),
+    ("amplitude_events", "device_id", "TEXT"),
+    ("amplitude_events", "source_line", "INTEGER"),
+    ("amplitude_events", "amplitude_id", "INTEGER"),
+    ("amplitude_events", "event_id", "INTEGER"),
+];
+
+// Brings databases created by older versions up to the current columns
+fn add_missing_columns(conn: &Connection) -> Result<()> {
+    for (table, column, column_type) in ADDED_COLUMNS {
+        if !has_column(conn, table, column)? {
+            conn.execute_batch(&format!(
+                "ALTER TABLE {table} ADD COLUMN {column} {column_type};"
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+// Columns amplitude_events always has, which --columns cannot add again
+const BUILT_IN_COLUMNS: [&str; 18] = [
+    "uuid",
+    "project_id",
+    "user_id",
+    "event_screen",
+    "server_event",
+    "event_time",
+    "event_name",
+    "session_id",
+    "raw_json",
+    "source_file",
+    "source_line",
+    "export_hour",
+    "insert_id",
+    "device_id",
+    "amplitude_id",
+    "event_id",
+    "created_at",
+    "local_date",
+];
+
+// Column for an export field: $insert_key -> insert_key
+fn column_name(field: &str) -> String {
+    field.trim_start_matches('$').to_string()
+}
+
+// Fails when two fields would share a column, like $os and os, or os and OS, which
+// SQLite column names do not tell apart
+fn check_column_names(fields: &HashSet<&str>) -> std::result::Result<(), Error> {
+    let mut fields: Vec<&str> = fields.iter().copied().collect();
+    fields.sort();
+    let mut columns: HashMap<String, &str> = HashMap::new();
+    for field in fields {
+        let column = column_name(field).to_ascii_lowercase();
+        if let Some(other) = columns.insert(column, field) {
+            return Err(Error::Config(format!(
+                "--columns {other} and {field} would both be stored in column {}",
+                column_name(field)
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Checks a --columns entry: a top-level export field whose column name is plain
+// letters, digits and underscores and not already taken
+pub fn parse_column_field(field: &str) -> std::result::Result<String, String> {
+    if field.is_empty() {
+        return Ok(String::new());
+    }
+    let column = column_name(field);
+    if column.is_empty()
+        || column.starts_with(|c: char| c.is_ascii_digit())
+        || !column
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(format!("{field:?} is not a top-level export field name"));
+    }
+    if BUILT_IN_COLUMNS.contains(&column.to_ascii_lowercase().as_str()) {
+        return Err(format!("{column} is always stored in its own column"));
+    }
+    Ok(field.to_string())
+}
+
+// A JSON value as SQLite stores it, matching json_extract: objects and arrays as JSON text
+fn json_to_sql(value: Option<&Value>) -> SqlValue {
+    match value {
+        None | Some(Value::Null) => SqlValue::Null,
+        Some(Value::Bool(b)) => SqlValue::Integer(i64::from(*b)),
+        Some(Value::Number(n)) => n
+            .as_i64()
+            .map(SqlValue::Integer)
+            .unwrap_or_else(|| SqlValue::Real(n.as_f64().unwrap_or_default())),
+        Some(Value::String(s)) => SqlValue::Text(s.clone()),
+        Some(other) => SqlValue::Text(other.to_string()),
+    }
+}
+
+pub fn has_table(conn: &Connection, table: &str) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        params![table],
+        |row| row.get(0),
+    )
+}
+
+pub fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pragma_table_xinfo(?1) WHERE name = ?2)",
+        params![table, column],
+        |row| row.get(0),
+    )
+}
+
+// (group type, group value) pairs from an event's `groups`, which maps each type to a name or a list of names
+fn group_memberships(json: &Value) -> Vec<(String, String)> {
+    let Some(Value::Object(groups)) = json.get("groups") else {
+        return Vec::new();
+    };
+    let mut memberships = Vec::new();
+    for (group_type, value) in groups {
+        let values = match value {
+            Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            match value {
+                Value::String(name) => memberships.push((group_type.clone(), name.clone())),
+                Value::Null => {}
+                other => memberships.push((group_type.clone(), other.to_string())),
+            }
+        }
+    }
+    memberships
+}
+
+// Properties per group from `group_properties`, keyed either by type and group name,
+// or by type alone when they describe the event's group(s) of that type
+fn group_properties<'a>(
+    json: &'a Value,
+    groups: &'a [(String, String)],
+) -> Vec<(&'a str, &'a str, &'a Value)> {
+    let Some(Value::Object(by_type)) = json.get("group_properties") else {
+        return Vec::new();
+    };
+    let mut result = Vec::new();
+    for (group_type, value) in by_type {
+        let Value::Object(fields) = value else {
+            continue;
+        };
+        let members = groups.iter().filter(|(t, _)| t == group_type);
+        let nested_by_name = members.clone().any(|(_, name)| fields.contains_key(name));
+        for (_, name) in members {
+            match (nested_by_name, fields.get(name)) {
+                (true, Some(props @ Value::Object(_))) => {
+                    result.push((group_type.as_str(), name.as_str(), props))
+                }
+                (true, _) => {}
+                (false, _) if !fields.is_empty() => {
+                    result.push((group_type.as_str(), name.as_str(), value))
+                }
+                (false, _) => {}
+            }
+        }
+    }
+    result
+}
+
+impl EventSink for SqliteSink {
+    // Reads filenames already processed (recorded in imported_files)
+    fn imported_files(&mut self) -> AnyhowResult<HashSet<String>> {
+        // Files imported before databases were shared between projects count for every project
+        let mut stmt = self
+            .conn
+            .prepare("SELECT filename FROM imported_files WHERE project_id IN (?1, '')")?;
+        let rows = stmt.query_map(params![self.project_id], |row| row.get(0))?;
+
+        let mut set = HashSet::new();
+        for filename in rows {
+            set.insert(filename?);
+        }
+        Ok(set)
+    }
+
+    fn begin(&mut self) -> AnyhowResult<()> {
+        self.conn.execute_batch("BEGIN")?;
+        Ok(())
+    }
+
+    fn write_batch(&mut self, items: &[ParsedItem]) -> AnyhowResult<usize> {
+        let mut stmt = self.conn.prepare_cached(
+            "INSERT OR IGNORE INTO amplitude_events (uuid, user_id, raw_json, source_file, created_at, event_screen, server_event, event_time, event_name, session_id, project_id, export_hour, insert_id, device_id, source_line, amplitude_id, event_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+        )?;
+
+        let mut inserted = 0;
+        for item in items {
+            if self.dedup == Dedup::Amplitude {
+                if let Some(original) = self.insert_id_original(item)? {
+                    let reason = if original == item.uuid {
+                        "uuid"
+                    } else {
+                        "insert_id"
+                    };
+                    self.record_duplicate(item, &original, reason)?;
+                    continue;
+                }
+            }
+            let rows = stmt.execute(params![
+                item.uuid,
+                item.user_id.as_deref(),
+                self.raw_json_value(item)?,
+                item.source_file,
+                Utc::now().to_rfc3339(),
+                item.screen_name,
+                if item.server_event { 1 } else { 0 },
+                item.event_time.to_rfc3339(),
+                item.event_name,
+                item.session_id,
+                self.project_id,
+                export_hour(&item.source_file),
+                item.insert_id,
+                item.device_id,
+                item.source_line,
+                item.amplitude_id,
+                item.event_id,
+            ])?;
+            if rows > 0 {
+                self.write_groups(item)?;
+                self.write_columns(item)?;
+                if self.fts {
+                    self.conn
+                        .prepare_cached(
+                            "INSERT INTO amplitude_events_fts (uuid, raw_json) VALUES (?1, ?2)",
+                        )?
+                        .execute(params![item.uuid, item.raw_json])?;
+                }
+                if self.raw_json == RawJson::Archive {
+                    self.conn
+                        .prepare_cached(
+                            "INSERT OR IGNORE INTO raw.amplitude_raw_json (uuid, raw_json) VALUES (?1, ?2)",
+                        )?
+                        .execute(params![item.uuid, item.raw_json])?;
+                }
+            } else {
+                self.record_duplicate(item, &item.uuid, "uuid")?;
+            }
+            inserted += rows;
+        }
+        Ok(inserted)
+    }
+
+    fn mark_imported(&mut self, filenames: &[String]) -> AnyhowResult<()> {
+        let mut stmt = self.conn.prepare_cached(
+            "INSERT OR IGNORE INTO imported_files (project_id, filename, export_hour) VALUES (?1, ?2, ?3)",
+        )?;
+        for filename in filenames {
+            stmt.execute(params![self.project_id, filename, export_hour(filename)])?;
+        }
+        Ok(())
+    }
+
+    fn rollback(&mut self) {
+        let _ = self.conn.execute_batch("ROLLBACK");
+    }
+
+    fn commit(&mut self) -> AnyhowResult<()> {
+        self.conn.execute_batch("COMMIT")?;
+        Ok(())
+    }
+
+    fn record_setting(&mut self, key: &str, value: &str) -> AnyhowResult<()> {
+        self.conn.execute(
+            "INSERT INTO import_settings (project_id, key, value) VALUES (?1, ?2, ?3)
+             ON CONFLICT (project_id, key) DO UPDATE SET value = ?3, updated_at = CURRENT_TIMESTAMP",
+            params![self.project_id, key, value],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::parsed_item;
+    use serde_json::json;
+
+    #[test]
+    fn test_group_properties_by_type_or_by_name() {
+        let json = json!({
+            "groups": { "company": ["Acme", "Initech"], "team": "core" },
+            "group_properties": {
+                "company": { "Acme": { "plan": "pro" } },
+                "team": { "size": 4 }
+            }
+        });
+        let groups = group_memberships(&json);
+        assert_eq!(groups.len(), 3);
+
+        let props = group_properties(&json, &groups);
+        assert_eq!(
+            props,
+            [
+                ("company", "Acme", &json!({ "plan": "pro" })),
+                ("team", "core", &json!({ "size": 4 })),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_amplitude_dedup_by_insert_id_within_seven_days() {
+        use chrono::TimeZone;
+
+        let event = |uuid: &str, day: u32| ParsedItem {
+            user_id: Some("u1".to_string()),
+            device_id: Some("d1".to_string()),
+            event_time: Utc.with_ymd_and_hms(2024, 1, day, 12, 0, 0).unwrap(),
+            insert_id: Some("insert-1".to_string()),
+            ..parsed_item(uuid)
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut sink = SqliteSink::open(dir.path().join("test.sqlite"), "123", RawJson::Keep)
+            .unwrap()
+            .with_dedup(Dedup::Amplitude);
+        let inserted = sink
+            .write_batch(&[event("a", 1), event("b", 5), event("c", 20)])
+            .unwrap();
+        assert_eq!(inserted, 2);
+        // The same event again matches by uuid before its $insert_id
+        assert_eq!(sink.write_batch(&[event("a", 1)]).unwrap(), 0);
+
+        let lineage: Vec<(String, String, String)> = sink
+            .conn
+            .prepare("SELECT uuid, duplicate_of, reason FROM duplicate_events ORDER BY rowid")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect();
+        let row = |uuid: &str, of: &str, reason: &str| (uuid.into(), of.into(), reason.into());
+        assert_eq!(lineage, [row("b", "a", "insert_id"), row("a", "a", "uuid")]);
+    }
+
+    #[test]
+    fn test_column_selection_migrates_existing_events() {
+        let event = |uuid: &str, country: &str| ParsedItem {
+            raw_json: json!({ "country": country, "paying": true }).to_string(),
+            ..parsed_item(uuid)
+        };
+        let column = |sink: &SqliteSink, name: &str| -> Vec<SqlValue> {
+            sink.conn
+                .prepare(&format!(
+                    "SELECT {name} FROM amplitude_events ORDER BY uuid"
+                ))
+                .unwrap()
+                .query_map([], |row| row.get(0))
+                .unwrap()
+                .map(|value| value.unwrap())
+                .collect()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("test.sqlite");
+        let open = |fields: Option<&[String]>| {
+            SqliteSink::open(&db, "123", RawJson::Compress)
+                .unwrap()
+                .with_columns(fields)
+                .unwrap()
+        };
+
+        let mut sink = open(None);
+        sink.write_batch(&[event("a", "NO")]).unwrap();
+
+        // Added columns are filled for stored events, here from compressed raw JSON
+        let mut sink = open(Some(&["country".into(), "paying".into()]));
+        sink.write_batch(&[event("b", "SE")]).unwrap();
+        assert_eq!(
+            column(&sink, "country"),
+            [SqlValue::Text("NO".into()), SqlValue::Text("SE".into())]
+        );
+        assert_eq!(
+            column(&sink, "paying"),
+            [SqlValue::Integer(1), SqlValue::Integer(1)]
+        );
+
+        // Reopening without a selection keeps it; a new selection drops the rest
+        assert_eq!(open(None).columns.len(), 2);
+        let sink = open(Some(&["country".into()]));
+        assert!(!has_column(&sink.conn, "amplitude_events", "paying").unwrap());
+
+        assert!(parse_column_field("$insert_key").is_ok());
+        assert!(SqliteSink::open(&db, "123", RawJson::Compress)
+            .unwrap()
+            .with_columns(Some(&["$os".into(), "os".into()]))
+            .is_err());
+        assert!(parse_column_field("user_id").is_err());
+        assert!(parse_column_field("a b").is_err());
+    }
+
+    #[test]
+    fn test_group_tables_migrate_to_projects() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("test.sqlite");
+        SqliteSink::open(&db, "123", RawJson::Keep)
+            .unwrap()
+            .write_batch(&[parsed_item("a")])
+            .unwrap();
+        // Group tables as created before they were scoped by project
+        Connection::open(&db)
+            .unwrap()
+            .execute_batch(
+                "
+                DROP TABLE event_groups;
+                DROP TABLE group_properties;
+                CREATE TABLE event_groups (
+                    event_uuid TEXT NOT NULL, group_type TEXT NOT NULL, group_value TEXT NOT NULL,
+                    PRIMARY KEY (event_uuid, group_type, group_value)
+                );
+                CREATE INDEX event_groups_by_group ON event_groups (group_type, group_value);
+                CREATE TABLE group_properties (
+                    group_type TEXT NOT NULL, group_value TEXT NOT NULL, properties TEXT NOT NULL,
+                    updated_at DATETIME NOT NULL, PRIMARY KEY (group_type, group_value)
+                );
+                INSERT INTO event_groups VALUES ('a', 'org', 'acme');
+                INSERT INTO group_properties VALUES ('org', 'acme', '{}', '2025-01-01');
+                ",
+            )
+            .unwrap();
+
+        let sink = SqliteSink::open(&db, "456", RawJson::Keep).unwrap();
+        let project = |table: &str| -> String {
+            sink.conn
+                .query_row(&format!("SELECT project_id FROM {table}"), [], |row| {
+                    row.get(0)
+                })
+                .unwrap()
+        };
+        assert_eq!(project("event_groups"), "123");
+        assert_eq!(project("group_properties"), "");
+    }
+
+    #[test]
+    fn test_events_without_project_get_an_empty_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("test.sqlite");
+        // Events table as an earlier migration left it, with a nullable project_id
+        Connection::open(&db)
+            .unwrap()
+            .execute_batch(
+                "
+                CREATE TABLE amplitude_events (
+                    uuid TEXT PRIMARY KEY, user_id TEXT, event_screen TEXT,
+                    server_event INTEGER, event_time DATETIME NOT NULL,
+                    event_name TEXT NOT NULL, session_id INTEGER, raw_json TEXT NOT NULL,
+                    source_file TEXT NOT NULL, created_at DATETIME NOT NULL, project_id TEXT
+                );
+                INSERT INTO amplitude_events (uuid, event_time, event_name, raw_json, source_file, created_at)
+                    VALUES ('a', '2025-01-01', 'e', '{}', 'f.json', '2025-01-01');
+                ",
+            )
+            .unwrap();
+
+        let sink = SqliteSink::open(&db, "123", RawJson::Keep).unwrap();
+        let project: String = sink
+            .conn
+            .query_row("SELECT project_id FROM amplitude_events", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(project, "");
+    }
+
+    #[test]
+    fn test_fts_index_follows_writes_without_the_flag() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("test.sqlite");
+        let write = |uuid: &str, fts: bool| {
+            let mut sink = SqliteSink::open(&db, "123", RawJson::Keep)
+                .and_then(|sink| sink.with_fts(fts))
+                .unwrap();
+            sink.write_batch(&[ParsedItem {
+                raw_json: format!(r#"{{"order": "{uuid}"}}"#),
+                ..parsed_item(uuid)
+            }])
+            .unwrap();
+            sink
+        };
+        write("a", false);
+        write("b", true);
+        let sink = write("c", false);
+
+        let indexed: Vec<String> = sink
+            .conn
+            .prepare("SELECT uuid FROM amplitude_events_fts ORDER BY uuid")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(indexed, ["a", "b", "c"]);
+    }
+}
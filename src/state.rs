@@ -0,0 +1,228 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result as AnyhowResult};
+use chrono::Utc;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::error::Error;
+use crate::fs_util;
+use crate::progress;
+use crate::sink::sqlite::raw_archive_path;
+
+// Bumped when a backup's layout changes; restore refuses backups from a newer format
+const FORMAT: u32 = 1;
+const STAMP: &str = "backup.json";
+const EVENTS_ENTRY: &str = "events.sqlite";
+const RAW_ENTRY: &str = "raw.sqlite";
+
+#[derive(clap::Subcommand, Debug)]
+pub enum StateCommand {
+    /// Snapshot the database (events, imported files, watermarks, download manifest)
+    /// and its raw JSON archive into one zip file
+    Backup {
+        /// Backup file to write
+        #[arg(long)]
+        out: PathBuf,
+
+        /// SQLite database to back up, relative to --workdir unless absolute
+        /// [default: --db-name]
+        #[arg(long)]
+        db: Option<PathBuf>,
+    },
+    /// Put a backup's database and raw JSON archive back in place
+    Restore {
+        /// Backup file written by `state backup`
+        #[arg(long)]
+        from: PathBuf,
+
+        /// Where the database is restored to, relative to --workdir unless absolute
+        /// [default: --db-name]
+        #[arg(long)]
+        db: Option<PathBuf>,
+
+        /// Replace an existing database (and drop a raw archive the backup lacks)
+        #[arg(long)]
+        overwrite: bool,
+    },
+}
+
+// Written into every backup so restores can tell what they are reading
+#[derive(Debug, Serialize, Deserialize)]
+struct Stamp {
+    format: u32,
+    version: String,
+    created_at: String,
+    raw_archive: bool,
+}
+
+impl StateCommand {
+    pub fn db(&self) -> Option<&Path> {
+        match self {
+            StateCommand::Backup { db, .. } | StateCommand::Restore { db, .. } => db.as_deref(),
+        }
+    }
+}
+
+// `db` is the command's --db resolved against the layout
+pub fn run(command: &StateCommand, db: &Path) -> AnyhowResult<()> {
+    match command {
+        StateCommand::Backup { out, .. } => backup(db, out),
+        StateCommand::Restore {
+            from, overwrite, ..
+        } => restore(from, db, *overwrite),
+    }
+}
+
+// Snapshots with VACUUM INTO, which is consistent even while another process reads
+fn backup(db: &Path, out: &Path) -> AnyhowResult<()> {
+    if !db.exists() {
+        bail!("{} does not exist", db.display());
+    }
+    let staging = tempfile::tempdir()?;
+    let raw = raw_archive_path(db);
+    let mut entries = vec![(EVENTS_ENTRY, db.to_path_buf())];
+    if raw.exists() {
+        entries.push((RAW_ENTRY, raw));
+    }
+
+    let stamp = Stamp {
+        format: FORMAT,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: Utc::now().to_rfc3339(),
+        raw_archive: entries.len() > 1,
+    };
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .large_file(true);
+    // Written beside `out` and renamed into place once complete
+    let mut zip = ZipWriter::new(BufWriter::new(fs_util::temp_file_for(out)?));
+    zip.start_file(STAMP, options)?;
+    serde_json::to_writer_pretty(&mut zip, &stamp)?;
+
+    for (entry, path) in entries {
+        let snapshot = staging.path().join(entry);
+        Connection::open(&path)?.execute("VACUUM INTO ?1", params![snapshot.to_string_lossy()])?;
+        zip.start_file(entry, options)?;
+        io::copy(&mut BufReader::new(File::open(&snapshot)?), &mut zip)?;
+        progress::info(format!("Backed up {}", path.display()));
+    }
+    let file = zip.finish()?.into_inner().map_err(|e| e.into_error())?;
+    fs_util::persist(file, out)?;
+
+    println!(
+        "Wrote {} ({} bytes)",
+        out.display(),
+        fs::metadata(out)?.len()
+    );
+    Ok(())
+}
+
+fn restore(from: &Path, db: &Path, overwrite: bool) -> AnyhowResult<()> {
+    let mut zip = ZipArchive::new(BufReader::new(File::open(from)?))
+        .with_context(|| format!("{} is not a state backup", from.display()))?;
+    let stamp: Stamp = serde_json::from_reader(
+        zip.by_name(STAMP)
+            .with_context(|| format!("{} has no {STAMP}", from.display()))?,
+    )?;
+    if stamp.format > FORMAT {
+        return Err(Error::Config(format!(
+            "{} was written by version {} in backup format {}; this version reads up to format {FORMAT}",
+            from.display(),
+            stamp.version,
+            stamp.format
+        ))
+        .into());
+    }
+
+    let raw = raw_archive_path(db);
+    if !overwrite && (db.exists() || raw.exists()) {
+        return Err(Error::Config(format!(
+            "{} already exists; pass --overwrite to replace it",
+            db.display()
+        ))
+        .into());
+    }
+
+    let mut targets = vec![(EVENTS_ENTRY, db.to_path_buf())];
+    if stamp.raw_archive {
+        targets.push((RAW_ENTRY, raw.clone()));
+    }
+    // Extract and check everything before replacing anything
+    let mut restored = Vec::new();
+    for (entry, path) in targets {
+        let mut file = fs_util::temp_file_for(&path)?;
+        io::copy(&mut zip.by_name(entry)?, &mut file)?;
+        check_integrity(file.path()).with_context(|| format!("{entry} in {}", from.display()))?;
+        restored.push((file, path));
+    }
+
+    for (file, path) in restored {
+        // A leftover write-ahead log would be replayed onto the restored file
+        for suffix in ["-wal", "-shm"] {
+            let mut sidecar = path.clone().into_os_string();
+            sidecar.push(suffix);
+            if Path::new(&sidecar).exists() {
+                fs::remove_file(&sidecar)?;
+            }
+        }
+        fs_util::persist(file, &path)?;
+        progress::info(format!("Restored {}", path.display()));
+    }
+    if !stamp.raw_archive && raw.exists() {
+        fs::remove_file(&raw)?;
+    }
+    println!(
+        "Restored backup from {} (version {})",
+        stamp.created_at, stamp.version
+    );
+    Ok(())
+}
+
+fn check_integrity(path: &Path) -> AnyhowResult<()> {
+    let result: String =
+        Connection::open(path)?.query_row("PRAGMA quick_check", [], |row| row.get(0))?;
+    if result != "ok" {
+        bail!("integrity check failed: {result}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_backup_restores_elsewhere() {
+        let source = tempdir().unwrap();
+        let db = source.path().join("amplitude_data.sqlite");
+        Connection::open(&db)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE sync_watermarks (project_id TEXT, synced_through TEXT);
+                 INSERT INTO sync_watermarks VALUES ('1', '20250131T23');",
+            )
+            .unwrap();
+        let backup_file = source.path().join("state.zip");
+        backup(&db, &backup_file).unwrap();
+
+        let target = tempdir().unwrap();
+        let restored = target.path().join("amplitude_data.sqlite");
+        restore(&backup_file, &restored, false).unwrap();
+        let watermark: String = Connection::open(&restored)
+            .unwrap()
+            .query_row("SELECT synced_through FROM sync_watermarks", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(watermark, "20250131T23");
+
+        assert!(restore(&backup_file, &restored, false).is_err());
+        restore(&backup_file, &restored, true).unwrap();
+    }
+}
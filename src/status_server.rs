@@ -0,0 +1,104 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// A response produced by a route: status code, content type and body.
+pub type Response = (u16, &'static str, String);
+
+// How long a client may take to send its request, or to read the response
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// Most of a request line and headers read before giving up on a client
+const MAX_HEAD_BYTES: u64 = 64 * 1024;
+
+// Serves GET requests on a background thread, answering each path with `route`.
+// Deliberately tiny: one request per connection, no keep-alive, no request bodies.
+pub fn spawn(addr: SocketAddr, route: fn(&str) -> Option<Response>) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    thread::Builder::new()
+        .name("status-server".into())
+        .spawn(move || serve(listener, route))?;
+    Ok(())
+}
+
+// Like `spawn`, but on the calling thread and forever. `route` gets the path with
+// its query string. Each connection gets its own thread, so a slow client or a slow
+// query does not hold up /health.
+pub fn serve(
+    listener: TcpListener,
+    route: impl Fn(&str) -> Option<Response> + Send + Sync + 'static,
+) {
+    let route = Arc::new(route);
+    for stream in listener.incoming().flatten() {
+        let route = Arc::clone(&route);
+        // A misbehaving client must not take the server down
+        let _ = thread::Builder::new()
+            .name("status-request".into())
+            .spawn(move || handle(stream, &*route));
+    }
+}
+
+fn handle(mut stream: TcpStream, route: &impl Fn(&str) -> Option<Response>) -> io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new((&stream).take(MAX_HEAD_BYTES));
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Read the headers up to the blank line ending them; closing the connection with
+    // unread input makes some clients see a reset instead of the response
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim_end().is_empty() {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(path)) => {
+            route(path).unwrap_or_else(|| (404, "text/plain", "not found\n".to_string()))
+        }
+        _ => (405, "text/plain", "method not allowed\n".to_string()),
+    };
+
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idle_connection_does_not_block_others() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || serve(listener, |_| Some((200, "text/plain", "ok\n".into()))));
+
+        // Connected but never sends its request
+        let _idle = TcpStream::connect(addr).unwrap();
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        write!(client, "GET /health HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        assert!(response.ends_with("\r\n\r\nok\n"), "{response}");
+    }
+}
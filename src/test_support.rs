@@ -0,0 +1,89 @@
+// Fixtures for tests that run events through the pipeline: this crate's own tests, and
+// with the `test_support` feature, integration tests of crates embedding the library
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{TimeZone, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde_json::{json, Value};
+
+use crate::ParsedItem;
+
+/// A client-side event line as it appears in an Amplitude export, with the fields the
+/// parser requires. `event_time` uses the export format, e.g. `2024-01-01 12:00:00.000000`;
+/// add or override fields on the returned object as a test needs.
+pub fn export_event(uuid: &str, event_type: &str, event_time: &str) -> Value {
+    json!({
+        "uuid": uuid,
+        "event_type": event_type,
+        "event_time": event_time,
+        "data": { "path": "/" },
+    })
+}
+
+/// Export lines for `events`, one JSON object per line.
+pub fn export_lines(events: &[Value]) -> String {
+    events.iter().map(|event| format!("{event}\n")).collect()
+}
+
+/// Writes `contents` gzipped to `dir/name`, like a file from an export archive.
+pub fn write_gzipped(dir: &Path, name: &str, contents: &str) -> io::Result<PathBuf> {
+    let path = dir.join(name);
+    let mut encoder = GzEncoder::new(File::create(&path)?, Compression::default());
+    encoder.write_all(contents.as_bytes())?;
+    encoder.finish()?;
+    Ok(path)
+}
+
+/// An already parsed event: type `test`, at 2024-01-01 12:00 UTC, from line 1 of
+/// `test.json`, with every optional field empty. Change fields with struct update syntax:
+/// `ParsedItem { user_id: Some("u1".into()), ..parsed_item("uuid-1") }`.
+pub fn parsed_item(uuid: &str) -> ParsedItem {
+    ParsedItem {
+        user_id: None,
+        device_id: None,
+        screen_name: None,
+        event_name: "test".to_string(),
+        server_event: false,
+        event_time: Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap(),
+        uuid: uuid.to_string(),
+        raw_json: "{}".to_string(),
+        source_file: "test.json".to_string(),
+        source_line: 1,
+        session_id: None,
+        insert_id: None,
+        amplitude_id: None,
+        event_id: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decompress_files, parse_json_objects_in_dir, ParseOptions};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_fixtures_parse_like_exports() {
+        let (compressed, unzipped) = (tempdir().unwrap(), tempdir().unwrap());
+        let mut server = export_event("uuid-2", "Purchased", "2024-01-01 12:05:00.000000");
+        server["data"]["path"] = json!("/batch");
+        server["user_id"] = json!("u1");
+        let lines = export_lines(&[
+            export_event("uuid-1", "Viewed", "2024-01-01 12:00:00.000000"),
+            server,
+        ]);
+        write_gzipped(compressed.path(), "187520_2024-01-01_12#0.json.gz", &lines).unwrap();
+
+        decompress_files(compressed.path(), unzipped.path()).unwrap();
+        let items = parse_json_objects_in_dir(unzipped.path(), &ParseOptions::default()).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].event_time, parsed_item("uuid-1").event_time);
+        assert!(!items[0].server_event);
+        assert!(items[1].server_event);
+        assert_eq!(items[1].user_id.as_deref(), Some("u1"));
+    }
+}
@@ -0,0 +1,148 @@
+use chrono::{DateTime, FixedOffset, TimeDelta, Timelike, Utc};
+use serde_json::Value;
+
+use crate::timestamp::parse_amplitude_time;
+
+// Every timestamp an export event carries
+const TIME_FIELDS: [&str; 6] = [
+    "event_time",
+    "client_event_time",
+    "client_upload_time",
+    "server_received_time",
+    "server_upload_time",
+    "processed_time",
+];
+
+/// Moves all of an event's timestamps by a fixed offset, e.g. to replay an old
+/// dataset as if it happened recently.
+#[derive(Debug, Clone, Copy)]
+pub struct TimeShift(pub TimeDelta);
+
+impl TimeShift {
+    // Parses a humantime duration with an optional leading minus: 30d, -2h 30m
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let (negative, duration) = match value.trim().strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, value.trim()),
+        };
+        let duration = humantime::parse_duration(duration).map_err(|e| e.to_string())?;
+        let delta = TimeDelta::from_std(duration).map_err(|e| e.to_string())?;
+        Ok(Self(if negative { -delta } else { delta }))
+    }
+
+    // Timestamps keep the layout they came in; session_id, the session's start in epoch
+    // milliseconds, moves along so sessions still line up with their events
+    pub fn apply(&self, event: &mut Value) {
+        for field in TIME_FIELDS {
+            let Some(time) = event.get_mut(field) else {
+                continue;
+            };
+            if let Ok(parsed) = parse_amplitude_time(time) {
+                *time = format_like(time, parsed + self.0);
+            }
+        }
+        if let Some(session_id) = event.get_mut("session_id") {
+            // -1 marks an event outside any session
+            if let Some(start) = session_id.as_i64().filter(|start| *start > 0) {
+                *session_id = (start + self.0.num_milliseconds()).into();
+            }
+        }
+    }
+}
+
+// Writes `time` the way `original` was written: epoch milliseconds, or the same date/time
+// separator, number of fraction digits and offset
+fn format_like(original: &Value, time: DateTime<Utc>) -> Value {
+    let text = match original {
+        Value::String(text) => text.trim(),
+        _ => return time.timestamp_millis().into(),
+    };
+    if text.bytes().all(|b| b.is_ascii_digit()) {
+        return time.timestamp_millis().to_string().into();
+    }
+
+    let separator = if text.as_bytes().get(10) == Some(&b'T') {
+        'T'
+    } else {
+        ' '
+    };
+    let (local, offset) = match DateTime::parse_from_rfc3339(text) {
+        Ok(_) if text.ends_with(['Z', 'z']) => (time.naive_utc(), "Z".to_string()),
+        Ok(parsed) => {
+            let offset: FixedOffset = *parsed.offset();
+            (
+                time.with_timezone(&offset).naive_local(),
+                offset.to_string(),
+            )
+        }
+        Err(_) => (time.naive_utc(), String::new()),
+    };
+    let digits = match text.get(19..20) {
+        Some(".") => text[20..].bytes().take_while(u8::is_ascii_digit).count(),
+        _ => 0,
+    };
+    let fraction = match digits {
+        0 => String::new(),
+        _ => format!(
+            ".{}",
+            &format!("{:09}", local.nanosecond())[..digits.min(9)]
+        ),
+    };
+    format!(
+        "{}{separator}{}{fraction}{offset}",
+        local.format("%Y-%m-%d"),
+        local.format("%H:%M:%S")
+    )
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_shifts_every_timestamp_both_ways() {
+        let mut event = json!({
+            "event_time": "2025-01-31 23:30:00.123000",
+            "server_upload_time": "2025-02-01 00:00:00.000000",
+            "user_id": "u1"
+        });
+        TimeShift::parse("1h").unwrap().apply(&mut event);
+        assert_eq!(event["event_time"], "2025-02-01 00:30:00.123000");
+        assert_eq!(event["server_upload_time"], "2025-02-01 01:00:00.000000");
+
+        TimeShift::parse("-1h").unwrap().apply(&mut event);
+        assert_eq!(event["event_time"], "2025-01-31 23:30:00.123000");
+    }
+
+    #[test]
+    fn test_keeps_each_layout_and_moves_sessions() {
+        let mut event = json!({
+            "event_time": "2025-01-31T23:30:00.25",
+            "client_event_time": "2025-02-01T07:30:00+08:00",
+            "client_upload_time": "2025-01-31 23:30:00",
+            "server_received_time": 1738366200000i64,
+            "server_upload_time": "1738366200000",
+            "processed_time": "2025-01-31T23:30:00.123Z",
+            "session_id": 1738366200000i64
+        });
+        TimeShift::parse("1h").unwrap().apply(&mut event);
+        assert_eq!(
+            event,
+            json!({
+                "event_time": "2025-02-01T00:30:00.25",
+                "client_event_time": "2025-02-01T08:30:00+08:00",
+                "client_upload_time": "2025-02-01 00:30:00",
+                "server_received_time": 1738369800000i64,
+                "server_upload_time": "1738369800000",
+                "processed_time": "2025-02-01T00:30:00.123Z",
+                "session_id": 1738369800000i64
+            })
+        );
+
+        let mut outside_session = json!({ "session_id": -1 });
+        TimeShift::parse("1h").unwrap().apply(&mut outside_session);
+        assert_eq!(outside_session["session_id"], -1);
+    }
+}
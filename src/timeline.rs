@@ -0,0 +1,188 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result as AnyhowResult};
+use rusqlite::{params, Connection};
+use serde_json::{json, Map, Value};
+
+use crate::db::{raw_json, table_exists};
+use crate::sink::sqlite::raw_archive_path;
+use crate::SyncArgs;
+
+// Event properties shown per line in the text listing
+const SHOWN_PROPERTIES: usize = 5;
+
+#[derive(clap::Args, Debug)]
+pub struct TimelineArgs {
+    /// user_id whose events to list; events of amplitude_ids merged into the user
+    /// (see `db user-merges`) are included
+    #[arg(long)]
+    user: String,
+
+    /// Print a JSON array instead of one line per event
+    #[arg(long)]
+    json: bool,
+
+    /// Most recent events to show at most
+    #[arg(long, default_value_t = 1000)]
+    limit: usize,
+
+    /// SQLite database to read, relative to --workdir unless absolute
+    /// [default: --db-name]
+    #[arg(long)]
+    db: Option<PathBuf>,
+}
+
+impl TimelineArgs {
+    pub fn db(&self) -> Option<&Path> {
+        self.db.as_deref()
+    }
+}
+
+struct Entry {
+    event_time: String,
+    event_name: String,
+    uuid: String,
+    device_id: Option<String>,
+    session_id: Option<i64>,
+    properties: Map<String, Value>,
+}
+
+// Lists one user's events in the --project-id project oldest first, as support usually
+// reads them. Events stored before projects were tracked are included.
+pub fn run(args: &SyncArgs, options: &TimelineArgs) -> AnyhowResult<()> {
+    let db = args
+        .layout
+        .db_path(args.project_id.as_deref().unwrap_or_default());
+    if !db.exists() {
+        bail!("{} does not exist", db.display());
+    }
+    let conn = Connection::open(&db)?;
+    let archive = raw_archive_path(&db);
+    let archived = if archive.exists() {
+        conn.execute(
+            "ATTACH DATABASE ?1 AS raw",
+            params![archive.to_string_lossy()],
+        )?;
+        "(SELECT raw_json FROM raw.amplitude_raw_json r WHERE r.uuid = e.uuid)"
+    } else {
+        "NULL"
+    };
+    let merged = if table_exists(&conn, "user_merges")? {
+        "OR e.amplitude_id IN (
+             SELECT amplitude_id FROM user_merges
+             WHERE user_id = ?1 AND project_id = e.project_id
+         )"
+    } else {
+        ""
+    };
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT * FROM (
+             SELECT e.event_time, e.event_name, e.uuid, e.device_id, e.session_id,
+                    e.raw_json, {archived}
+             FROM amplitude_events e
+             WHERE (e.user_id = ?1 {merged})
+               AND (?3 IS NULL OR e.project_id IN (?3, ''))
+             ORDER BY e.event_time DESC, e.uuid DESC
+             LIMIT ?2
+         ) ORDER BY event_time, uuid"
+    ))?;
+    let mut rows = stmt.query(params![options.user, options.limit, args.project_id])?;
+
+    let mut entries = Vec::new();
+    while let Some(row) = rows.next()? {
+        let properties = raw_json(row.get(5)?, row.get(6)?)?
+            .and_then(|raw| serde_json::from_slice::<Value>(&raw).ok())
+            .and_then(|json| match json.get("event_properties") {
+                Some(Value::Object(properties)) => Some(properties.clone()),
+                _ => None,
+            })
+            .unwrap_or_default();
+        entries.push(Entry {
+            event_time: row.get(0)?,
+            event_name: row.get(1)?,
+            uuid: row.get(2)?,
+            device_id: row.get(3)?,
+            session_id: row.get(4)?,
+            properties,
+        });
+    }
+
+    if options.json {
+        let events: Vec<Value> = entries
+            .into_iter()
+            .map(|entry| {
+                json!({
+                    "event_time": entry.event_time,
+                    "event_type": entry.event_name,
+                    "uuid": entry.uuid,
+                    "device_id": entry.device_id,
+                    "session_id": entry.session_id,
+                    "event_properties": entry.properties,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&events)?);
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("No events for user {}", options.user);
+    }
+    for entry in &entries {
+        println!("{}", format_line(entry));
+    }
+    Ok(())
+}
+
+// 2025-01-31 05:06:07  Order Completed  [session 1738299967000]  total=10, currency="USD"
+fn format_line(entry: &Entry) -> String {
+    let time = entry
+        .event_time
+        .get(..19)
+        .unwrap_or(&entry.event_time)
+        .replace('T', " ");
+    let mut line = format!("{time}  {}", entry.event_name);
+    if let Some(session_id) = entry.session_id.filter(|&id| id > 0) {
+        line.push_str(&format!("  [session {session_id}]"));
+    }
+    let shown: Vec<String> = entry
+        .properties
+        .iter()
+        .take(SHOWN_PROPERTIES)
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect();
+    if !shown.is_empty() {
+        line.push_str("  ");
+        line.push_str(&shown.join(", "));
+        if entry.properties.len() > SHOWN_PROPERTIES {
+            line.push_str(&format!(
+                ", +{} more",
+                entry.properties.len() - SHOWN_PROPERTIES
+            ));
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats_readable_lines() {
+        let properties = json!({ "a": 1, "b": "x", "c": 3, "d": 4, "e": 5, "f": 6 });
+        let entry = Entry {
+            event_time: "2025-01-31T05:06:07.250+00:00".to_string(),
+            event_name: "Order Completed".to_string(),
+            uuid: "u".to_string(),
+            device_id: None,
+            session_id: Some(42),
+            properties: properties.as_object().unwrap().clone(),
+        };
+        assert_eq!(
+            format_line(&entry),
+            "2025-01-31 05:06:07  Order Completed  [session 42]  a=1, b=\"x\", c=3, d=4, e=5, +1 more"
+        );
+    }
+}
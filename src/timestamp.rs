@@ -0,0 +1,90 @@
+use std::fmt;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde_json::Value;
+
+// Naive UTC layouts seen in exports and feeds, tried in order; %.f also matches no fraction
+const NAIVE_FORMATS: [&str; 2] = ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"];
+
+/// A timestamp in none of the layouts Amplitude is known to use.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimestampError {
+    pub value: String,
+}
+
+impl fmt::Display for TimestampError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid event time {}: expected \"YYYY-MM-DD HH:MM:SS[.ffffff]\", ISO 8601 or epoch milliseconds",
+            self.value
+        )
+    }
+}
+
+impl std::error::Error for TimestampError {}
+
+// Reads an Amplitude timestamp: the export layout with or without a fraction,
+// ISO 8601 with or without an offset, or epoch milliseconds as a number or string
+pub fn parse_amplitude_time(value: &Value) -> Result<DateTime<Utc>, TimestampError> {
+    let invalid = || TimestampError {
+        value: value.to_string(),
+    };
+    let text = match value {
+        Value::Number(number) => return number.as_i64().and_then(from_millis).ok_or_else(invalid),
+        Value::String(text) => text.trim(),
+        _ => return Err(invalid()),
+    };
+
+    if !text.is_empty() && text.bytes().all(|b| b.is_ascii_digit()) {
+        return text.parse().ok().and_then(from_millis).ok_or_else(invalid);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(time.to_utc());
+    }
+    NAIVE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .map(|time| time.and_utc())
+        .ok_or_else(invalid)
+}
+
+fn from_millis(millis: i64) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp_millis(millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parses_known_layouts() {
+        let expected = "2025-01-31T05:06:07.250Z";
+        for value in [
+            json!("2025-01-31 05:06:07.250000"),
+            json!("2025-01-31 05:06:07.25"),
+            json!("2025-01-31T05:06:07.250"),
+            json!("2025-01-31T13:06:07.250+08:00"),
+            json!(1738299967250i64),
+            json!("1738299967250"),
+        ] {
+            let parsed = parse_amplitude_time(&value).unwrap();
+            assert_eq!(
+                parsed.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                expected,
+                "{value}"
+            );
+        }
+        assert_eq!(
+            parse_amplitude_time(&json!("2025-01-31 05:06:07"))
+                .unwrap()
+                .to_rfc3339(),
+            "2025-01-31T05:06:07+00:00"
+        );
+
+        let error = parse_amplitude_time(&json!("31/01/2025")).unwrap_err();
+        assert_eq!(error.value, "\"31/01/2025\"");
+        assert!(parse_amplitude_time(&json!(null)).is_err());
+    }
+}
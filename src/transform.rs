@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Result as AnyhowResult};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// Per-event rewrites applied between reading an export and writing it anywhere.
+///
+/// Loaded from a TOML or JSON mapping file, e.g.
+///
+/// ```toml
+/// drop_properties = ["debug"]
+///
+/// [rename_events]
+/// "Old Event" = "New Event"
+///
+/// [rename_properties]
+/// PropertyName = "Property"
+///
+/// [add_properties]
+/// migrated = "true"
+///
+/// [rewrite_values]
+/// plan = "legacy-{value}"
+/// ```
+///
+/// Property operations act on `event_properties`. String values in
+/// `add_properties` and all `rewrite_values` templates may reference
+/// `{value}`, `{event_type}`, `{user_id}` and `{device_id}`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EventTransform {
+    #[serde(default)]
+    pub rename_events: BTreeMap<String, String>,
+    #[serde(default)]
+    pub rename_properties: BTreeMap<String, String>,
+    #[serde(default)]
+    pub drop_properties: Vec<String>,
+    #[serde(default)]
+    pub add_properties: BTreeMap<String, Value>,
+    #[serde(default)]
+    pub rewrite_values: BTreeMap<String, String>,
+}
+
+impl EventTransform {
+    // Loads a mapping file, picking the format from its extension
+    pub fn from_file(path: &Path) -> AnyhowResult<Self> {
+        let contents = fs::read_to_string(path)?;
+        match path.extension().and_then(|s| s.to_str()) {
+            Some("toml") => Ok(toml::from_str(&contents)?),
+            Some("json") => Ok(serde_json::from_str(&contents)?),
+            _ => bail!(
+                "Unsupported transform file {}: expected .toml or .json",
+                path.display()
+            ),
+        }
+    }
+
+    // Applies every configured rewrite to a single export event in place
+    pub fn apply(&self, event: &mut Value) {
+        let Some(event) = event.as_object_mut() else {
+            return;
+        };
+
+        if let Some(new_name) = event
+            .get("event_type")
+            .and_then(|v| v.as_str())
+            .and_then(|name| self.rename_events.get(name))
+        {
+            event.insert("event_type".to_string(), Value::String(new_name.clone()));
+        }
+
+        if !event.contains_key("event_properties") && self.add_properties.is_empty() {
+            return;
+        }
+
+        let context = TemplateContext::from_event(event);
+        let Some(properties) = event
+            .entry("event_properties")
+            .or_insert_with(|| Value::Object(Map::new()))
+            .as_object_mut()
+        else {
+            return;
+        };
+
+        for (from, to) in &self.rename_properties {
+            if let Some(value) = properties.remove(from) {
+                properties.insert(to.clone(), value);
+            }
+        }
+
+        for key in &self.drop_properties {
+            properties.remove(key);
+        }
+
+        for (key, value) in &self.add_properties {
+            let value = match value {
+                Value::String(template) => Value::String(context.render(template, "")),
+                other => other.clone(),
+            };
+            properties.insert(key.clone(), value);
+        }
+
+        for (key, template) in &self.rewrite_values {
+            if let Some(value) = properties.get_mut(key) {
+                let current = match &*value {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                *value = Value::String(context.render(template, &current));
+            }
+        }
+    }
+}
+
+struct TemplateContext {
+    event_type: String,
+    user_id: String,
+    device_id: String,
+}
+
+impl TemplateContext {
+    fn from_event(event: &Map<String, Value>) -> Self {
+        let field = |name: &str| {
+            event
+                .get(name)
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string()
+        };
+        Self {
+            event_type: field("event_type"),
+            user_id: field("user_id"),
+            device_id: field("device_id"),
+        }
+    }
+
+    fn render(&self, template: &str, value: &str) -> String {
+        template
+            .replace("{value}", value)
+            .replace("{event_type}", &self.event_type)
+            .replace("{user_id}", &self.user_id)
+            .replace("{device_id}", &self.device_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_apply_renames_drops_adds_and_rewrites() {
+        let transform: EventTransform = toml::from_str(
+            r#"
+            drop_properties = ["debug"]
+
+            [rename_events]
+            "Old Event" = "New Event"
+
+            [rename_properties]
+            PropertyName = "Property"
+
+            [add_properties]
+            origin = "{event_type}/{user_id}"
+            count = 3
+
+            [rewrite_values]
+            plan = "legacy-{value}"
+            "#,
+        )
+        .unwrap();
+
+        let mut event = json!({
+            "event_type": "Old Event",
+            "user_id": "u1",
+            "event_properties": { "PropertyName": "x", "debug": true, "plan": "pro" }
+        });
+        transform.apply(&mut event);
+
+        assert_eq!(
+            event,
+            json!({
+                "event_type": "New Event",
+                "user_id": "u1",
+                "event_properties": {
+                    "Property": "x",
+                    "plan": "legacy-pro",
+                    "origin": "New Event/u1",
+                    "count": 3
+                }
+            })
+        );
+    }
+}
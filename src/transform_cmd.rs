@@ -0,0 +1,48 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::process::Stdio;
+
+use crate::secrets::shell;
+
+// Pipes an extracted export file through a --transform-cmd and returns what it printed.
+//
+// The command sees the file's JSONL on stdin and writes JSONL to stdout; it may add,
+// change or drop events, so parse errors point at lines of its output. Its stderr is
+// passed through, and a non-zero exit fails the import.
+pub fn run(cmd: &str, path: &Path) -> io::Result<Vec<u8>> {
+    let output = shell(cmd)
+        .stdin(File::open(path)?)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| io::Error::other(format!("Failed to run transform command `{cmd}`: {e}")))?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "Transform command `{cmd}` failed on {}: {}",
+            path.display(),
+            output.status
+        )));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_pipes_file_through_command() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("events.json");
+        fs::write(&path, "{\"event_type\":\"a\"}\n{\"event_type\":\"drop\"}\n").unwrap();
+
+        let output = run("grep -v drop", &path).unwrap();
+        assert_eq!(output, b"{\"event_type\":\"a\"}\n");
+
+        let error = run("exit 3", &path).unwrap_err();
+        assert!(error.to_string().contains("failed on"));
+    }
+}
@@ -0,0 +1,363 @@
+use std::panic;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::Result as AnyhowResult;
+use chrono::{Days, Utc};
+use clap::Parser;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::cancel;
+use crate::progress::{self, CounterSnapshot, COUNTERS};
+use crate::{lock_state, sync, Cli};
+
+const FIELDS: [&str; 3] = ["Project ID", "Start (YYYYMMDDTHH)", "End (YYYYMMDDTHH)"];
+const ACTIONS: [&str; 2] = ["Export + import", "Import last export (skip download)"];
+const ACTION_FOCUS: usize = FIELDS.len();
+const LOG_LINES: usize = 500;
+const WORKER_THREAD: &str = "sync-worker";
+
+enum Screen {
+    Form,
+    Running {
+        worker: JoinHandle<Result<(), String>>,
+        started: Instant,
+    },
+    Finished {
+        result: Result<(), String>,
+        elapsed: Duration,
+    },
+}
+
+struct App {
+    inputs: [String; 3],
+    action: usize,
+    focus: usize,
+    message: Option<String>,
+    log: Vec<String>,
+    screen: Screen,
+    extra_args: Vec<String>,
+}
+
+// Runs the guided sync UI until the user quits
+pub fn run(extra_args: Vec<String>) -> AnyhowResult<()> {
+    progress::capture_output();
+    let mut terminal = ratatui::init();
+
+    // Worker panics are reported in the log pane; anything else restores the terminal first
+    let restore_and_report = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if thread::current().name() == Some(WORKER_THREAD) {
+            progress::error(info.to_string());
+        } else {
+            restore_and_report(info);
+        }
+    }));
+
+    let result = App::new(extra_args).event_loop(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn new(extra_args: Vec<String>) -> Self {
+        let yesterday = Utc::now()
+            .date_naive()
+            .checked_sub_days(Days::new(1))
+            .unwrap_or_default()
+            .format("%Y%m%d");
+        Self {
+            inputs: [
+                String::new(),
+                format!("{yesterday}T00"),
+                format!("{yesterday}T23"),
+            ],
+            action: 0,
+            focus: 0,
+            message: None,
+            log: Vec::new(),
+            screen: Screen::Form,
+            extra_args,
+        }
+    }
+
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> AnyhowResult<()> {
+        loop {
+            self.poll_worker();
+            self.log.extend(progress::drain_captured());
+            if self.log.len() > LOG_LINES {
+                self.log.drain(..self.log.len() - LOG_LINES);
+            }
+
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if !event::poll(Duration::from_millis(200))? {
+                continue;
+            }
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !self.handle_key(key) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    // Returns false when the user asked to quit
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let code = key.code;
+        let ctrl_c = code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+        match &self.screen {
+            Screen::Form | Screen::Finished { .. } if ctrl_c => return false,
+            Screen::Form => match code {
+                KeyCode::Esc => return false,
+                KeyCode::Tab | KeyCode::Down => self.focus = (self.focus + 1) % (ACTION_FOCUS + 1),
+                KeyCode::BackTab | KeyCode::Up => {
+                    self.focus = (self.focus + ACTION_FOCUS) % (ACTION_FOCUS + 1)
+                }
+                KeyCode::Left | KeyCode::Right if self.focus == ACTION_FOCUS => {
+                    self.action = (self.action + 1) % ACTIONS.len()
+                }
+                KeyCode::Char(c) if self.focus < ACTION_FOCUS => self.inputs[self.focus].push(c),
+                KeyCode::Backspace if self.focus < ACTION_FOCUS => {
+                    self.inputs[self.focus].pop();
+                }
+                KeyCode::Enter => self.start(),
+                _ => {}
+            },
+            // The sync stops at its next safe point, between files or windows, and the
+            // Finished screen reports it as cancelled
+            Screen::Running { .. } if ctrl_c || code == KeyCode::Esc => cancel::request(),
+            Screen::Running { .. } => {}
+            Screen::Finished { .. } => match code {
+                KeyCode::Esc | KeyCode::Char('q') => return false,
+                KeyCode::Enter => self.screen = Screen::Form,
+                _ => {}
+            },
+        }
+        true
+    }
+
+    // Validates the form and starts a sync on a background thread
+    fn start(&mut self) {
+        let [project_id, start_date, end_date] = &self.inputs;
+        if project_id.trim().is_empty() {
+            self.message = Some("Project ID is required".into());
+            return;
+        }
+        for date in [start_date, end_date] {
+            if !is_export_hour(date) {
+                self.message = Some(format!("{date:?} is not in YYYYMMDDTHH format"));
+                return;
+            }
+        }
+
+        let mut args = vec![
+            "amplitude-things".to_string(),
+            "--project-id".into(),
+            project_id.trim().into(),
+            "--start-date".into(),
+            start_date.clone(),
+            "--end-date".into(),
+            end_date.clone(),
+        ];
+        if self.action == 1 {
+            args.push("--skip-download".into());
+        }
+        args.extend(self.extra_args.iter().cloned());
+
+        let cli = match Cli::try_parse_from(args) {
+            Ok(cli) => cli,
+            Err(e) => {
+                // Keep clap's complaint but drop its multi-line usage banner
+                let text = e.to_string();
+                let summary = text.split("Usage:").next().unwrap_or_default();
+                self.message = Some(summary.split_whitespace().collect::<Vec<_>>().join(" "));
+                return;
+            }
+        };
+
+        COUNTERS.reset();
+        cancel::reset();
+        self.log.clear();
+        self.message = None;
+        let worker = thread::Builder::new()
+            .name(WORKER_THREAD.into())
+            .spawn(move || {
+                let _lock = lock_state(&cli.sync).map_err(|e| format!("{e:#}"))?;
+                sync(&cli.sync).map_err(|e| e.to_string())
+            })
+            .expect("Failed to spawn sync thread");
+        self.screen = Screen::Running {
+            worker,
+            started: Instant::now(),
+        };
+    }
+
+    fn poll_worker(&mut self) {
+        let Screen::Running { worker, started } = &self.screen else {
+            return;
+        };
+        if !worker.is_finished() {
+            return;
+        }
+        let elapsed = started.elapsed();
+        let Screen::Running { worker, .. } = std::mem::replace(&mut self.screen, Screen::Form)
+        else {
+            unreachable!();
+        };
+        let result = worker
+            .join()
+            .unwrap_or_else(|_| Err("Sync panicked; see the log for details".into()));
+        self.screen = Screen::Finished { result, elapsed };
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [title, body, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        frame.render_widget(
+            Line::from("Amplitude → database sync").bold().centered(),
+            title,
+        );
+
+        let help_text = match &self.screen {
+            Screen::Form => "Tab/↑↓ move · ←→ change action · Enter start · Esc quit",
+            Screen::Running { .. } if cancel::requested() => "Cancelling after the current file…",
+            Screen::Running { .. } => "Sync running… · Esc/Ctrl-C cancel",
+            Screen::Finished { .. } => "Enter new sync · q/Esc quit",
+        };
+        frame.render_widget(Line::from(help_text).dark_gray(), help);
+
+        match &self.screen {
+            Screen::Form => self.draw_form(frame, body),
+            Screen::Running { started, .. } => {
+                self.draw_progress(frame, body, started.elapsed(), None)
+            }
+            Screen::Finished { result, elapsed } => {
+                self.draw_progress(frame, body, *elapsed, Some(result))
+            }
+        }
+    }
+
+    fn draw_form(&self, frame: &mut Frame, area: Rect) {
+        let rows = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Min(0),
+        ])
+        .split(area);
+
+        let border = |focused: bool| {
+            if focused {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            }
+        };
+
+        for (i, label) in FIELDS.iter().enumerate() {
+            let cursor = if self.focus == i { "▏" } else { "" };
+            frame.render_widget(
+                Paragraph::new(format!("{}{}", self.inputs[i], cursor)).block(
+                    Block::bordered()
+                        .title(*label)
+                        .border_style(border(self.focus == i)),
+                ),
+                rows[i],
+            );
+        }
+
+        frame.render_widget(
+            Paragraph::new(format!("◀ {} ▶", ACTIONS[self.action])).block(
+                Block::bordered()
+                    .title("Action")
+                    .border_style(border(self.focus == ACTION_FOCUS)),
+            ),
+            rows[ACTION_FOCUS],
+        );
+
+        if let Some(message) = &self.message {
+            frame.render_widget(Paragraph::new(message.as_str()).red(), rows[4]);
+        }
+    }
+
+    fn draw_progress(
+        &self,
+        frame: &mut Frame,
+        area: Rect,
+        elapsed: Duration,
+        result: Option<&Result<(), String>>,
+    ) {
+        let [stats, log] =
+            Layout::vertical([Constraint::Length(10), Constraint::Min(0)]).areas(area);
+
+        let counters: CounterSnapshot = COUNTERS.snapshot();
+        let seconds = elapsed.as_secs_f64().max(0.001);
+        let stage = match result {
+            None => self.log.last().cloned().unwrap_or_default(),
+            Some(Ok(())) => "Finished".to_string(),
+            Some(Err(e)) => format!("Failed: {e}"),
+        };
+
+        let lines = vec![
+            Line::from(format!("Stage:        {stage}")),
+            Line::from(format!("Elapsed:      {:.0}s", seconds)),
+            Line::from(format!(
+                "Downloaded:   {:.1} MB",
+                counters.bytes_downloaded as f64 / 1_048_576.0
+            )),
+            Line::from(format!("Files:        {}", counters.files_unzipped)),
+            Line::from(format!(
+                "Parsed:       {} ({:.0} events/s)",
+                counters.events_parsed,
+                counters.events_parsed as f64 / seconds
+            )),
+            Line::from(format!("Inserted:     {}", counters.events_inserted)),
+            Line::from(format!("Parse errors: {}", counters.parse_errors)),
+        ];
+        let style = match result {
+            Some(Err(_)) => Style::default().fg(Color::Red),
+            Some(Ok(())) => Style::default().fg(Color::Green),
+            None => Style::default(),
+        };
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Progress").border_style(style)),
+            stats,
+        );
+
+        let visible = log.height.saturating_sub(2) as usize;
+        let items: Vec<ListItem> = self
+            .log
+            .iter()
+            .skip(self.log.len().saturating_sub(visible))
+            .map(|line| {
+                let item = ListItem::new(line.as_str());
+                if line.starts_with("ERROR") {
+                    item.red()
+                } else {
+                    item
+                }
+            })
+            .collect();
+        frame.render_widget(List::new(items).block(Block::bordered().title("Log")), log);
+    }
+}
+
+// Export windows are hour-granular: YYYYMMDDTHH
+fn is_export_hour(value: &str) -> bool {
+    value.len() == 11
+        && value
+            .char_indices()
+            .all(|(i, c)| if i == 8 { c == 'T' } else { c.is_ascii_digit() })
+}
@@ -0,0 +1,174 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result as AnyhowResult;
+use rusqlite::{params, Connection};
+
+// (project_id, amplitude_id, user_id, device_id, first event_time) seen together
+type IdentityRow = (String, i64, Option<String>, Option<String>, String);
+
+// Derives which amplitude_ids Amplitude has merged, from the events themselves.
+//
+// Exports carry no merge records, but merges show in the data: every amplitude_id
+// seen with a user_id other than that user's first one was merged into it, and an
+// anonymous amplitude_id on a device that later identifies as a user was merged into
+// that user's. The result replaces user_merges; merged_events applies it to events.
+pub fn derive(conn: &mut Connection) -> AnyhowResult<usize> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS user_merges (
+            project_id TEXT NOT NULL,
+            amplitude_id INTEGER NOT NULL,
+            merged_into INTEGER NOT NULL,
+            user_id TEXT NOT NULL,
+            reason TEXT NOT NULL,
+            first_seen DATETIME NOT NULL,
+            PRIMARY KEY (project_id, amplitude_id)
+        );
+
+        CREATE VIEW IF NOT EXISTS merged_events AS
+            SELECT e.*,
+                   COALESCE(m.merged_into, e.amplitude_id) AS merged_amplitude_id,
+                   COALESCE(e.user_id, m.user_id) AS merged_user_id
+            FROM amplitude_events e
+            LEFT JOIN user_merges m
+                ON m.project_id = e.project_id AND m.amplitude_id = e.amplitude_id;
+        ",
+    )?;
+
+    // One row per identity combination, in order of first appearance
+    let rows: Vec<IdentityRow> = conn
+        .prepare(
+            "SELECT project_id, amplitude_id, user_id, device_id, MIN(event_time) AS first_seen
+             FROM amplitude_events
+             WHERE amplitude_id IS NOT NULL
+             GROUP BY project_id, amplitude_id, user_id, device_id
+             ORDER BY first_seen, project_id, amplitude_id, user_id, device_id",
+        )?
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut canonical: HashMap<(&str, &str), i64> = HashMap::new();
+    for (project, amplitude_id, user_id, _, _) in &rows {
+        if let Some(user_id) = user_id {
+            canonical.entry((project, user_id)).or_insert(*amplitude_id);
+        }
+    }
+    let canonical_ids: HashSet<(&str, i64)> = canonical
+        .iter()
+        .map(|(&(project, _), &id)| (project, id))
+        .collect();
+
+    let mut anonymous: HashMap<(&str, &str), Vec<i64>> = HashMap::new();
+    for (project, amplitude_id, user_id, device_id, _) in &rows {
+        if let (None, Some(device_id)) = (user_id, device_id) {
+            anonymous
+                .entry((project, device_id))
+                .or_default()
+                .push(*amplitude_id);
+        }
+    }
+
+    let tx = conn.transaction()?;
+    tx.execute("DELETE FROM user_merges", [])?;
+    let mut merges = 0;
+    {
+        let mut insert = tx.prepare(
+            "INSERT OR IGNORE INTO user_merges (project_id, amplitude_id, merged_into, user_id, reason, first_seen)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        for (project, amplitude_id, user_id, device_id, first_seen) in &rows {
+            let Some(user_id) = user_id else {
+                continue;
+            };
+            let into = canonical[&(project.as_str(), user_id.as_str())];
+            if *amplitude_id != into {
+                merges += insert.execute(params![
+                    project,
+                    amplitude_id,
+                    into,
+                    user_id,
+                    "user_id",
+                    first_seen
+                ])?;
+            }
+            let devices_anonymous = device_id
+                .as_deref()
+                .and_then(|device_id| anonymous.get(&(project.as_str(), device_id)));
+            for &anonymous_id in devices_anonymous.into_iter().flatten() {
+                if anonymous_id != into
+                    && !canonical_ids.contains(&(project.as_str(), anonymous_id))
+                {
+                    merges += insert.execute(params![
+                        project,
+                        anonymous_id,
+                        into,
+                        user_id,
+                        "device_id",
+                        first_seen
+                    ])?;
+                }
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(merges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derives_merges_from_user_and_device() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "
+            CREATE TABLE amplitude_events (
+                uuid TEXT, project_id TEXT, amplitude_id INTEGER, user_id TEXT,
+                device_id TEXT, event_time TEXT
+            );
+            INSERT INTO amplitude_events VALUES
+                ('1', 'p', 10, NULL, 'd1', '2025-01-01T00:00:00'),
+                ('2', 'p', 20, 'u1', 'd2', '2025-01-01T01:00:00'),
+                ('3', 'p', 30, 'u1', 'd1', '2025-01-01T02:00:00'),
+                ('4', 'p', 40, 'u2', 'd3', '2025-01-01T03:00:00');
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(derive(&mut conn).unwrap(), 2);
+        let merges: Vec<(i64, i64, String)> = conn
+            .prepare(
+                "SELECT amplitude_id, merged_into, reason FROM user_merges ORDER BY amplitude_id",
+            )
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            merges,
+            [
+                (10, 20, "device_id".to_string()),
+                (30, 20, "user_id".to_string())
+            ]
+        );
+
+        let merged_user: String = conn
+            .query_row(
+                "SELECT merged_user_id FROM merged_events WHERE uuid = '1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(merged_user, "u1");
+    }
+}
@@ -0,0 +1,410 @@
+use std::collections::HashSet;
+
+use anyhow::Result as AnyhowResult;
+use rusqlite::{params, Connection, Result};
+use serde_json::{Map, Value};
+
+use crate::progress;
+use crate::sink::sqlite::{has_column, has_table};
+use crate::ParsedItem;
+
+const CURRENT_TABLE: &str = "
+    CREATE TABLE IF NOT EXISTS user_properties_current (
+        project_id TEXT NOT NULL DEFAULT '',
+        user_id TEXT NOT NULL,
+        key TEXT NOT NULL,
+        value TEXT NOT NULL,
+        updated_at DATETIME NOT NULL,
+        PRIMARY KEY (project_id, user_id, key)
+    );
+";
+
+const HISTORY_TABLE: &str = "
+    CREATE TABLE IF NOT EXISTS user_properties_history (
+        project_id TEXT NOT NULL DEFAULT '',
+        uuid TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        key TEXT NOT NULL,
+        operation TEXT NOT NULL,
+        value TEXT,
+        event_time DATETIME NOT NULL,
+        PRIMARY KEY (project_id, uuid, key, operation)
+    );
+";
+
+// Ensures the user property snapshot and history tables exist, scoped by project.
+// Tables from before projects were tracked are rebuilt with the new primary keys;
+// their rows keep an empty project_id, as imported_files does.
+fn ensure_schema(conn: &Connection) -> Result<()> {
+    for (table, create, columns) in [
+        (
+            "user_properties_current",
+            CURRENT_TABLE,
+            "user_id, key, value, updated_at",
+        ),
+        (
+            "user_properties_history",
+            HISTORY_TABLE,
+            "uuid, user_id, key, operation, value, event_time",
+        ),
+    ] {
+        if has_table(conn, table)? && !has_column(conn, table, "project_id")? {
+            conn.execute_batch(&format!(
+                "
+                BEGIN;
+                ALTER TABLE {table} RENAME TO {table}_unscoped;
+                {create}
+                INSERT INTO {table} ({columns}) SELECT {columns} FROM {table}_unscoped;
+                DROP TABLE {table}_unscoped;
+                COMMIT;
+                "
+            ))?;
+        }
+    }
+    conn.execute_batch(CURRENT_TABLE)?;
+    conn.execute_batch(HISTORY_TABLE)
+}
+
+// Replays the user property operations carried by events, oldest first, into
+// user_properties_current (latest value per user and key) and user_properties_history.
+//
+// Plain keys in user_properties are treated as $set, which is how exported events
+// carry the properties in effect when they fired. $set, $setOnce, $add, $unset and
+// $clearAll are applied; list operations ($append, $prepend, ...) only reach history.
+// $add increments commute and are applied whenever they arrive; other operations older
+// than the current value of a key are recorded but not applied, so importing hours out
+// of order still converges on the latest value. Operations already in history, from a
+// re-imported hour or a repeated uuid, and events the sink skipped as resends of
+// another event (by insert_id) are not applied again. Both tables are keyed by
+// project, so projects sharing a database keep separate users.
+pub fn update_user_properties(
+    conn: &mut Connection,
+    project_id: &str,
+    items: &[ParsedItem],
+) -> AnyhowResult<()> {
+    ensure_schema(conn)?;
+
+    let mut events: Vec<&ParsedItem> = items.iter().filter(|i| i.user_id.is_some()).collect();
+    events.sort_by_key(|item| item.event_time);
+
+    let tx = conn.transaction()?;
+    let resends = resent_uuids(&tx, project_id)?;
+    let mut operations = 0;
+    for item in events {
+        if resends.contains(&item.uuid) {
+            continue;
+        }
+        let Ok(json) = serde_json::from_str::<Value>(&item.raw_json) else {
+            continue;
+        };
+        let Some(properties) = properties_of(&json) else {
+            continue;
+        };
+        let user_id = item.user_id.as_deref().unwrap_or_default();
+        let event_time = item.event_time.to_rfc3339();
+
+        for (operation, key, value) in operations_of(properties) {
+            let recorded = tx.execute(
+                "INSERT OR IGNORE INTO user_properties_history
+                     (project_id, uuid, user_id, key, operation, value, event_time)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    project_id,
+                    item.uuid,
+                    user_id,
+                    key,
+                    operation,
+                    value.map(|v| v.to_string()),
+                    event_time
+                ],
+            )?;
+            if recorded == 0 {
+                continue;
+            }
+            apply(&tx, project_id, user_id, operation, key, value, &event_time)?;
+            operations += 1;
+        }
+    }
+    tx.commit()?;
+
+    progress::info(format!("Applied {operations} user property operations."));
+    Ok(())
+}
+
+// Events the sink skipped as resends of another event, looked up once per batch
+fn resent_uuids(conn: &Connection, project_id: &str) -> Result<HashSet<String>> {
+    if !has_column(conn, "duplicate_events", "uuid")? {
+        return Ok(HashSet::new());
+    }
+    let mut stmt = conn.prepare(
+        "SELECT uuid FROM duplicate_events WHERE project_id = ?1 AND duplicate_of <> uuid",
+    )?;
+    let uuids = stmt.query_map(params![project_id], |row| row.get(0))?;
+    uuids.collect()
+}
+
+// User property operations an event carries: Amplitude's user_properties, or the
+// traits of a Segment identify call, which are plain values and so replay as $set
+fn properties_of(json: &Value) -> Option<&Map<String, Value>> {
+    let properties = match json.get("type").and_then(|v| v.as_str()) {
+        Some("identify") => json.get("traits"),
+        _ => json.get("user_properties"),
+    };
+    properties.and_then(|v| v.as_object())
+}
+
+// Flattens a user_properties object into (operation, key, value) triples
+fn operations_of(properties: &Map<String, Value>) -> Vec<(&str, &str, Option<&Value>)> {
+    let mut operations = Vec::new();
+    for (key, value) in properties {
+        match (key.as_str(), value) {
+            ("$clearAll", _) => operations.push(("$clearAll", "", None)),
+            ("$unset", Value::Object(fields)) => {
+                operations.extend(fields.keys().map(|k| ("$unset", k.as_str(), None)))
+            }
+            (operation, Value::Object(fields)) if operation.starts_with('$') => {
+                operations.extend(fields.iter().map(|(k, v)| (operation, k.as_str(), Some(v))))
+            }
+            (operation, _) if operation.starts_with('$') => {}
+            (key, value) => operations.push(("$set", key, Some(value))),
+        }
+    }
+    operations
+}
+
+fn apply(
+    conn: &Connection,
+    project_id: &str,
+    user_id: &str,
+    operation: &str,
+    key: &str,
+    value: Option<&Value>,
+    event_time: &str,
+) -> Result<()> {
+    match (operation, value) {
+        ("$set", Some(value)) => {
+            conn.execute(
+                "INSERT INTO user_properties_current (project_id, user_id, key, value, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (project_id, user_id, key) DO UPDATE SET value = ?4, updated_at = ?5
+                 WHERE updated_at <= ?5",
+                params![project_id, user_id, key, value.to_string(), event_time],
+            )?;
+        }
+        ("$setOnce", Some(value)) => {
+            conn.execute(
+                "INSERT OR IGNORE INTO user_properties_current (project_id, user_id, key, value, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![project_id, user_id, key, value.to_string(), event_time],
+            )?;
+        }
+        ("$add", Some(Value::Number(amount))) => {
+            let amount = amount.as_f64().unwrap_or_default();
+            conn.execute(
+                "INSERT INTO user_properties_current (project_id, user_id, key, value, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (project_id, user_id, key) DO UPDATE
+                 SET value = CAST(value AS REAL) + ?4, updated_at = MAX(updated_at, ?5)",
+                params![project_id, user_id, key, amount, event_time],
+            )?;
+        }
+        ("$unset", _) => {
+            conn.execute(
+                "DELETE FROM user_properties_current
+                 WHERE project_id = ?1 AND user_id = ?2 AND key = ?3 AND updated_at <= ?4",
+                params![project_id, user_id, key, event_time],
+            )?;
+        }
+        ("$clearAll", _) => {
+            conn.execute(
+                "DELETE FROM user_properties_current
+                 WHERE project_id = ?1 AND user_id = ?2 AND updated_at <= ?3",
+                params![project_id, user_id, event_time],
+            )?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::parsed_item;
+    use chrono::{DateTime, Utc};
+    use serde_json::json;
+
+    fn event(uuid: &str, time: &str, user_properties: Value) -> ParsedItem {
+        ParsedItem {
+            user_id: Some("u1".into()),
+            event_name: "$identify".into(),
+            server_event: true,
+            event_time: DateTime::parse_from_rfc3339(time)
+                .unwrap()
+                .with_timezone(&Utc),
+            raw_json: json!({ "user_properties": user_properties }).to_string(),
+            ..parsed_item(uuid)
+        }
+    }
+
+    #[test]
+    fn test_operations_replay_in_event_time_order() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let items = [
+            event(
+                "b",
+                "2025-01-01T02:00:00Z",
+                json!({ "$set": { "plan": "pro" }, "$add": { "logins": 2 }, "$unset": { "trial": "-" } }),
+            ),
+            event(
+                "a",
+                "2025-01-01T01:00:00Z",
+                json!({ "plan": "free", "trial": true, "logins": 1, "$setOnce": { "source": "ad" } }),
+            ),
+        ];
+        update_user_properties(&mut conn, "123", &items).unwrap();
+
+        let current: Vec<(String, String)> = conn
+            .prepare("SELECT key, value FROM user_properties_current ORDER BY key")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            current,
+            [
+                ("logins".to_string(), "3.0".to_string()),
+                ("plan".to_string(), "\"pro\"".to_string()),
+                ("source".to_string(), "\"ad\"".to_string()),
+            ]
+        );
+
+        let history: i64 = conn
+            .query_row("SELECT COUNT(*) FROM user_properties_history", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(history, 7);
+
+        // Replaying the same events, as a re-imported hour does, changes nothing
+        update_user_properties(&mut conn, "123", &items).unwrap();
+        let logins: String = conn
+            .query_row(
+                "SELECT value FROM user_properties_current WHERE key = 'logins'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(logins, "3.0");
+    }
+
+    #[test]
+    fn test_projects_sharing_a_database_keep_separate_users() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        // Tables as created before they were scoped by project
+        conn.execute_batch(
+            "
+            CREATE TABLE user_properties_current (
+                user_id TEXT NOT NULL, key TEXT NOT NULL, value TEXT NOT NULL,
+                updated_at DATETIME NOT NULL, PRIMARY KEY (user_id, key)
+            );
+            CREATE TABLE user_properties_history (
+                uuid TEXT NOT NULL, user_id TEXT NOT NULL, key TEXT NOT NULL,
+                operation TEXT NOT NULL, value TEXT, event_time DATETIME NOT NULL,
+                PRIMARY KEY (uuid, key, operation)
+            );
+            INSERT INTO user_properties_current VALUES ('u1', 'plan', '\"old\"', '2024-01-01');
+            ",
+        )
+        .unwrap();
+
+        let item = event(
+            "a",
+            "2025-01-01T01:00:00Z",
+            json!({ "$add": { "logins": 1 } }),
+        );
+        update_user_properties(&mut conn, "123", std::slice::from_ref(&item)).unwrap();
+        update_user_properties(&mut conn, "456", &[item]).unwrap();
+
+        let current: Vec<(String, String, String)> = conn
+            .prepare("SELECT project_id, key, value FROM user_properties_current ORDER BY project_id, key")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        let row = |project: &str, key: &str, value: &str| {
+            (project.to_string(), key.to_string(), value.to_string())
+        };
+        assert_eq!(
+            current,
+            [
+                row("", "plan", "\"old\""),
+                row("123", "logins", "1.0"),
+                row("456", "logins", "1.0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_only_insert_id_duplicates_are_skipped() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        // As the SQLite sink records them: a repeated uuid under its own uuid, and a
+        // resend of "a" with another uuid but the same insert_id
+        conn.execute_batch(
+            "
+            CREATE TABLE duplicate_events (
+                uuid TEXT NOT NULL, duplicate_of TEXT NOT NULL, project_id TEXT NOT NULL,
+                reason TEXT NOT NULL, source_file TEXT NOT NULL, source_line INTEGER NOT NULL
+            );
+            INSERT INTO duplicate_events VALUES ('a', 'a', '123', 'uuid', 'f.json', 2);
+            INSERT INTO duplicate_events VALUES ('b', 'a', '123', 'insert_id', 'f.json', 3);
+            ",
+        )
+        .unwrap();
+
+        let items = [
+            event("a", "2025-01-01T01:00:00Z", json!({ "plan": "pro" })),
+            event("a", "2025-01-01T01:00:00Z", json!({ "plan": "pro" })),
+            event("b", "2025-01-01T02:00:00Z", json!({ "plan": "free" })),
+        ];
+        update_user_properties(&mut conn, "123", &items).unwrap();
+
+        let plan: String = conn
+            .query_row(
+                "SELECT value FROM user_properties_current WHERE key = 'plan'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(plan, "\"pro\"");
+    }
+
+    #[test]
+    fn test_add_counts_increments_imported_out_of_order() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let later = event(
+            "b",
+            "2025-01-01T02:00:00Z",
+            json!({ "$add": { "logins": 2 } }),
+        );
+        let earlier = event(
+            "a",
+            "2025-01-01T01:00:00Z",
+            json!({ "$add": { "logins": 1 } }),
+        );
+        update_user_properties(&mut conn, "123", &[later]).unwrap();
+        update_user_properties(&mut conn, "123", &[earlier]).unwrap();
+
+        let (logins, updated_at): (String, String) = conn
+            .query_row(
+                "SELECT value, updated_at FROM user_properties_current WHERE key = 'logins'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(logins, "3.0");
+        assert_eq!(updated_at, "2025-01-01T02:00:00+00:00");
+    }
+}
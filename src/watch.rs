@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result as AnyhowResult};
+use tempfile::tempdir;
+
+use crate::import::SourceFormat;
+use crate::progress::COUNTERS;
+use crate::sink::EventSink;
+use crate::{cancel, import_export, notify, open_sink, progress, SyncArgs};
+
+#[derive(clap::Args, Debug)]
+pub struct WatchArgs {
+    /// Directory where export .zip, .gz, .zst or .json(l) files are dropped
+    dir: PathBuf,
+
+    /// How often to look for new files, e.g. 10s, 1m
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "10s")]
+    interval: Duration,
+
+    /// Import whatever is in the directory now and exit
+    #[arg(long)]
+    once: bool,
+}
+
+// Imports export archives as they appear in a directory, until interrupted
+pub fn run(args: &SyncArgs, options: &WatchArgs) -> AnyhowResult<()> {
+    progress::info(format!("Watching {} for exports", options.dir.display()));
+    let db_path = args
+        .layout
+        .db_path(args.project_id.as_deref().unwrap_or_default());
+    let mut sink = open_sink(args, &db_path)?;
+
+    // Files are only picked up once their size stops changing between two scans,
+    // so archives still being copied in are left alone
+    let mut sizes: HashMap<PathBuf, u64> = HashMap::new();
+    let mut done: HashMap<PathBuf, u64> = HashMap::new();
+
+    loop {
+        for (path, size) in scan(&options.dir)? {
+            let stable = options.once || sizes.get(&path) == Some(&size);
+            sizes.insert(path.clone(), size);
+            if !stable || done.get(&path) == Some(&size) {
+                continue;
+            }
+
+            progress::info(format!("Importing {}", path.display()));
+            // The counters keep running for the whole watch, so report this file's share
+            let before = COUNTERS.snapshot();
+            let result = import_drop(args, sink.as_mut(), &path);
+            let error = result.as_ref().err().map(|e| format!("{e:#}"));
+            if let Some(error) = &error {
+                progress::error(format!("Failed to import {}: {error}", path.display()));
+            }
+            let run = format!("Import of {}", path.display());
+            let counters = COUNTERS.snapshot().since(&before);
+            notify::send_summary(&args.notify, &args.http, &run, &counters, error.as_deref());
+            if cancel::requested() {
+                progress::info("Watcher stopped");
+                return Ok(());
+            }
+            // Failed files are not retried until they change or the watcher restarts
+            done.insert(path, size);
+        }
+
+        if options.once || cancel::sleep(options.interval) {
+            return Ok(());
+        }
+    }
+}
+
+// Export files directly inside `dir` with their current sizes
+fn scan(dir: &Path) -> AnyhowResult<Vec<(PathBuf, u64)>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let is_export = matches!(
+            path.extension().and_then(|s| s.to_str()),
+            Some("zip" | "gz" | "zst" | "json" | "jsonl")
+        );
+        if is_export && entry.file_type()?.is_file() {
+            files.push((path, entry.metadata()?.len()));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// Stages one dropped file on its own and imports it
+fn import_drop(args: &SyncArgs, sink: &mut dyn EventSink, path: &Path) -> AnyhowResult<()> {
+    let staging = tempdir()?;
+    let compressed_dir = staging.path().join("compressed");
+    fs::create_dir_all(&compressed_dir)?;
+
+    let file_name = path.file_name().ok_or_else(|| anyhow!("Not a file"))?;
+    fs::copy(path, compressed_dir.join(file_name))?;
+
+    import_export(
+        args,
+        sink,
+        &compressed_dir,
+        &staging.path().join("data"),
+        SourceFormat::Amplitude,
+    )?;
+    Ok(())
+}
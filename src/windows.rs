@@ -0,0 +1,263 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result as AnyhowResult};
+use chrono::{NaiveDateTime, TimeDelta};
+use clap::ValueEnum;
+
+use rusqlite::Connection;
+
+use crate::error::Error;
+use crate::sink::EventSink;
+use crate::{cancel, manifest, progress, remove_intermediates, sync_window, SyncArgs};
+
+pub const EXPORT_HOUR_FORMAT: &str = "%Y%m%dT%H";
+
+/// How a requested range is split into Export API requests.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Granularity {
+    /// One request for the whole range
+    #[default]
+    Whole,
+    /// One request per day
+    Day,
+    /// One request per hour
+    Hour,
+    /// Days, dropping to hours once a day is rejected or comes close to --window-max-bytes
+    Auto,
+}
+
+// Syncs start..end (inclusive hours) one window at a time into `sink`
+pub fn sync_range(
+    args: &SyncArgs,
+    sink: &mut dyn EventSink,
+    start: &str,
+    end: &str,
+) -> AnyhowResult<()> {
+    let step = match args.window {
+        Granularity::Whole if args.remove_archives => {
+            sync_one(
+                args,
+                sink,
+                parse_export_hour(start)?,
+                parse_export_hour(end)?,
+            )?;
+            return Ok(());
+        }
+        Granularity::Whole => {
+            sync_window(args, sink, start, end)?;
+            return Ok(());
+        }
+        Granularity::Day | Granularity::Auto => TimeDelta::days(1),
+        Granularity::Hour => TimeDelta::hours(1),
+    };
+    let (start, end) = (parse_export_hour(start)?, parse_export_hour(end)?);
+
+    let mut hourly = false;
+    for (window_start, window_end) in split(start, end, step) {
+        if hourly {
+            sync_hours(args, sink, window_start, window_end)?;
+            continue;
+        }
+
+        match sync_one(args, sink, window_start, window_end) {
+            Ok(size) if args.window == Granularity::Auto && size >= args.window_max_bytes => {
+                progress::info(format!(
+                    "Export for {} was {size} bytes; requesting hours from now on",
+                    window_start.format(EXPORT_HOUR_FORMAT)
+                ));
+                hourly = true;
+            }
+            Ok(_) => {}
+            Err(e) if args.window == Granularity::Auto && !is_cancelled(&e) => {
+                progress::error(format!(
+                    "Daily export for {} failed ({e:#}); retrying hour by hour",
+                    window_start.format(EXPORT_HOUR_FORMAT)
+                ));
+                hourly = true;
+                sync_hours(args, sink, window_start, window_end)?;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+fn sync_hours(
+    args: &SyncArgs,
+    sink: &mut dyn EventSink,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+) -> AnyhowResult<()> {
+    for (hour_start, hour_end) in split(from, to, TimeDelta::hours(1)) {
+        sync_one(args, sink, hour_start, hour_end)?;
+    }
+    Ok(())
+}
+
+// Syncs one window from a clean slate; returns the size of the downloaded archive
+fn sync_one(
+    args: &SyncArgs,
+    sink: &mut dyn EventSink,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> AnyhowResult<u64> {
+    let (start, end) = (
+        start.format(EXPORT_HOUR_FORMAT).to_string(),
+        end.format(EXPORT_HOUR_FORMAT).to_string(),
+    );
+    cancel::check()?;
+    progress::info(format!("Syncing {start}..{end}"));
+
+    remove_intermediates(args, &start, &end)?;
+    let size = match sync_window(args, sink, &start, &end) {
+        Err(e) if cancel::requested() => {
+            // Files imported so far are committed; only the extracted copies go
+            remove_intermediates(args, &start, &end)?;
+            return Err(e);
+        }
+        result => result?,
+    };
+    remove_intermediates(args, &start, &end)?;
+    if args.remove_archives {
+        remove_archive(args, &start, &end)?;
+    }
+    Ok(size)
+}
+
+// Deletes a window's imported archive and notes it in the download manifest
+pub fn remove_archive(args: &SyncArgs, start: &str, end: &str) -> AnyhowResult<()> {
+    let project_id = args.project_id.as_deref().unwrap_or_default();
+    let archive = args.layout.archive(project_id, start, end);
+    // Windows without data never got an archive
+    if archive.exists() {
+        fs::remove_file(&archive)?;
+        let conn = Connection::open(args.layout.db_path(project_id))?;
+        manifest::record_removed(&conn, project_id, &archive)?;
+    }
+    Ok(())
+}
+
+fn is_cancelled(error: &anyhow::Error) -> bool {
+    matches!(Error::find(error), Some(Error::Cancelled))
+}
+
+// Consecutive windows of `step` covering start..=end, the last one possibly shorter
+pub fn split(
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    step: TimeDelta,
+) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+    let mut windows = Vec::new();
+    let mut window_start = start;
+    while window_start <= end {
+        let window_end = (window_start + step - TimeDelta::hours(1)).min(end);
+        windows.push((window_start, window_end));
+        window_start = window_end + TimeDelta::hours(1);
+    }
+    windows
+}
+
+// Hour ranges a sync covers: --start-date..--end-date, each --range and each line of
+// --range-file, merged where they overlap or touch so no hour is exported twice
+pub fn requested_ranges(args: &SyncArgs) -> AnyhowResult<Vec<(NaiveDateTime, NaiveDateTime)>> {
+    let mut ranges = args.ranges.clone();
+    if let (Some(start), Some(end)) = (&args.start_date, &args.end_date) {
+        ranges.push(parse_range(&format!("{start}..{end}"))?);
+    }
+    if let Some(path) = &args.range_file {
+        ranges.extend(read_range_file(path)?);
+    }
+    Ok(merge(ranges))
+}
+
+// START..END in YYYYMMDDTHH, both hours included
+pub fn parse_range(value: &str) -> AnyhowResult<(NaiveDateTime, NaiveDateTime)> {
+    let (start, end) = value
+        .split_once("..")
+        .with_context(|| format!("{value:?} is not a START..END range"))?;
+    let (start, end) = (parse_export_hour(start)?, parse_export_hour(end)?);
+    if start > end {
+        bail!("Range {value:?} ends before it starts");
+    }
+    Ok((start, end))
+}
+
+// One START..END per line; blank lines and # comments are ignored
+fn read_range_file(path: &Path) -> AnyhowResult<Vec<(NaiveDateTime, NaiveDateTime)>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read range file {}", path.display()))?;
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| parse_range(line).with_context(|| format!("In {}", path.display())))
+        .collect()
+}
+
+fn merge(mut ranges: Vec<(NaiveDateTime, NaiveDateTime)>) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+    ranges.sort();
+    let mut merged: Vec<(NaiveDateTime, NaiveDateTime)> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some((_, last_end)) if start <= *last_end + TimeDelta::hours(1) => {
+                *last_end = (*last_end).max(end);
+            }
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+pub fn describe(ranges: &[(NaiveDateTime, NaiveDateTime)]) -> String {
+    ranges
+        .iter()
+        .map(|(start, end)| {
+            format!(
+                "{}..{}",
+                start.format(EXPORT_HOUR_FORMAT),
+                end.format(EXPORT_HOUR_FORMAT)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn parse_export_hour(value: &str) -> AnyhowResult<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(&format!("{value}:00"), &format!("{EXPORT_HOUR_FORMAT}:%M"))
+        .with_context(|| format!("{value:?} is not in YYYYMMDDTHH format"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_into_days_with_partial_last_day() {
+        let hour = |value| parse_export_hour(value).unwrap();
+        let windows = split(hour("20250101T05"), hour("20250102T10"), TimeDelta::days(1));
+        assert_eq!(
+            windows,
+            [
+                (hour("20250101T05"), hour("20250102T04")),
+                (hour("20250102T05"), hour("20250102T10")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merges_overlapping_and_adjacent_ranges() {
+        let range = |value| parse_range(value).unwrap();
+        let merged = merge(vec![
+            range("20250110T00..20250110T23"),
+            range("20250101T00..20250101T12"),
+            range("20250101T06..20250101T20"),
+            range("20250101T21..20250101T23"),
+        ]);
+        assert_eq!(
+            describe(&merged),
+            "20250101T00..20250101T23, 20250110T00..20250110T23"
+        );
+        assert!(parse_range("20250102T00..20250101T00").is_err());
+    }
+}
@@ -0,0 +1,343 @@
+// Runs the built binary against export archives prepared on disk, for behaviour that
+// only shows in exit statuses and files a whole run leaves behind
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use tempfile::tempdir;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+fn event(uuid: &str) -> String {
+    format!(
+        r#"{{ "uuid": "{uuid}", "data": {{"path": "/"}}, "event_time": "2024-01-01 12:00:00.000000", "event_type": "test" }}"#
+    )
+}
+
+// Replaces the workdir's export archive, as a download would, with (file name, lines) entries
+fn write_archive(workdir: &Path, files: &[(&str, &[String])]) {
+    write_named_archive(workdir, "amplitude_export.zip", files);
+}
+
+fn write_named_archive(workdir: &Path, name: &str, files: &[(&str, &[String])]) {
+    let mut zip = ZipWriter::new(File::create(workdir.join(name)).unwrap());
+    for (name, lines) in files {
+        zip.start_file(format!("123/{name}"), SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(lines.join("\n").as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+// The binary run in `workdir` for project 123, with no terminal to answer prompts
+fn command(workdir: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_amplitude-things"));
+    command
+        .current_dir(workdir)
+        .env_clear()
+        .args(["--api-key=key", "--project-id=123"]);
+    command
+}
+
+// A sync of the archive already in `workdir`, without asking before cleaning up
+fn sync(workdir: &Path, extra: &[&str]) -> Output {
+    sync_asking(workdir, &[&["--yes"], extra].concat())
+}
+
+fn sync_asking(workdir: &Path, extra: &[&str]) -> Output {
+    command(workdir)
+        .args([
+            "--start-date=20240101T00",
+            "--end-date=20240101T23",
+            "--skip-download",
+        ])
+        .args(extra)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_fail_on_duplicates_only_counts_new_duplicates() {
+    let workdir = tempdir().unwrap();
+    let fail_on = ["--fail-on", "duplicates"];
+    write_archive(
+        workdir.path(),
+        &[("123_2024-01-01_12#0.json", &[event("uuid-1")])],
+    );
+    assert!(sync(workdir.path(), &fail_on).status.success());
+
+    // Nothing new to import
+    let rerun = sync(workdir.path(), &fail_on);
+    assert!(
+        rerun.status.success(),
+        "{}",
+        String::from_utf8_lossy(&rerun.stderr)
+    );
+
+    // A new file holding an event that is already stored
+    write_archive(
+        workdir.path(),
+        &[
+            ("123_2024-01-01_12#0.json", &[event("uuid-1")]),
+            (
+                "123_2024-01-01_13#0.json",
+                &[event("uuid-1"), event("uuid-2")],
+            ),
+        ],
+    );
+    let duplicate = sync(workdir.path(), &fail_on);
+    assert!(!duplicate.status.success());
+    assert!(String::from_utf8_lossy(&duplicate.stderr).contains("1 duplicates"));
+}
+
+#[test]
+fn test_jsonl_output_keeps_every_window() {
+    let workdir = tempdir().unwrap();
+    for day in ["01", "02"] {
+        write_named_archive(
+            workdir.path(),
+            &format!("export_202401{day}T00.zip"),
+            &[(
+                &format!("123_2024-01-{day}_12#0.json"),
+                &[event(&format!("uuid-{day}"))],
+            )],
+        );
+    }
+    let output = sync(
+        workdir.path(),
+        &[
+            "--range=20240102T00..20240102T23",
+            "--window=day",
+            "--archive-name=export_{start}.zip",
+            "--db-engine=jsonl",
+            "--dsn=events.jsonl",
+        ],
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let lines = std::fs::read_to_string(workdir.path().join("events.jsonl")).unwrap();
+    assert_eq!(lines.lines().count(), 2);
+}
+
+#[test]
+fn test_import_failures_are_errors_not_panics() {
+    let workdir = tempdir().unwrap();
+    write_archive(
+        workdir.path(),
+        &[("123_2024-01-01_12#0.json", &[event("uuid-1")])],
+    );
+    let output = sync(workdir.path(), &["--transform=missing.json"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{stderr}");
+    assert!(stderr.contains("Failed to load transform file"), "{stderr}");
+    assert!(!stderr.contains("panicked"), "{stderr}");
+}
+
+#[test]
+fn test_summary_keeps_the_error_chain_and_exit_status() {
+    let workdir = tempdir().unwrap();
+    write_archive(
+        workdir.path(),
+        &[("123_2024-01-01_12#0.json", &[event("uuid-1")])],
+    );
+    let output = sync(
+        workdir.path(),
+        &["--transform=missing.json", "--summary-json=summary.json"],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    let summary = std::fs::read_to_string(workdir.path().join("summary.json")).unwrap();
+    assert!(
+        summary.contains("Failed to load transform file: "),
+        "{summary}"
+    );
+
+    // A summary that cannot be written does not hide the command's own error
+    let output = sync(
+        workdir.path(),
+        &[
+            "--transform=missing.json",
+            "--summary-json=missing/summary.json",
+        ],
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{stderr}");
+    assert!(
+        stderr.contains("Failed to write --summary-json"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("Error: Failed to load transform file"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn test_daemon_syncs_each_window_and_records_the_range() {
+    let workdir = tempdir().unwrap();
+    write_archive(
+        workdir.path(),
+        &[("123_2024-01-01_12#0.json", &[event("uuid-1")])],
+    );
+    let initial_start = (chrono::Utc::now() - chrono::TimeDelta::days(2))
+        .format("%Y%m%dT%H")
+        .to_string();
+    let output = command(workdir.path())
+        .args([
+            "--skip-download",
+            "--no-clean",
+            "--window=day",
+            "daemon",
+            "--once",
+            &format!("--initial-start={initial_start}"),
+        ])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // One line per day window, after the daemon's own line for the whole range
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.matches("Syncing 2").count() >= 2, "{stdout}");
+
+    let conn = rusqlite::Connection::open(workdir.path().join("amplitude_data.sqlite")).unwrap();
+    let ranges: i64 = conn
+        .query_row("SELECT COUNT(*) FROM synced_ranges", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(ranges, 1);
+}
+
+#[test]
+fn test_leftovers_of_an_earlier_sync_are_removed_without_asking() {
+    let workdir = tempdir().unwrap();
+    write_archive(
+        workdir.path(),
+        &[("123_2024-01-01_12#0.json", &[event("uuid-1")])],
+    );
+    // Leaves the extraction directories behind, as a crashed run would
+    assert!(sync_asking(workdir.path(), &["--no-clean"])
+        .status
+        .success());
+    assert!(workdir.path().join("data").exists());
+
+    let rerun = sync_asking(workdir.path(), &["--window=day"]);
+    assert!(
+        rerun.status.success(),
+        "{}",
+        String::from_utf8_lossy(&rerun.stderr)
+    );
+
+    // Someone else's directory still needs --yes
+    let workdir = tempdir().unwrap();
+    write_archive(
+        workdir.path(),
+        &[("123_2024-01-01_12#0.json", &[event("uuid-1")])],
+    );
+    std::fs::create_dir(workdir.path().join("data")).unwrap();
+    let foreign = sync_asking(workdir.path(), &["--window=day"]);
+    assert!(!foreign.status.success());
+    assert!(String::from_utf8_lossy(&foreign.stderr).contains("pass --yes"));
+}
+
+#[test]
+fn test_commands_read_the_workdir_database() {
+    let dir = tempdir().unwrap();
+    let workdir = dir.path().join("project");
+    std::fs::create_dir(&workdir).unwrap();
+    write_archive(
+        &workdir,
+        &[("123_2024-01-01_12#0.json", &[event("uuid-1")])],
+    );
+    assert!(sync(dir.path(), &["--workdir=project"]).status.success());
+
+    for args in [
+        &["verify-downloads"][..],
+        &["timeline", "--user=someone"],
+        &["db", "user-merges"],
+        &["state", "backup", "--out=state.zip"],
+    ] {
+        let output = command(dir.path())
+            .arg("--workdir=project")
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{args:?}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    assert!(!dir.path().join("amplitude_data.sqlite").exists());
+}
+
+#[test]
+fn test_db_commands_lock_the_database_they_open() {
+    let workdir = tempdir().unwrap();
+    write_archive(
+        workdir.path(),
+        &[("123_2024-01-01_12#0.json", &[event("uuid-1")])],
+    );
+    assert!(sync(workdir.path(), &["--db-name=other.sqlite"])
+        .status
+        .success());
+    std::fs::remove_file(workdir.path().join("other.sqlite.lock")).unwrap();
+
+    let output = command(workdir.path())
+        .args(["db", "compact", "--db=other.sqlite"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(workdir.path().join("other.sqlite.lock").exists());
+    assert!(!workdir.path().join("amplitude_data.sqlite.lock").exists());
+}
+
+#[test]
+fn test_import_rejects_amplitude_only_flags() {
+    let workdir = tempdir().unwrap();
+    for (flag, format) in [
+        ("--shift-time=1d", "mixpanel"),
+        ("--id-prefix=x", "segment"),
+    ] {
+        let output = command(workdir.path())
+            .args([flag, "import", format, "."])
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(1), "{stderr}");
+        let name = flag.split('=').next().unwrap();
+        assert!(
+            stderr.contains(&format!("{name} only applies to Amplitude exports")),
+            "{stderr}"
+        );
+    }
+}
+
+#[test]
+fn test_mirror_locks_its_own_database() {
+    let workdir = tempdir().unwrap();
+    let held = File::create(workdir.path().join("mirror.sqlite.lock")).unwrap();
+    held.try_lock().unwrap();
+
+    let output = command(workdir.path())
+        .args([
+            "mirror",
+            "--start=20240101T00",
+            "--end=20240101T23",
+            "--db=mirror.sqlite",
+        ])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("mirror.sqlite.lock"), "{stderr}");
+}